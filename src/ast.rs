@@ -0,0 +1,568 @@
+use std::fmt::Display;
+
+use crate::diagnostics::Diagnostic;
+use crate::lexer::TToken;
+use crate::span::Span;
+
+// the tree both front-ends produce, parser::program::Program::new from the
+// hand written parser and pest_parser::parse_program from the pest grammar.
+// spans follow the same rules in both so whole programs can be compared
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Program {
+    pub shebang: String,
+    pub body: Vec<Node>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+impl Program {
+    pub fn has_errors(&self) -> bool {
+        !self.diagnostics.is_empty()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Node {
+    Include { var: Include },
+    Func {var: Func},
+    VariableDelclear { var: VariableDelclear },
+    Struct { var: Struct },
+    Enum { var: Enum },
+}
+impl Node {
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Include { var } => var.span,
+            Self::Func { var } => var.span,
+            Self::VariableDelclear { var } => var.span,
+            Self::Struct { var } => var.span,
+            Self::Enum { var } => var.span,
+        }
+    }
+}
+
+// `include "path";`, the path is relative to the including file. span runs
+// from `include` to the semicolon
+#[derive(Debug, PartialEq)]
+pub struct Include {
+    pub path: String,
+    pub span: Span,
+}
+
+// span runs from `fun` to the closing curly of the body
+#[derive(Debug, PartialEq)]
+pub struct Func {
+    pub ident: String,
+    pub args: Vec<Arg>,
+    pub return_type: Type,
+    pub block: Vec<Stmt>,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Arg {
+    pub ident: String,
+    pub kind: Type,
+    pub span: Span,
+}
+
+// `struct name { field type, ... }`, fields are written like arguments.
+// span runs from `struct` to the closing curly
+#[derive(Debug, PartialEq)]
+pub struct Struct {
+    pub ident: String,
+    pub fields: Vec<Arg>,
+    pub span: Span,
+}
+
+// `enum name { variant, variant(type), ... }`, span runs from `enum` to the
+// closing curly
+#[derive(Debug, PartialEq)]
+pub struct Enum {
+    pub ident: String,
+    pub variants: Vec<Variant>,
+    pub span: Span,
+}
+
+// span runs from the name to the closing paren of the payload type
+#[derive(Debug, PartialEq)]
+pub struct Variant {
+    pub ident: String,
+    pub payload: Option<Type>,
+    pub span: Span,
+}
+
+// `@` in front of the name is optional and part of the span.
+// inferred variable types are named "undifiend" and missing return types
+// "void", both with an empty span where the type would have been written.
+// a type of an included file is named `module.name`. `[type; len]` is an
+// array and `[type]` a slice, they have an element and are named like they
+// are written with single spaces
+#[derive(Debug, PartialEq)]
+pub struct Type {
+    pub name: String,
+    pub element: Option<Box<Type>>,
+    // the length of an array, None for a slice
+    pub len: Option<u32>,
+    pub span: Span,
+}
+impl Type {
+    pub fn named(name: impl ToString, span: Span) -> Self {
+        Self { name: name.to_string(), element: None, len: None, span }
+    }
+
+    pub fn array(element: Type, len: Option<u32>, span: Span) -> Self {
+        let name = match len {
+            Some(len) => format!("[{}; {}]",element.name,len),
+            None => format!("[{}]",element.name),
+        };
+        Self { name, element: Some(Box::new(element)), len, span }
+    }
+}
+
+// `name type? (: | :: | =) expr;`, the span starts at the introducing `@`,
+// `static`, `const` or `mut`
+#[derive(Debug, PartialEq)]
+pub struct VariableDelclear {
+    pub is_const: bool,
+    pub is_static: bool,
+    pub ident: String,
+    pub kind: Type,
+    pub init_value: Option<Expr>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AssignOp {
+    Eq,
+    AddEq,
+    SubEq,
+    MultiEq,
+    DivideEq,
+    ModEq,
+    AndEq,
+    OrEq,
+    XorEq,
+}
+impl AssignOp {
+    pub fn get(lit: String) -> Self {
+        match lit.as_str() {
+            "=" => Self::Eq,
+            "+=" => Self::AddEq,
+            "-=" => Self::SubEq,
+            "*=" => Self::MultiEq,
+            "/=" => Self::DivideEq,
+            "%=" => Self::ModEq,
+            "&=" => Self::AndEq,
+            "|=" => Self::OrEq,
+            "^=" => Self::XorEq,
+            _ => unreachable!("Undifiend Operand"),
+        }
+    }
+
+    pub fn from_token(ttype: &TToken) -> Option<Self> {
+        match ttype {
+            TToken::EQ => Some(Self::Eq),
+            TToken::PLUSEQ => Some(Self::AddEq),
+            TToken::SUBEQ => Some(Self::SubEq),
+            TToken::MULTYEQ => Some(Self::MultiEq),
+            TToken::DEVIDEEQ => Some(Self::DivideEq),
+            TToken::MODEQ => Some(Self::ModEq),
+            TToken::ANDEQ => Some(Self::AndEq),
+            TToken::OREQ => Some(Self::OrEq),
+            TToken::XOREQ => Some(Self::XorEq),
+            _ => None,
+        }
+    }
+
+    // the binary operator of a compound assignment, None for `=`
+    pub fn binary_op(&self) -> Option<Op> {
+        match self {
+            Self::Eq => None,
+            Self::AddEq => Some(Op::Add),
+            Self::SubEq => Some(Op::Sub),
+            Self::MultiEq => Some(Op::Multi),
+            Self::DivideEq => Some(Op::Divide),
+            Self::ModEq => Some(Op::Mod),
+            Self::AndEq => Some(Op::And),
+            Self::OrEq => Some(Op::Or),
+            Self::XorEq => Some(Op::Xor),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Stmt {
+    VariableDelclear { var: VariableDelclear },
+    Assign {
+        lhs : Expr,
+        op  : AssignOp,
+        rhs : Expr,
+        span: Span,
+    },
+    Expr {
+        expr: Expr,
+        span: Span,
+    },
+    Return {
+        value: Option<Expr>,
+        span : Span,
+    },
+    Block {
+        body: Vec<Stmt>,
+        span: Span,
+    },
+    // the else branch is either another If or a Block
+    If {
+        cond      : Expr,
+        then_block: Vec<Stmt>,
+        else_block: Option<Box<Stmt>>,
+        span      : Span,
+    },
+    While {
+        cond: Expr,
+        body: Vec<Stmt>,
+        span: Span,
+    },
+    Loop {
+        body: Vec<Stmt>,
+        span: Span,
+    },
+    // for ident from to to { }
+    ForRange {
+        ident: String,
+        from : Expr,
+        to   : Expr,
+        body : Vec<Stmt>,
+        span : Span,
+    },
+    // for ident in iter { }
+    ForIn {
+        ident: String,
+        iter : Expr,
+        body : Vec<Stmt>,
+        span : Span,
+    },
+    Break {
+        span: Span,
+    },
+    Continue {
+        span: Span,
+    },
+    // # expr;
+    DebugPrint {
+        expr: Expr,
+        span: Span,
+    },
+    // match value { pattern => { }, ... }
+    Match {
+        value: Expr,
+        arms : Vec<MatchArm<Vec<Stmt>>>,
+        span : Span,
+    },
+}
+impl Stmt {
+    pub fn span(&self) -> Span {
+        match self {
+            Self::VariableDelclear { var } => var.span,
+            Self::Assign { span, .. } | Self::Expr { span, .. } |
+            Self::Return { span, .. } | Self::Block { span, .. } |
+            Self::If { span, .. } | Self::While { span, .. } | Self::Loop { span, .. } |
+            Self::ForRange { span, .. } | Self::ForIn { span, .. } |
+            Self::Break { span } | Self::Continue { span } | Self::DebugPrint { span, .. } |
+            Self::Match { span, .. } => *span,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Add,
+    Sub,
+    Multi,
+    Divide,
+    Mod,
+    And,
+    Or,
+    Xor,
+    Lsh,
+    Rsh,
+    Eq,
+    NotEq,
+    Less,
+    LessEq,
+    More,
+    MoreEq,
+    AndAnd,
+    OrOr,
+    Not,
+}
+impl Op {
+    pub fn get(lit: String) -> Self{
+        match lit.as_str() {
+            "+" => Self::Add,
+            "-" => Self::Sub,
+            "*" => Self::Multi,
+            "/" => Self::Divide,
+            "%" => Self::Mod,
+            "&" => Self::And,
+            "|" => Self::Or,
+            "^" => Self::Xor,
+            "<<" => Self::Lsh,
+            ">>" => Self::Rsh,
+            "==" => Self::Eq,
+            "!=" => Self::NotEq,
+            "<" => Self::Less,
+            "<=" => Self::LessEq,
+            ">" => Self::More,
+            ">=" => Self::MoreEq,
+            "&&" => Self::AndAnd,
+            "||" => Self::OrOr,
+            "!" => Self::Not,
+            _ => unreachable!("Undifiend Operand"),
+        }
+    }
+
+    pub fn from_token(ttype: &TToken) -> Option<Self> {
+        match ttype {
+            TToken::PLUS => Some(Self::Add),
+            TToken::SUB => Some(Self::Sub),
+            TToken::MULTY => Some(Self::Multi),
+            TToken::DEVIDE => Some(Self::Divide),
+            TToken::MOD => Some(Self::Mod),
+            TToken::AND => Some(Self::And),
+            TToken::OR => Some(Self::Or),
+            TToken::XOR => Some(Self::Xor),
+            TToken::LEFTSHIFT => Some(Self::Lsh),
+            TToken::RIGHTSHIFT => Some(Self::Rsh),
+            TToken::EQEQ => Some(Self::Eq),
+            TToken::NOTEQ => Some(Self::NotEq),
+            TToken::LESS => Some(Self::Less),
+            TToken::LESSEQ => Some(Self::LessEq),
+            TToken::MORE => Some(Self::More),
+            TToken::MOREEQ => Some(Self::MoreEq),
+            TToken::ANDAND => Some(Self::AndAnd),
+            TToken::OROR => Some(Self::OrOr),
+            TToken::NOT => Some(Self::Not),
+            _ => None,
+        }
+    }
+}
+impl Display for Op {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let lit = match self {
+            Self::Add => "+",
+            Self::Sub => "-",
+            Self::Multi => "*",
+            Self::Divide => "/",
+            Self::Mod => "%",
+            Self::And => "&",
+            Self::Or => "|",
+            Self::Xor => "^",
+            Self::Lsh => "<<",
+            Self::Rsh => ">>",
+            Self::Eq => "==",
+            Self::NotEq => "!=",
+            Self::Less => "<",
+            Self::LessEq => "<=",
+            Self::More => ">",
+            Self::MoreEq => ">=",
+            Self::AndAnd => "&&",
+            Self::OrOr => "||",
+            Self::Not => "!",
+        };
+        write!(f,"{}",lit)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+    Int(i64),
+    Float(f64),
+    Str(String),
+    Char(char),
+    Bool(bool),
+}
+impl Literal {
+    // integer `12`, float `1f` `1.5` `1.5f` and binary `1011b2` `17b8` `1fb16` literals
+    pub fn parse_number(lit: &str) -> Option<Self> {
+        let is_digits = |digits: &str| !digits.is_empty() && digits.bytes().all(|c| c.is_ascii_digit());
+        for (suffix, radix) in [("b16", 16), ("b8", 8), ("b2", 2)] {
+            if let Some(digits) = lit.strip_suffix(suffix) {
+                if digits.is_empty() || !digits.bytes().all(|c| (c as char).is_digit(radix)) {
+                    return None;
+                }
+                return i64::from_str_radix(digits, radix).ok().map(Self::Int);
+            }
+        }
+        let float = lit.strip_suffix('f');
+        if float.is_some() || lit.contains('.') {
+            let float = float.unwrap_or(lit);
+            let mut parts = float.splitn(2, '.');
+            if !parts.all(is_digits) {
+                return None;
+            }
+            return float.parse::<f64>().ok().map(Self::Float);
+        }
+        if !is_digits(lit) {
+            return None;
+        }
+        lit.parse::<i64>().ok().map(Self::Int)
+    }
+
+    // a well formed integer literal, decimal or binary, too large for an int
+    pub fn overflows(lit: &str) -> bool {
+        let (digits, radix) = [("b16", 16), ("b8", 8), ("b2", 2)].into_iter()
+            .find_map(|(suffix, radix)| lit.strip_suffix(suffix).map(|digits| (digits, radix)))
+            .unwrap_or((lit, 10));
+        !digits.is_empty() && digits.chars().all(|c| c.is_digit(radix)) && i64::from_str_radix(digits, radix).is_err()
+    }
+}
+impl Display for Literal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Int(value) => write!(f,"{}",value),
+            Self::Float(value) => write!(f,"{:?}",value),
+            Self::Str(value) => write!(f,"{:?}",value),
+            Self::Char(value) => write!(f,"{:?}",value),
+            Self::Bool(value) => write!(f,"{}",value),
+        }
+    }
+}
+
+// parentheses are not kept in the tree, a parenthesised expression has
+// the span of the expression inside them
+#[derive(Debug, PartialEq)]
+pub enum Expr {
+    Literal{
+        value: Literal,
+        span : Span,
+    },
+    Binary{
+        left : Box<Expr>,
+        right: Box<Expr>,
+        op   : Op,
+        span : Span,
+    },
+    Unary {
+        op     : Op,
+        operand: Box<Expr>,
+        span   : Span,
+    },
+    Path {
+        ident: String,
+        span : Span,
+    },
+    // `name { field: value, ... }`, from the name to the closing curly.
+    // like the names of variants and patterns, name can be `module.name`
+    StructLiteral {
+        ident : String,
+        fields: Vec<FieldValue>,
+        span  : Span,
+    },
+    // `base.ident`, from the start of base to the end of ident. also
+    // `module.name` for a variable of an included file, the resolver tells
+    Field {
+        base : Box<Expr>,
+        ident: String,
+        span : Span,
+    },
+    // `name::variant` or `name::variant(payload)`, from the name to the end
+    // of the variant or the closing paren
+    Variant {
+        ident  : String,
+        variant: String,
+        payload: Option<Box<Expr>>,
+        span   : Span,
+    },
+    // `match value { pattern => expr, ... }`, from `match` to the closing curly
+    Match {
+        value: Box<Expr>,
+        arms : Vec<MatchArm<Expr>>,
+        span : Span,
+    },
+    // `[a, b, ...]`, from the opening to the closing bracket
+    Array {
+        elements: Vec<Expr>,
+        span    : Span,
+    },
+    // `base[index]`, from the start of base to the closing bracket
+    Index {
+        base : Box<Expr>,
+        index: Box<Expr>,
+        span : Span,
+    },
+    // `name(arg, ...)`, from the name to the closing paren. name can be
+    // `module.name` for a function of an included file
+    Call {
+        ident: String,
+        args : Vec<Expr>,
+        span : Span,
+    },
+}
+impl Expr {
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Literal { span, .. } | Self::Binary { span, .. } |
+            Self::Unary { span, .. } | Self::Path { span, .. } |
+            Self::StructLiteral { span, .. } | Self::Field { span, .. } |
+            Self::Variant { span, .. } | Self::Match { span, .. } |
+            Self::Array { span, .. } | Self::Index { span, .. } |
+            Self::Call { span, .. } => *span,
+        }
+    }
+
+    // true for what can be assigned to, a variable or a field or element of one
+    pub fn is_place(&self) -> bool {
+        match self {
+            Self::Path { .. } => true,
+            Self::Field { base, .. } | Self::Index { base, .. } => base.is_place(),
+            _ => false,
+        }
+    }
+}
+
+// `ident: value` in a struct literal, from the name to the end of the value
+#[derive(Debug, PartialEq)]
+pub struct FieldValue {
+    pub ident: String,
+    pub value: Expr,
+    pub span : Span,
+}
+impl FieldValue {
+    pub fn ident_span(&self) -> Span {
+        Span::new(self.span.file_id, self.span.start, self.span.start + self.ident.len())
+    }
+}
+
+// `pattern => body`, the body is a block in a match statement and an
+// expression in a match expression. span runs from the pattern to the end
+// of the body
+#[derive(Debug, PartialEq)]
+pub struct MatchArm<T> {
+    pub pattern: Pattern,
+    pub body   : T,
+    pub span   : Span,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Pattern {
+    // `name::variant` or `name::variant(binding)`, from the name to the end
+    // of the variant or the closing paren
+    Variant {
+        ident  : String,
+        variant: String,
+        binding: Option<String>,
+        span   : Span,
+    },
+    // `_`
+    Wildcard {
+        span: Span,
+    },
+}
+impl Pattern {
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Variant { span, .. } | Self::Wildcard { span } => *span,
+        }
+    }
+}
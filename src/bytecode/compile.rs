@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+
+use crate::bytecode::{Bytecode, Function, Global, Instr, encode_value};
+use crate::ir::{Inst, IrFunc, Module, Operand, Place, Terminator};
+use crate::semantic::types::Ty;
+use crate::value::Value;
+
+// compiles a verified module. the registers of a function become its first
+// locals, so the arguments %0..%n are locals 0..n, and its slots follow them
+pub fn compile(module: &Module) -> Bytecode {
+    let funcs = module.funcs.iter().enumerate().map(|(i, func)| (func.name.clone(), i as u32)).collect();
+    let mut compiler = Compiler { bytecode: Bytecode::default(), constants: HashMap::new(), funcs };
+    for global in &module.globals {
+        let value = global.init.clone().unwrap_or_else(|| Value::zero(&global.ty));
+        let init = compiler.constant(value);
+        compiler.bytecode.globals.push(Global { name: global.name.clone(), init });
+    }
+    for func in &module.funcs {
+        let func = compiler.func(func);
+        compiler.bytecode.funcs.push(func);
+    }
+    compiler.bytecode
+}
+
+struct Compiler {
+    bytecode: Bytecode,
+    // encoded value -> index in the pool, each constant is stored once
+    constants: HashMap<Vec<u8>, u32>,
+    // function name -> index, functions keep the order of the module
+    funcs: HashMap<String, u32>,
+}
+impl Compiler {
+    fn constant(&mut self, value: Value) -> u32 {
+        let mut key = Vec::new();
+        encode_value(&mut key, &value);
+        let next = self.bytecode.constants.len() as u32;
+        let index = *self.constants.entry(key).or_insert(next);
+        if index == next {
+            self.bytecode.constants.push(value);
+        }
+        index
+    }
+
+    fn func(&mut self, func: &IrFunc) -> Function {
+        let regs = func.regs.len() as u32;
+        let load = |place: Place| match place {
+            Place::Slot(slot) => Instr::Load(regs + slot as u32),
+            Place::Global(global) => Instr::LoadGlobal(global as u32),
+        };
+        let store = |place: Place| match place {
+            Place::Slot(slot) => Instr::Store(regs + slot as u32),
+            Place::Global(global) => Instr::StoreGlobal(global as u32),
+        };
+        let mut code = Vec::new();
+        // index of the first instruction of every block, jumps hold block ids until the end
+        let mut starts = Vec::new();
+        for (block, data) in func.blocks.iter().enumerate() {
+            starts.push(code.len() as u32);
+            for inst in &data.insts {
+                let result = match inst {
+                    Inst::Copy { dst, src, .. } => {
+                        self.push(&mut code, src);
+                        Some(dst)
+                    },
+                    Inst::Binary { dst, op, lhs, rhs, .. } => {
+                        self.push(&mut code, lhs);
+                        self.push(&mut code, rhs);
+                        code.push(Instr::Binary(*op));
+                        Some(dst)
+                    },
+                    Inst::Unary { dst, op, operand, .. } => {
+                        self.push(&mut code, operand);
+                        code.push(Instr::Unary(*op));
+                        Some(dst)
+                    },
+                    Inst::Load { dst, place, .. } => {
+                        code.push(load(*place));
+                        Some(dst)
+                    },
+                    Inst::Store { place, value } => {
+                        self.push(&mut code, value);
+                        code.push(store(*place));
+                        None
+                    },
+                    Inst::Len { dst, value } => {
+                        self.push(&mut code, value);
+                        code.push(Instr::Len);
+                        Some(dst)
+                    },
+                    Inst::Index { dst, base, index, .. } => {
+                        self.push(&mut code, base);
+                        self.push(&mut code, index);
+                        code.push(Instr::Index);
+                        Some(dst)
+                    },
+                    Inst::Print { value } => {
+                        self.push(&mut code, value);
+                        code.push(Instr::Print);
+                        None
+                    },
+                    Inst::Variant { dst, ty, variant, payload } => {
+                        let Ty::Enum(name) = ty else { unreachable!("the verifier checks variants are enums") };
+                        let template = self.constant(Value::Enum { name: name.clone(), variant: variant.clone(), payload: None });
+                        self.push(&mut code, payload);
+                        code.push(Instr::Wrap(template));
+                        Some(dst)
+                    },
+                    Inst::IsVariant { dst, value, variant } => {
+                        let variant = self.constant(Value::Str(variant.clone()));
+                        self.push(&mut code, value);
+                        code.push(Instr::IsVariant(variant));
+                        Some(dst)
+                    },
+                    Inst::Payload { dst, value, .. } => {
+                        self.push(&mut code, value);
+                        code.push(Instr::Unwrap);
+                        Some(dst)
+                    },
+                    // the arguments are taken from the stack, the result is left on it
+                    Inst::Call { dst, func, args, .. } => {
+                        for arg in args {
+                            self.push(&mut code, arg);
+                        }
+                        code.push(Instr::Call(self.funcs[func]));
+                        dst.as_ref()
+                    },
+                };
+                if let Some(dst) = result {
+                    code.push(Instr::Store(*dst as u32));
+                }
+            }
+            match &data.term {
+                // falling through to the next block needs no jump
+                Some(Terminator::Jump(target)) if *target == block + 1 => {},
+                Some(Terminator::Jump(target)) => code.push(Instr::Jump(*target as u32)),
+                Some(Terminator::Branch { cond, then, otherwise }) => {
+                    self.push(&mut code, cond);
+                    code.push(Instr::JumpIfFalse(*otherwise as u32));
+                    if *then != block + 1 {
+                        code.push(Instr::Jump(*then as u32));
+                    }
+                },
+                Some(Terminator::Return(Some(value))) => {
+                    self.push(&mut code, value);
+                    code.push(Instr::Ret);
+                },
+                Some(Terminator::Return(None)) => code.push(Instr::RetVoid),
+                Some(Terminator::Unreachable) | None => code.push(Instr::Trap),
+            }
+        }
+        for instr in &mut code {
+            if let Instr::Jump(target) | Instr::JumpIfFalse(target) = instr {
+                *target = starts[*target as usize];
+            }
+        }
+        Function {
+            name: func.name.clone(),
+            params: func.params.len() as u32,
+            locals: regs + func.slots.len() as u32,
+            code,
+        }
+    }
+
+    fn push(&mut self, code: &mut Vec<Instr>, operand: &Operand) {
+        code.push(match operand {
+            Operand::Reg(reg) => Instr::Load(*reg as u32),
+            Operand::Const(value) => Instr::Const(self.constant(value.clone())),
+        });
+    }
+}
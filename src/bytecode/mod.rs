@@ -0,0 +1,350 @@
+pub mod compile;
+pub mod vm;
+
+use std::fmt::Display;
+
+use crate::ast::Op;
+use crate::value::Value;
+
+// instructions of a stack machine. every function has numbered locals, its
+// arguments arrive in the first of them. jumps go to an instruction index
+// of the same function
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Instr {
+    // push constants[i]
+    Const(u32),
+    Load(u32),
+    // pops into the local
+    Store(u32),
+    LoadGlobal(u32),
+    StoreGlobal(u32),
+    // pops the right then the left operand
+    Binary(Op),
+    Unary(Op),
+    // number of chars of a string
+    Len,
+    // pops the index then the string, pushes the char
+    Index,
+    Print,
+    Jump(u32),
+    // pops a bool
+    JumpIfFalse(u32),
+    // pops the arguments of funcs[i], pushes what it returns
+    Call(u32),
+    // pops the value returned
+    Ret,
+    RetVoid,
+    // code the type checker proved is never reached
+    Trap,
+    // pops the payload, pushes constants[i], a variant without one, holding it
+    Wrap(u32),
+    // pops an enum value, pushes whether it is the variant named by constants[i]
+    IsVariant(u32),
+    // pops an enum value, pushes its payload
+    Unwrap,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub name: String,
+    pub params: u32,
+    pub locals: u32,
+    pub code: Vec<Instr>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Global {
+    pub name: String,
+    // index into the constant pool
+    pub init: u32,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Bytecode {
+    pub constants: Vec<Value>,
+    pub globals: Vec<Global>,
+    pub funcs: Vec<Function>,
+}
+impl Bytecode {
+    pub fn func(&self, name: &str) -> Option<u32> {
+        self.funcs.iter().position(|func| func.name == name).map(|i| i as u32)
+    }
+}
+
+// a .nbc file, every number little endian:
+//   header     "NBC\0", version u16
+//   constants  count u32, then a tag u8 and the value for each, an enum is
+//              its name, its variant and u8 1 followed by the payload or u8 0
+//   globals    count u32, then name and constant u32 for each
+//   functions  count u32, then name, params u32, locals u32 and
+//              instruction count u32 followed by the instructions
+// names and strings are a length u32 and utf-8 bytes, an instruction is an
+// opcode u8 followed by its operand if it has one
+pub const MAGIC: [u8; 4] = *b"NBC\0";
+pub const VERSION: u16 = 1;
+
+// position of an Op in the file
+const OPS: [Op; 19] = [
+    Op::Add, Op::Sub, Op::Multi, Op::Divide, Op::Mod, Op::And, Op::Or, Op::Xor, Op::Lsh, Op::Rsh,
+    Op::Eq, Op::NotEq, Op::Less, Op::LessEq, Op::More, Op::MoreEq, Op::AndAnd, Op::OrOr, Op::Not,
+];
+
+pub fn encode(bytecode: &Bytecode) -> Vec<u8> {
+    let mut out = MAGIC.to_vec();
+    out.extend(VERSION.to_le_bytes());
+    put_u32(&mut out, bytecode.constants.len());
+    for value in &bytecode.constants {
+        encode_value(&mut out, value);
+    }
+    put_u32(&mut out, bytecode.globals.len());
+    for global in &bytecode.globals {
+        put_str(&mut out, &global.name);
+        put_u32(&mut out, global.init as usize);
+    }
+    put_u32(&mut out, bytecode.funcs.len());
+    for func in &bytecode.funcs {
+        put_str(&mut out, &func.name);
+        put_u32(&mut out, func.params as usize);
+        put_u32(&mut out, func.locals as usize);
+        put_u32(&mut out, func.code.len());
+        for instr in &func.code {
+            let (opcode, operand) = match *instr {
+                Instr::Const(i) => (0, Some(i)),
+                Instr::Load(i) => (1, Some(i)),
+                Instr::Store(i) => (2, Some(i)),
+                Instr::LoadGlobal(i) => (3, Some(i)),
+                Instr::StoreGlobal(i) => (4, Some(i)),
+                Instr::Binary(op) => (5, Some(op_index(op))),
+                Instr::Unary(op) => (6, Some(op_index(op))),
+                Instr::Len => (7, None),
+                Instr::Index => (8, None),
+                Instr::Print => (9, None),
+                Instr::Jump(target) => (10, Some(target)),
+                Instr::JumpIfFalse(target) => (11, Some(target)),
+                Instr::Call(func) => (12, Some(func)),
+                Instr::Ret => (13, None),
+                Instr::RetVoid => (14, None),
+                Instr::Trap => (15, None),
+                Instr::Wrap(i) => (16, Some(i)),
+                Instr::IsVariant(i) => (17, Some(i)),
+                Instr::Unwrap => (18, None),
+            };
+            out.push(opcode);
+            if let Some(operand) = operand {
+                put_u32(&mut out, operand as usize);
+            }
+        }
+    }
+    out
+}
+
+fn encode_value(out: &mut Vec<u8>, value: &Value) {
+    match value {
+        Value::Int(value) => {
+            out.push(0);
+            out.extend(value.to_le_bytes());
+        },
+        Value::Float(value) => {
+            out.push(1);
+            out.extend(value.to_bits().to_le_bytes());
+        },
+        Value::Bool(value) => out.extend([2, *value as u8]),
+        Value::Char(value) => {
+            out.push(3);
+            put_u32(out, *value as usize);
+        },
+        Value::Str(value) => {
+            out.push(4);
+            put_str(out, value);
+        },
+        Value::Struct { .. } | Value::Array(_) => unreachable!("structs and arrays are not lowered to the ir"),
+        Value::Enum { name, variant, payload } => {
+            out.push(5);
+            put_str(out, name);
+            put_str(out, variant);
+            match payload {
+                Some(payload) => {
+                    out.push(1);
+                    encode_value(out, payload);
+                },
+                None => out.push(0),
+            }
+        },
+    }
+}
+
+fn op_index(op: Op) -> u32 {
+    OPS.iter().position(|known| *known == op).unwrap_or_default() as u32
+}
+
+fn put_u32(out: &mut Vec<u8>, value: usize) {
+    out.extend((value as u32).to_le_bytes());
+}
+
+fn put_str(out: &mut Vec<u8>, value: &str) {
+    put_u32(out, value.len());
+    out.extend(value.as_bytes());
+}
+
+// reads a .nbc file and checks every index in it, so the vm can trust them
+pub fn decode(bytes: &[u8]) -> Result<Bytecode, String> {
+    let mut reader = Reader { bytes, at: 0 };
+    if reader.take(4).ok() != Some(&MAGIC[..]) {
+        return Err("not a nemet bytecode file".to_string());
+    }
+    let version = u16::from_le_bytes([reader.u8()?, reader.u8()?]);
+    if version != VERSION {
+        return Err(format!("bytecode version {} is not supported, expected {}",version,VERSION));
+    }
+    let mut bytecode = Bytecode::default();
+    for _ in 0..reader.u32()? {
+        let value = reader.value()?;
+        bytecode.constants.push(value);
+    }
+    for _ in 0..reader.u32()? {
+        bytecode.globals.push(Global { name: reader.str()?, init: reader.u32()? });
+    }
+    for _ in 0..reader.u32()? {
+        let (name, params, locals) = (reader.str()?, reader.u32()?, reader.u32()?);
+        let mut code = Vec::new();
+        for _ in 0..reader.u32()? {
+            let opcode = reader.u8()?;
+            let instr = match opcode {
+                0 => Instr::Const(reader.u32()?),
+                1 => Instr::Load(reader.u32()?),
+                2 => Instr::Store(reader.u32()?),
+                3 => Instr::LoadGlobal(reader.u32()?),
+                4 => Instr::StoreGlobal(reader.u32()?),
+                5 | 6 => {
+                    let op = *OPS.get(reader.u32()? as usize).ok_or("unknown operator")?;
+                    if opcode == 5 { Instr::Binary(op) } else { Instr::Unary(op) }
+                },
+                7 => Instr::Len,
+                8 => Instr::Index,
+                9 => Instr::Print,
+                10 => Instr::Jump(reader.u32()?),
+                11 => Instr::JumpIfFalse(reader.u32()?),
+                12 => Instr::Call(reader.u32()?),
+                13 => Instr::Ret,
+                14 => Instr::RetVoid,
+                15 => Instr::Trap,
+                16 => Instr::Wrap(reader.u32()?),
+                17 => Instr::IsVariant(reader.u32()?),
+                18 => Instr::Unwrap,
+                _ => return Err(format!("unknown opcode {}",opcode)),
+            };
+            code.push(instr);
+        }
+        bytecode.funcs.push(Function { name, params, locals, code });
+    }
+    if reader.at != bytes.len() {
+        return Err("unexpected bytes after the last function".to_string());
+    }
+    check(&bytecode)?;
+    Ok(bytecode)
+}
+
+fn check(bytecode: &Bytecode) -> Result<(), String> {
+    let constants = bytecode.constants.len();
+    for global in &bytecode.globals {
+        if global.init as usize >= constants {
+            return Err(format!("global {}: constant {} does not exist",global.name,global.init));
+        }
+    }
+    for func in &bytecode.funcs {
+        if func.params > func.locals {
+            return Err(format!("{}: has more arguments than locals",func.name));
+        }
+        for (at, instr) in func.code.iter().enumerate() {
+            let valid = match *instr {
+                Instr::Const(i) => (i as usize) < constants,
+                Instr::Load(i) | Instr::Store(i) => i < func.locals,
+                Instr::LoadGlobal(i) | Instr::StoreGlobal(i) => (i as usize) < bytecode.globals.len(),
+                Instr::Jump(target) | Instr::JumpIfFalse(target) => (target as usize) < func.code.len(),
+                Instr::Call(i) => (i as usize) < bytecode.funcs.len(),
+                Instr::Wrap(i) => matches!(bytecode.constants.get(i as usize), Some(Value::Enum { payload: None, .. })),
+                Instr::IsVariant(i) => matches!(bytecode.constants.get(i as usize), Some(Value::Str(_))),
+                _ => true,
+            };
+            if !valid {
+                return Err(format!("{} {}: `{}` refers to something that does not exist",func.name,at,instr));
+            }
+        }
+    }
+    Ok(())
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    at: usize,
+}
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let bytes = self.bytes.get(self.at..self.at + len).ok_or("unexpected end of file")?;
+        self.at += len;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        Ok(self.take(N)?.try_into().unwrap_or([0; N]))
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn str(&mut self) -> Result<String, String> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| "invalid utf-8 in a string".to_string())
+    }
+
+    // a tag and the constant, what encode_value writes
+    fn value(&mut self) -> Result<Value, String> {
+        let value = match self.u8()? {
+            0 => Value::Int(i64::from_le_bytes(self.array()?)),
+            1 => Value::Float(f64::from_bits(u64::from_le_bytes(self.array()?))),
+            2 => Value::Bool(self.u8()? != 0),
+            3 => Value::Char(char::from_u32(self.u32()?).ok_or("invalid char constant")?),
+            4 => Value::Str(self.str()?),
+            5 => {
+                let (name, variant) = (self.str()?, self.str()?);
+                let payload = if self.u8()? != 0 { Some(Box::new(self.value()?)) } else { None };
+                Value::Enum { name, variant, payload }
+            },
+            tag => return Err(format!("unknown constant tag {}",tag)),
+        };
+        Ok(value)
+    }
+}
+
+impl Display for Instr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Const(i) => write!(f,"const {}",i),
+            Self::Load(i) => write!(f,"load {}",i),
+            Self::Store(i) => write!(f,"store {}",i),
+            Self::LoadGlobal(i) => write!(f,"load.global {}",i),
+            Self::StoreGlobal(i) => write!(f,"store.global {}",i),
+            Self::Binary(op) => write!(f,"{}",crate::ir::op_name(*op)),
+            Self::Unary(Op::Sub) => write!(f,"neg"),
+            Self::Unary(Op::Not) => write!(f,"not"),
+            Self::Unary(_) => write!(f,"plus"),
+            Self::Len => write!(f,"len"),
+            Self::Index => write!(f,"index"),
+            Self::Print => write!(f,"print"),
+            Self::Jump(target) => write!(f,"jmp {}",target),
+            Self::JumpIfFalse(target) => write!(f,"jmp.false {}",target),
+            Self::Call(func) => write!(f,"call {}",func),
+            Self::Ret => write!(f,"ret"),
+            Self::RetVoid => write!(f,"ret.void"),
+            Self::Trap => write!(f,"trap"),
+            Self::Wrap(i) => write!(f,"wrap {}",i),
+            Self::IsVariant(i) => write!(f,"isvariant {}",i),
+            Self::Unwrap => write!(f,"unwrap"),
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use std::io::Write;
+
+use crate::bytecode::{Bytecode, Instr};
+use crate::ir::INIT;
+use crate::value::Value;
+
+// calls deeper than this are reported instead of exhausting memory
+pub const MAX_FRAMES: usize = 100_000;
+
+// runs `.init` then `main` of decoded or compiled bytecode, returns what main returned
+pub fn run(bytecode: &Bytecode, out: impl Write) -> Result<Option<Value>, String> {
+    let main = bytecode.func("main").ok_or("there is no `main` function to run")?;
+    if bytecode.funcs[main as usize].params != 0 {
+        return Err("`main` cannot take arguments".to_string());
+    }
+    let mut vm = Vm::new(bytecode, out);
+    if let Some(init) = bytecode.func(INIT) {
+        vm.call(init, Vec::new())?;
+    }
+    vm.call(main, Vec::new())
+}
+
+struct Frame {
+    func: usize,
+    pc: usize,
+    // where its locals start in Vm::locals
+    base: usize,
+}
+
+pub struct Vm<'a, W: Write> {
+    bytecode: &'a Bytecode,
+    globals: Vec<Value>,
+    // operands of every frame
+    stack: Vec<Value>,
+    // locals of every frame
+    locals: Vec<Value>,
+    frames: Vec<Frame>,
+    out: W,
+}
+
+impl<'a, W: Write> Vm<'a, W> {
+    pub fn new(bytecode: &'a Bytecode, out: W) -> Self {
+        let globals = bytecode.globals.iter().map(|global| bytecode.constants[global.init as usize].clone()).collect();
+        Self { bytecode, globals, stack: Vec::new(), locals: Vec::new(), frames: Vec::new(), out }
+    }
+
+    pub fn call(&mut self, func: u32, args: Vec<Value>) -> Result<Option<Value>, String> {
+        let depth = self.frames.len();
+        self.stack.extend(args);
+        self.enter(func)?;
+        loop {
+            let frame = self.frames.last_mut().unwrap();
+            let instr = self.bytecode.funcs[frame.func].code.get(frame.pc).copied();
+            frame.pc += 1;
+            let result = match instr {
+                Some(instr) => self.step(instr),
+                None => Err("the function ends without returning".to_string()),
+            };
+            match result {
+                Ok(Some(value)) if self.frames.len() == depth => return Ok(value),
+                Ok(Some(Some(value))) => self.stack.push(value),
+                Ok(_) => {},
+                Err(msg) => {
+                    let frame = self.frames.last().unwrap();
+                    let msg = format!("{} in `{}` at instruction {}",msg,self.bytecode.funcs[frame.func].name,frame.pc - 1);
+                    if let Some(frame) = self.frames.get(depth) {
+                        self.locals.truncate(frame.base);
+                    }
+                    self.frames.truncate(depth);
+                    return Err(msg);
+                },
+            }
+        }
+    }
+
+    // a frame for func, taking its arguments from the stack
+    fn enter(&mut self, func: u32) -> Result<(), String> {
+        if self.frames.len() >= MAX_FRAMES {
+            return Err("too many nested calls".to_string());
+        }
+        let function = &self.bytecode.funcs[func as usize];
+        let params = function.params as usize;
+        if self.stack.len() < params {
+            return Err(format!("`{}` is missing arguments",function.name));
+        }
+        let base = self.locals.len();
+        self.locals.resize(base + function.locals as usize, Value::Int(0));
+        for (i, arg) in self.stack.drain(self.stack.len() - params..).enumerate() {
+            self.locals[base + i] = arg;
+        }
+        self.frames.push(Frame { func: func as usize, pc: 0, base });
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Result<Value, String> {
+        self.stack.pop().ok_or("the stack is empty".to_string())
+    }
+
+    // runs one instruction of the top frame, Some when it returned from it
+    fn step(&mut self, instr: Instr) -> Result<Option<Option<Value>>, String> {
+        let base = self.frames.last().map_or(0, |frame| frame.base);
+        match instr {
+            Instr::Const(i) => self.stack.push(self.bytecode.constants[i as usize].clone()),
+            Instr::Load(i) => self.stack.push(self.locals[base + i as usize].clone()),
+            Instr::Store(i) => self.locals[base + i as usize] = self.pop()?,
+            Instr::LoadGlobal(i) => self.stack.push(self.globals[i as usize].clone()),
+            Instr::StoreGlobal(i) => self.globals[i as usize] = self.pop()?,
+            Instr::Binary(op) => {
+                let right = self.pop()?;
+                let left = self.pop()?;
+                self.stack.push(Value::binary(op, &left, &right)?);
+            },
+            Instr::Unary(op) => {
+                let operand = self.pop()?;
+                self.stack.push(Value::unary(op, &operand)?);
+            },
+            Instr::Len => match self.pop()? {
+                Value::Str(value) => self.stack.push(Value::Int(value.chars().count() as i64)),
+                value => return Err(format!("cannot take the length of {}",value)),
+            },
+            Instr::Index => {
+                let index = self.pop()?;
+                let value = self.pop()?;
+                let (Value::Str(value), Value::Int(index)) = (&value, &index) else {
+                    return Err(format!("cannot index {} with {}",value,index));
+                };
+                let char = usize::try_from(*index).ok().and_then(|i| value.chars().nth(i));
+                let char = char.ok_or_else(|| format!("index {} is out of bounds for a string of length {}",index,value.chars().count()))?;
+                self.stack.push(Value::Char(char));
+            },
+            Instr::Print => {
+                let value = self.pop()?;
+                writeln!(self.out, "{}", value).map_err(|e| format!("cannot print: {}",e))?;
+            },
+            Instr::Jump(target) => self.frames.last_mut().unwrap().pc = target as usize,
+            Instr::JumpIfFalse(target) => match self.pop()? {
+                Value::Bool(true) => {},
+                Value::Bool(false) => self.frames.last_mut().unwrap().pc = target as usize,
+                value => return Err(format!("cannot branch on {}",value)),
+            },
+            Instr::Call(func) => self.enter(func)?,
+            Instr::Ret | Instr::RetVoid => {
+                let value = if instr == Instr::Ret { Some(self.pop()?) } else { None };
+                let frame = self.frames.pop().unwrap();
+                self.locals.truncate(frame.base);
+                return Ok(Some(value));
+            },
+            Instr::Trap => return Err("reached code that cannot be reached".to_string()),
+            Instr::Wrap(i) => {
+                let payload = self.pop()?;
+                let mut value = self.bytecode.constants[i as usize].clone();
+                if let Value::Enum { payload: slot, .. } = &mut value {
+                    *slot = Some(Box::new(payload));
+                }
+                self.stack.push(value);
+            },
+            Instr::IsVariant(i) => match (self.pop()?, &self.bytecode.constants[i as usize]) {
+                (Value::Enum { variant, .. }, Value::Str(expected)) => self.stack.push(Value::Bool(variant == *expected)),
+                (value, _) => return Err(format!("{} is not an enum",value)),
+            },
+            Instr::Unwrap => match self.pop()? {
+                Value::Enum { payload: Some(payload), .. } => self.stack.push(*payload),
+                value => return Err(format!("{} has no payload",value)),
+            },
+        }
+        Ok(None)
+    }
+}
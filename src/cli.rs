@@ -0,0 +1,303 @@
+use std::fs;
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+
+use crate::ast::Program;
+use crate::bytecode;
+use crate::codegen;
+use crate::diagnostics::{Diagnostic, Severity};
+use crate::frontend::{Frontend, cross_check_file};
+use crate::lexer::{Lexer, TToken};
+use crate::interpreter;
+use crate::ir;
+use crate::modules;
+use crate::repl;
+use crate::semantic::{self, Analysis};
+use crate::span::SourceMap;
+
+pub const USAGE: &str = "\
+usage: nemet <command> [--frontend=manual|pest|both] <file>
+       nemet repl
+
+commands:
+    lex     print the tokens of the file, always with the hand written lexer
+    parse   print the syntax tree
+    check   run every analysis and report the diagnostics
+    run     check the file and run its `main` with the interpreter
+    ir      print the intermediate representation of the file
+    vm      compile the file to bytecode in `<file>.nbc` and run it, a .nbc file is run as is
+    build   compile the file to an x86-64 linux executable named after it, with `as` and `ld`
+    repl    read, check and run input interactively, `:help` lists its commands
+
+--frontend picks the parser, `both` parses with both and checks they agree
+
+exit codes: 0 success, 1 the program has errors, 2 bad usage or unreadable file
+";
+
+// the program has errors
+pub const EXIT_ERROR: i32 = 1;
+// bad arguments or a file that can not be read
+pub const EXIT_USAGE: i32 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command {
+    Lex,
+    Parse,
+    Check,
+    Run,
+    Ir,
+    Build,
+    Vm,
+    Repl,
+}
+impl Command {
+    pub fn get(name: &str) -> Option<Self> {
+        match name {
+            "lex" => Some(Self::Lex),
+            "parse" => Some(Self::Parse),
+            "check" => Some(Self::Check),
+            "run" => Some(Self::Run),
+            "ir" => Some(Self::Ir),
+            "build" => Some(Self::Build),
+            "vm" => Some(Self::Vm),
+            "repl" => Some(Self::Repl),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Options {
+    pub command: Command,
+    pub file_path: String,
+    pub frontend: Frontend,
+    // parse with both front-ends, set by --frontend=both
+    pub cross_check: bool,
+}
+impl Options {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut command = None;
+        let mut file_path = None;
+        let mut frontend = Frontend::Manual;
+        let mut cross_check = false;
+        for arg in args {
+            if let Some(name) = arg.strip_prefix("--frontend=") {
+                cross_check = name == "both";
+                if !cross_check {
+                    frontend = Frontend::get(name).ok_or_else(|| format!("unknown front-end `{}`", name))?;
+                }
+            }else if arg.starts_with('-') {
+                return Err(format!("unknown option `{}`", arg));
+            }else if command.is_none() {
+                command = Some(Command::get(&arg).ok_or_else(|| format!("unknown command `{}`", arg))?);
+            }else if file_path.is_none() && command != Some(Command::Repl) {
+                file_path = Some(arg);
+            }else {
+                return Err(format!("unexpected argument `{}`", arg));
+            }
+        }
+        let command = command.ok_or("missing command")?;
+        // the repl reads standard input instead of a file
+        let file_path = match command {
+            Command::Repl => String::new(),
+            _ => file_path.ok_or("missing file")?,
+        };
+        Ok(Self { command, file_path, frontend, cross_check })
+    }
+}
+
+// entry point of the binary, returns the exit code
+pub fn main(args: impl IntoIterator<Item = String>) -> i32 {
+    let args: Vec<String> = args.into_iter().collect();
+    if args.iter().any(|arg| arg == "--help" || arg == "-h" || arg == "help") {
+        print!("{}", USAGE);
+        return 0;
+    }
+    let options = match Options::parse(args) {
+        Ok(options) => options,
+        Err(msg) => {
+            eprint!("error: {}\n\n{}", msg, USAGE);
+            return EXIT_USAGE;
+        },
+    };
+    let color = std::io::stderr().is_terminal();
+    run(&options, &mut std::io::stdout(), &mut std::io::stderr(), color)
+}
+
+pub fn run(options: &Options, out: &mut (impl Write + Send), err: &mut impl Write, color: bool) -> i32 {
+    if options.command == Command::Repl {
+        return repl::repl(std::io::stdin().lock(), out, err, color);
+    }
+    if options.command == Command::Vm && options.file_path.ends_with(".nbc") {
+        let bytes = match fs::read(&options.file_path) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                let _ = writeln!(err, "error: could not read `{}`: {}", options.file_path, e);
+                return EXIT_USAGE;
+            },
+        };
+        return match bytecode::decode(&bytes) {
+            Ok(bytecode) => run_bytecode(&bytecode, out, err),
+            Err(msg) => {
+                let _ = writeln!(err, "error: `{}` is not valid bytecode: {}", options.file_path, msg);
+                EXIT_ERROR
+            },
+        };
+    }
+    let source = match fs::read_to_string(&options.file_path) {
+        Ok(source) => source,
+        Err(e) => {
+            let _ = writeln!(err, "error: could not read `{}`: {}", options.file_path, e);
+            return EXIT_USAGE;
+        },
+    };
+    // a diagnostic is shown with the source of the file it is in
+    let mut report = |sources: &SourceMap, diagnostics: &[Diagnostic]| {
+        for diagnostic in diagnostics {
+            let text = sources.find(&diagnostic.file_path).map_or(source.as_str(), |file| &file.source);
+            let _ = write!(err, "{}", diagnostic.render(text, color));
+        }
+        diagnostics.iter().any(|d| d.severity == Severity::Error)
+    };
+    match options.command {
+        Command::Lex => {
+            let mut lexer = Lexer::from_source(&options.file_path, &source);
+            let mut diagnostics = Vec::new();
+            loop {
+                match lexer.next_token() {
+                    Ok(token) if token.ttype == TToken::EOF => break,
+                    Ok(token) => { let _ = writeln!(out, "{}", token); },
+                    Err(e) => diagnostics.push(Diagnostic::from(e)),
+                }
+            }
+            exit_code(report(&SourceMap::new(), &diagnostics))
+        },
+        Command::Parse => {
+            let mut sources = SourceMap::new();
+            let program = parse(options, &source, &mut sources);
+            if report(&sources, &program.diagnostics) {
+                return EXIT_ERROR;
+            }
+            let _ = writeln!(out, "{:#?}", program.body);
+            0
+        },
+        Command::Check => {
+            let (program, sources, _) = check(options, &source);
+            exit_code(report(&sources, &program.diagnostics))
+        },
+        Command::Run => {
+            let (program, sources, analysis) = check(options, &source);
+            if report(&sources, &program.diagnostics) {
+                return EXIT_ERROR;
+            }
+            match interpreter::run(&program, &analysis, &sources, &mut *out) {
+                Ok(_) => 0,
+                Err(diagnostic) => exit_code(report(&sources, &[diagnostic])),
+            }
+        },
+        Command::Ir => {
+            let (program, sources, analysis) = check_lowerable(options, &source);
+            if report(&sources, &program.diagnostics) {
+                return EXIT_ERROR;
+            }
+            let module = ir::lower::lower(&program, &analysis);
+            let _ = write!(out, "{}", module);
+            match ir::verify::verify(&module) {
+                Ok(()) => 0,
+                Err(errors) => {
+                    for error in errors {
+                        let _ = writeln!(err, "error: invalid ir: {}", error);
+                    }
+                    EXIT_ERROR
+                },
+            }
+        },
+        Command::Build => {
+            let (program, sources, analysis) = check_lowerable(options, &source);
+            if report(&sources, &program.diagnostics) {
+                return EXIT_ERROR;
+            }
+            let module = ir::lower::lower(&program, &analysis);
+            let output = output_path(&options.file_path);
+            match codegen::x86_64::emit(&module).and_then(|asm| codegen::build(&asm, &output)) {
+                Ok(()) => 0,
+                Err(msg) => {
+                    let _ = writeln!(err, "error: {}", msg);
+                    EXIT_ERROR
+                },
+            }
+        },
+        Command::Vm => {
+            let (program, sources, analysis) = check_lowerable(options, &source);
+            if report(&sources, &program.diagnostics) {
+                return EXIT_ERROR;
+            }
+            let bytecode = bytecode::compile::compile(&ir::lower::lower(&program, &analysis));
+            let path = Path::new(&options.file_path).with_extension("nbc");
+            if let Err(e) = fs::write(&path, bytecode::encode(&bytecode)) {
+                let _ = writeln!(err, "error: could not write `{}`: {}", path.display(), e);
+                return EXIT_ERROR;
+            }
+            run_bytecode(&bytecode, out, err)
+        },
+        Command::Repl => unreachable!("the repl does not read a file"),
+    }
+}
+
+fn run_bytecode(bytecode: &bytecode::Bytecode, out: &mut impl Write, err: &mut impl Write) -> i32 {
+    match bytecode::vm::run(bytecode, &mut *out) {
+        Ok(_) => 0,
+        Err(msg) => {
+            let _ = writeln!(err, "error: {}", msg);
+            EXIT_ERROR
+        },
+    }
+}
+
+// `dir/main.nmt` builds `dir/main`
+fn output_path(file_path: &str) -> std::path::PathBuf {
+    let path = Path::new(file_path);
+    let output = path.with_extension("");
+    if output == path { path.with_extension("out") } else { output }
+}
+
+// check, then what the ir cannot express yet is reported like any other error
+fn check_lowerable(options: &Options, source: &str) -> (Program, SourceMap, Analysis) {
+    let (mut program, sources, analysis) = check(options, source);
+    if !program.has_errors() {
+        program.diagnostics.extend(ir::lower::unsupported(&program, &analysis, &sources));
+    }
+    (program, sources, analysis)
+}
+
+fn exit_code(has_errors: bool) -> i32 {
+    if has_errors { EXIT_ERROR } else { 0 }
+}
+
+// the file and every file it includes, each registered in sources
+fn parse(options: &Options, source: &str, sources: &mut SourceMap) -> Program {
+    modules::load(&options.file_path, source, sources, |path, source, file_id| {
+        if !options.cross_check {
+            return options.frontend.parse_file(path, source, file_id);
+        }
+        cross_check_file(path, source, file_id).unwrap_or_else(|diagnostics| Program {
+            shebang: String::new(),
+            body: Vec::new(),
+            diagnostics,
+        })
+    })
+}
+
+// parsing plus every analysis pass, diagnostics end up in the program.
+// analysis only runs on programs that parsed without errors
+fn check(options: &Options, source: &str) -> (Program, SourceMap, Analysis) {
+    let mut sources = SourceMap::new();
+    let mut program = parse(options, source, &mut sources);
+    if program.has_errors() {
+        return (program, sources, Analysis::default());
+    }
+    let mut diagnostics = Vec::new();
+    let analysis = semantic::analyze(&program, &sources, &mut diagnostics);
+    program.diagnostics.append(&mut diagnostics);
+    (program, sources, analysis)
+}
@@ -0,0 +1,27 @@
+pub mod x86_64;
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+// assembles and links the output of x86_64::emit into the executable `output`
+// with the GNU assembler and linker found on the PATH. the assembly is kept
+// next to it as `output.s`
+pub fn build(asm: &str, output: &Path) -> Result<(), String> {
+    let source = output.with_extension("s");
+    let object = output.with_extension("o");
+    fs::write(&source, asm).map_err(|e| format!("could not write `{}`: {}",source.display(),e))?;
+    let result = tool("as", &[Path::new("-o"), &object, &source])
+        .and_then(|_| tool("ld", &[Path::new("-o"), output, &object]));
+    let _ = fs::remove_file(&object);
+    result
+}
+
+fn tool(name: &str, args: &[&Path]) -> Result<(), String> {
+    let result = Command::new(name).args(args).output().map_err(|e| format!("could not run `{}`: {}",name,e))?;
+    if result.status.success() {
+        Ok(())
+    }else {
+        Err(format!("`{}` failed:\n{}",name,String::from_utf8_lossy(&result.stderr).trim_end()))
+    }
+}
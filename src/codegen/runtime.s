@@ -0,0 +1,585 @@
+# runtime of programs built by nemet, appended to every generated file.
+# no libc, output goes straight to the write syscall.
+# strings are a quad with the number of chars followed by one long per char.
+# an enum value points at three quads, the string `Enum::Variant`, which two
+# values of the same variant share, the payload and the kind of the payload:
+# 0 none, 1 int, 2 float, 3 bool, 4 char, 5 str and 6 enum
+
+.text
+
+# rdi = pointer, rsi = length, to stdout
+nemet_write:
+    mov rdx, rsi
+    mov rsi, rdi
+    mov edi, 1
+    mov eax, 1
+    syscall
+    ret
+
+nemet_newline:
+    lea rdi, [rip + nemet_nl]
+    mov esi, 1
+    jmp nemet_write
+
+# rax = value, rdi = one past the end of a buffer.
+# writes the decimal digits in front of rdi, returns rdi = first digit
+nemet_fmt_u64:
+    mov rcx, 10
+1:  xor edx, edx
+    div rcx
+    add dl, '0'
+    dec rdi
+    mov [rdi], dl
+    test rax, rax
+    jnz 1b
+    ret
+
+# edi = char, rsi = buffer. writes its utf-8 bytes, returns rax = how many
+nemet_utf8:
+    cmp edi, 0x80
+    jae 1f
+    mov [rsi], dil
+    mov eax, 1
+    ret
+1:  cmp edi, 0x800
+    jae 2f
+    mov eax, edi
+    shr eax, 6
+    or al, 0xC0
+    mov [rsi], al
+    mov eax, edi
+    and al, 0x3F
+    or al, 0x80
+    mov [rsi + 1], al
+    mov eax, 2
+    ret
+2:  cmp edi, 0x10000
+    jae 3f
+    mov eax, edi
+    shr eax, 12
+    or al, 0xE0
+    mov [rsi], al
+    mov eax, edi
+    shr eax, 6
+    and al, 0x3F
+    or al, 0x80
+    mov [rsi + 1], al
+    mov eax, edi
+    and al, 0x3F
+    or al, 0x80
+    mov [rsi + 2], al
+    mov eax, 3
+    ret
+3:  mov eax, edi
+    shr eax, 18
+    or al, 0xF0
+    mov [rsi], al
+    mov eax, edi
+    shr eax, 12
+    and al, 0x3F
+    or al, 0x80
+    mov [rsi + 1], al
+    mov eax, edi
+    shr eax, 6
+    and al, 0x3F
+    or al, 0x80
+    mov [rsi + 2], al
+    mov eax, edi
+    and al, 0x3F
+    or al, 0x80
+    mov [rsi + 3], al
+    mov eax, 4
+    ret
+
+# the nemet_print_* functions print rdi and a newline, what `# expr;` does.
+# the nemet_show_* functions print it without one
+nemet_print_int:
+    call nemet_show_int
+    jmp nemet_newline
+
+nemet_print_bool:
+    call nemet_show_bool
+    jmp nemet_newline
+
+nemet_print_char:
+    call nemet_show_char
+    jmp nemet_newline
+
+nemet_print_str:
+    call nemet_show_str
+    jmp nemet_newline
+
+nemet_print_float:
+    call nemet_show_float
+    jmp nemet_newline
+
+nemet_print_enum:
+    call nemet_show_enum
+    jmp nemet_newline
+
+nemet_show_int:
+    push rbp
+    mov rbp, rsp
+    sub rsp, 32
+    mov r8, rdi
+    lea rdi, [rbp]
+    mov rax, r8
+    test rax, rax
+    jns 1f
+    neg rax
+1:  call nemet_fmt_u64
+    test r8, r8
+    jns 2f
+    dec rdi
+    mov byte ptr [rdi], '-'
+2:  lea rsi, [rbp]
+    sub rsi, rdi
+    call nemet_write
+    leave
+    ret
+
+nemet_show_bool:
+    test rdi, rdi
+    jz 1f
+    lea rdi, [rip + nemet_true]
+    mov esi, 4
+    jmp nemet_write
+1:  lea rdi, [rip + nemet_false]
+    mov esi, 5
+    jmp nemet_write
+
+nemet_show_char:
+    push rbp
+    mov rbp, rsp
+    sub rsp, 16
+    lea rsi, [rbp - 16]
+    call nemet_utf8
+    lea rdi, [rbp - 16]
+    mov rsi, rax
+    call nemet_write
+    leave
+    ret
+
+nemet_show_str:
+    push rbp
+    mov rbp, rsp
+    push rbx
+    push r12
+    push r13
+    sub rsp, 24
+    mov rbx, rdi
+    mov r12, [rdi]
+    xor r13d, r13d
+1:  cmp r13, r12
+    jae 2f
+    mov edi, dword ptr [rbx + r13 * 4 + 8]
+    mov rsi, rsp
+    call nemet_utf8
+    mov rdi, rsp
+    mov rsi, rax
+    call nemet_write
+    inc r13
+    jmp 1b
+2:  add rsp, 24
+    pop r13
+    pop r12
+    pop rbx
+    pop rbp
+    ret
+
+# rdi = bits of a double. prints at most 6 decimals, the interpreter prints
+# the shortest text that reads back as the same double
+nemet_show_float:
+    push rbp
+    mov rbp, rsp
+    sub rsp, 336
+    movq xmm0, rdi
+    ucomisd xmm0, xmm0
+    jp 7f
+    mov r9, rdi
+    btr rdi, 63
+    movabs rax, 0x7FF0000000000000
+    cmp rdi, rax
+    je 8f
+    movq xmm0, rdi
+    lea rdi, [rbp]
+    ucomisd xmm0, [rip + nemet_two_63]
+    jae 6f
+    # the integer part and the decimals rounded to 6 digits apart, so a
+    # large integer part cannot overflow
+    cvttsd2si r8, xmm0
+    cvtsi2sd xmm1, r8
+    subsd xmm0, xmm1
+    mulsd xmm0, [rip + nemet_million]
+    cvtsd2si rax, xmm0
+    cmp rax, 1000000
+    jb 1f
+    inc r8
+    xor eax, eax
+    # drop trailing zeros of the 6 decimals, keeping at least one
+1:  mov ecx, 6
+    mov r11, 10
+2:  cmp ecx, 1
+    je 4f
+    mov rsi, rax
+    xor edx, edx
+    div r11
+    test rdx, rdx
+    jnz 3f
+    dec ecx
+    jmp 2b
+3:  mov rax, rsi
+4:  xor edx, edx
+    div r11
+    add dl, '0'
+    dec rdi
+    mov [rdi], dl
+    dec ecx
+    jnz 4b
+    dec rdi
+    mov byte ptr [rdi], '.'
+    mov rax, r8
+10: call nemet_fmt_u64
+    test r9, r9
+    jns 5f
+    dec rdi
+    mov byte ptr [rdi], '-'
+5:  lea rsi, [rbp]
+    sub rsi, rdi
+    call nemet_write
+    leave
+    ret
+    # from 2^63 on a double is an integer that does not fit a register, it
+    # is divided by 10 until it does and a zero printed for every division
+6:  dec rdi
+    mov byte ptr [rdi], '0'
+    dec rdi
+    mov byte ptr [rdi], '.'
+11: divsd xmm0, [rip + nemet_ten]
+    dec rdi
+    mov byte ptr [rdi], '0'
+    ucomisd xmm0, [rip + nemet_two_63]
+    jae 11b
+    cvttsd2si rax, xmm0
+    jmp 10b
+7:  lea rdi, [rip + nemet_nan]
+    mov esi, 3
+    call nemet_write
+    leave
+    ret
+8:  lea rdi, [rip + nemet_inf]
+    mov esi, 3
+    test r9, r9
+    jns 9f
+    lea rdi, [rip + nemet_neg_inf]
+    mov esi, 4
+9:  call nemet_write
+    leave
+    ret
+
+# the payload of an enum is shown in parentheses, with strings and chars
+# quoted the way the interpreter quotes them
+nemet_show_enum:
+    push rbx
+    mov rbx, rdi
+    mov rdi, [rbx]
+    call nemet_show_str
+    cmp qword ptr [rbx + 16], 0
+    je 9f
+    lea rdi, [rip + nemet_open]
+    mov esi, 1
+    call nemet_write
+    mov rdi, [rbx + 8]
+    mov rax, [rbx + 16]
+    cmp rax, 1
+    jne 2f
+    call nemet_show_int
+    jmp 8f
+2:  cmp rax, 2
+    jne 3f
+    call nemet_show_float
+    jmp 8f
+3:  cmp rax, 3
+    jne 4f
+    call nemet_show_bool
+    jmp 8f
+4:  cmp rax, 4
+    jne 5f
+    call nemet_show_quoted_char
+    jmp 8f
+5:  cmp rax, 5
+    jne 6f
+    call nemet_show_quoted_str
+    jmp 8f
+6:  call nemet_show_enum
+8:  lea rdi, [rip + nemet_close]
+    mov esi, 1
+    call nemet_write
+9:  pop rbx
+    ret
+
+nemet_show_quoted_char:
+    push rbx
+    mov rbx, rdi
+    lea rdi, [rip + nemet_squote]
+    mov esi, 1
+    call nemet_write
+    mov edi, ebx
+    mov esi, 39
+    call nemet_show_escaped
+    lea rdi, [rip + nemet_squote]
+    mov esi, 1
+    call nemet_write
+    pop rbx
+    ret
+
+nemet_show_quoted_str:
+    push rbx
+    push r12
+    push r13
+    mov rbx, rdi
+    mov r12, [rdi]
+    xor r13d, r13d
+    lea rdi, [rip + nemet_dquote]
+    mov esi, 1
+    call nemet_write
+1:  cmp r13, r12
+    jae 2f
+    mov edi, dword ptr [rbx + r13 * 4 + 8]
+    mov esi, 34
+    call nemet_show_escaped
+    inc r13
+    jmp 1b
+2:  lea rdi, [rip + nemet_dquote]
+    mov esi, 1
+    call nemet_write
+    pop r13
+    pop r12
+    pop rbx
+    ret
+
+# edi = char, esi = the quote around it. the escapes of literals, the quote
+# itself and nul get a backslash, everything else is written as it is
+nemet_show_escaped:
+    push rbp
+    mov rbp, rsp
+    sub rsp, 16
+    mov byte ptr [rbp - 16], 92
+    mov eax, 110
+    cmp edi, 10
+    je 1f
+    mov eax, 116
+    cmp edi, 9
+    je 1f
+    mov eax, 114
+    cmp edi, 13
+    je 1f
+    mov eax, 48
+    test edi, edi
+    jz 1f
+    mov eax, edi
+    cmp edi, 92
+    je 1f
+    cmp edi, esi
+    je 1f
+    lea rsi, [rbp - 16]
+    call nemet_utf8
+    lea rdi, [rbp - 16]
+    mov rsi, rax
+    jmp 2f
+1:  mov [rbp - 15], al
+    lea rdi, [rbp - 16]
+    mov esi, 2
+2:  call nemet_write
+    leave
+    ret
+
+# rdi, rsi = enums, returns rax = 1 when they are the same variant with
+# equal payloads, 0 otherwise
+nemet_enum_eq:
+    mov rax, [rdi]
+    cmp rax, [rsi]
+    jne 3f
+    mov rax, [rdi + 16]
+    mov rdi, [rdi + 8]
+    mov rsi, [rsi + 8]
+    test rax, rax
+    jz 4f
+    cmp rax, 6
+    je nemet_enum_eq
+    cmp rax, 5
+    je 2f
+    cmp rax, 2
+    je 1f
+    cmp rdi, rsi
+    sete al
+    movzx eax, al
+    ret
+1:  movq xmm0, rdi
+    movq xmm1, rsi
+    ucomisd xmm0, xmm1
+    sete al
+    setnp cl
+    and al, cl
+    movzx eax, al
+    ret
+2:  call nemet_str_cmp
+    test rax, rax
+    sete al
+    movzx eax, al
+    ret
+3:  xor eax, eax
+    ret
+4:  mov eax, 1
+    ret
+
+# rdi = size, a multiple of 8, returns rax = that many bytes that are never
+# freed. the first call finds where the heap starts, a call out of room moves
+# the break a megabyte past what it needs
+nemet_alloc:
+    mov rax, [rip + nemet_heap_next]
+    lea rdx, [rax + rdi]
+    cmp rdx, [rip + nemet_heap_end]
+    ja 1f
+    mov [rip + nemet_heap_next], rdx
+    ret
+1:  push rdi
+    mov rdi, [rip + nemet_heap_end]
+    test rdi, rdi
+    jnz 2f
+    mov eax, 12
+    syscall
+    mov [rip + nemet_heap_next], rax
+    mov rdi, rax
+2:  add rdi, [rsp]
+    add rdi, 1048576
+    mov eax, 12
+    syscall
+    cmp rax, rdi
+    jb nemet_panic_memory
+    mov [rip + nemet_heap_end], rax
+    pop rdi
+    jmp nemet_alloc
+
+# rdi, rsi = strings, returns rax = -1, 0 or 1 comparing them char by char
+nemet_str_cmp:
+    mov r8, [rdi]
+    mov r9, [rsi]
+    xor ecx, ecx
+1:  cmp rcx, r8
+    jae 3f
+    cmp rcx, r9
+    jae 5f
+    mov eax, dword ptr [rdi + rcx * 4 + 8]
+    mov edx, dword ptr [rsi + rcx * 4 + 8]
+    cmp eax, edx
+    jb 4f
+    ja 5f
+    inc rcx
+    jmp 1b
+3:  cmp r8, r9
+    je 6f
+4:  mov rax, -1
+    ret
+5:  mov eax, 1
+    ret
+6:  xor eax, eax
+    ret
+
+# xmm0 % xmm1 like fmod, the x87 remainder is exact
+nemet_fmod:
+    sub rsp, 16
+    movsd [rsp], xmm1
+    fld qword ptr [rsp]
+    movsd [rsp], xmm0
+    fld qword ptr [rsp]
+1:  fprem
+    fnstsw ax
+    test ah, 4
+    jnz 1b
+    fstp qword ptr [rsp]
+    fstp st(0)
+    movsd xmm0, [rsp]
+    add rsp, 16
+    ret
+
+# rdi = message, rsi = length. prints `error: message` to stderr and exits with 1
+nemet_panic:
+    mov r12, rdi
+    mov r13, rsi
+    mov edi, 2
+    lea rsi, [rip + nemet_error]
+    mov edx, 7
+    mov eax, 1
+    syscall
+    mov edi, 2
+    mov rsi, r12
+    mov rdx, r13
+    mov eax, 1
+    syscall
+    mov edi, 2
+    lea rsi, [rip + nemet_nl]
+    mov edx, 1
+    mov eax, 1
+    syscall
+    mov edi, 1
+    mov eax, 60
+    syscall
+
+nemet_panic_div:
+    lea rdi, [rip + nemet_msg_div]
+    mov esi, OFFSET nemet_msg_div_len
+    jmp nemet_panic
+
+nemet_panic_overflow:
+    lea rdi, [rip + nemet_msg_overflow]
+    mov esi, OFFSET nemet_msg_overflow_len
+    jmp nemet_panic
+
+nemet_panic_shift:
+    lea rdi, [rip + nemet_msg_shift]
+    mov esi, OFFSET nemet_msg_shift_len
+    jmp nemet_panic
+
+nemet_panic_index:
+    lea rdi, [rip + nemet_msg_index]
+    mov esi, OFFSET nemet_msg_index_len
+    jmp nemet_panic
+
+nemet_panic_memory:
+    lea rdi, [rip + nemet_msg_memory]
+    mov esi, OFFSET nemet_msg_memory_len
+    jmp nemet_panic
+
+.section .rodata
+.p2align 3
+nemet_million: .double 1000000.0
+nemet_ten: .double 10.0
+nemet_two_63: .double 9223372036854775808.0
+nemet_nl: .ascii "\n"
+nemet_true: .ascii "true"
+nemet_false: .ascii "false"
+nemet_nan: .ascii "NaN"
+nemet_inf: .ascii "inf"
+nemet_neg_inf: .ascii "-inf"
+nemet_open: .ascii "("
+nemet_close: .ascii ")"
+nemet_squote: .ascii "'"
+nemet_dquote: .ascii "\""
+nemet_error: .ascii "error: "
+# the length of every message follows it, so the two cannot disagree
+nemet_msg_div: .ascii "division by zero"
+.set nemet_msg_div_len, . - nemet_msg_div
+nemet_msg_overflow: .ascii "integer overflow"
+.set nemet_msg_overflow_len, . - nemet_msg_overflow
+nemet_msg_shift: .ascii "shift is out of range"
+.set nemet_msg_shift_len, . - nemet_msg_shift
+nemet_msg_index: .ascii "index out of bounds"
+.set nemet_msg_index_len, . - nemet_msg_index
+nemet_msg_memory: .ascii "out of memory"
+.set nemet_msg_memory_len, . - nemet_msg_memory
+
+.bss
+.p2align 3
+nemet_heap_next: .zero 8
+nemet_heap_end: .zero 8
@@ -0,0 +1,500 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::ast::Op;
+use crate::ir::{BlockId, INIT, Inst, IrFunc, Module, Operand, Place, Terminator};
+use crate::semantic::types::Ty;
+use crate::value::Value;
+
+// GNU assembler source in intel syntax for linux on x86-64, linked without libc.
+// functions follow the System V calling convention. every slot and every
+// register of a function gets 8 bytes of its stack frame, values are kept in
+// rax and rcx only while an instruction runs:
+// - int, bool and char are 64 bit integers, a char is its code point
+// - a float is a double, moved through xmm0 and xmm1 as its bits
+// - a str points at a quad with its number of chars followed by a long per char
+// - an enum points at its name, payload and payload kind, see runtime.s.
+//   variants with a payload are allocated when they are made, never freed
+pub const RUNTIME: &str = include_str!("runtime.s");
+
+const INT_ARGS: [&str; 6] = ["rdi", "rsi", "rdx", "rcx", "r8", "r9"];
+const FLOAT_ARGS: usize = 8;
+
+// how runtime.s prints and compares the payload of an enum
+fn payload_kind(ty: &Ty) -> u8 {
+    match ty {
+        Ty::Int => 1,
+        Ty::Float => 2,
+        Ty::Bool => 3,
+        Ty::Char => 4,
+        Ty::Str => 5,
+        Ty::Enum(_) => 6,
+        _ => unreachable!("payloads of type {} are not lowered to the ir",ty),
+    }
+}
+
+// the string an enum value points at, `Enum::Variant`
+fn variant_name(ty: &Ty, variant: &str) -> Value {
+    let Ty::Enum(name) = ty else { unreachable!("the verifier checks variants are enums") };
+    Value::Str(format!("{}::{}",name,variant))
+}
+
+pub fn emit(module: &Module) -> Result<String, String> {
+    let main = module.func("main").ok_or("there is no `main` function")?;
+    if !main.params.is_empty() {
+        return Err("`main` cannot take arguments".to_string());
+    }
+    let mut emitter = Emitter { asm: String::new(), strings: HashMap::new(), enums: HashMap::new() };
+    emitter.line(".intel_syntax noprefix");
+    emitter.line(".text");
+    emitter.line(".globl _start");
+    emitter.label("_start");
+    if module.func(INIT).is_some() {
+        emitter.inst(format!("call {}",func_label(INIT)));
+    }
+    emitter.inst(format!("call {}",func_label("main")));
+    emitter.inst("xor edi, edi");
+    emitter.inst("mov eax, 60");
+    emitter.inst("syscall");
+    for (i, func) in module.funcs.iter().enumerate() {
+        emitter.func(i, func);
+    }
+    if !module.globals.is_empty() {
+        emitter.line("\n.data");
+        emitter.line(".p2align 3");
+        for (i, global) in module.globals.iter().enumerate() {
+            let value = match &global.init {
+                Some(value) => emitter.constant(value),
+                None => "0".to_string(),
+            };
+            emitter.line(format!("{}: .quad {}",global_label(i),value));
+        }
+    }
+    let Emitter { mut asm, strings, enums } = emitter;
+    let mut strings: Vec<(String, usize)> = strings.into_iter().collect();
+    strings.sort_by_key(|(_, id)| *id);
+    if !strings.is_empty() {
+        asm.push_str("\n.section .rodata\n.p2align 3\n");
+        for (value, id) in strings {
+            let _ = writeln!(asm, ".Lstr.{}: .quad {}",id,value.chars().count());
+            if !value.is_empty() {
+                let chars: Vec<String> = value.chars().map(|c| (c as u32).to_string()).collect();
+                let _ = writeln!(asm, "    .long {}",chars.join(", "));
+            }
+        }
+    }
+    let mut enums: Vec<(String, usize)> = enums.into_iter().collect();
+    enums.sort_by_key(|(_, id)| *id);
+    if !enums.is_empty() {
+        asm.push_str(".p2align 3\n");
+        for (quads, id) in enums {
+            let _ = writeln!(asm, ".Lenum.{}: .quad {}",id,quads);
+        }
+    }
+    asm.push('\n');
+    asm.push_str(RUNTIME);
+    Ok(asm)
+}
+
+fn func_label(name: &str) -> String {
+    // nemet identifiers could collide with register names and the runtime
+    format!("nm.{}",name)
+}
+
+fn global_label(global: usize) -> String {
+    format!("nm.g.{}",global)
+}
+
+struct Emitter {
+    asm: String,
+    // string constant -> its .Lstr number
+    strings: HashMap<String, usize>,
+    // quads of an enum constant -> its .Lenum number
+    enums: HashMap<String, usize>,
+}
+
+// the function being emitted
+struct Frame<'a> {
+    index: usize,
+    func: &'a IrFunc,
+}
+impl Frame<'_> {
+    fn slot(&self, slot: usize) -> String {
+        format!("qword ptr [rbp - {}]",8 * (slot + 1))
+    }
+
+    fn reg(&self, reg: usize) -> String {
+        format!("qword ptr [rbp - {}]",8 * (self.func.slots.len() + reg + 1))
+    }
+
+    fn place(&self, place: Place) -> String {
+        match place {
+            Place::Slot(slot) => self.slot(slot),
+            Place::Global(global) => format!("qword ptr [rip + {}]",global_label(global)),
+        }
+    }
+
+    fn block(&self, block: BlockId) -> String {
+        format!(".L{}.bb{}",self.index,block)
+    }
+
+    fn operand_type(&self, operand: &Operand) -> Ty {
+        match operand {
+            Operand::Reg(reg) => self.func.regs[*reg].clone(),
+            Operand::Const(value) => value.ty(),
+        }
+    }
+}
+
+impl Emitter {
+    fn line(&mut self, line: impl AsRef<str>) {
+        self.asm.push_str(line.as_ref());
+        self.asm.push('\n');
+    }
+
+    fn label(&mut self, label: impl AsRef<str>) {
+        self.line(format!("{}:",label.as_ref()));
+    }
+
+    fn inst(&mut self, inst: impl AsRef<str>) {
+        self.line(format!("    {}",inst.as_ref()));
+    }
+
+    // a constant as an assembler expression
+    fn constant(&mut self, value: &Value) -> String {
+        match value {
+            Value::Int(value) => value.to_string(),
+            Value::Float(value) => format!("{:#x}",value.to_bits()),
+            Value::Bool(value) => (*value as u8).to_string(),
+            Value::Char(value) => (*value as u32).to_string(),
+            Value::Str(value) => {
+                let next = self.strings.len();
+                format!(".Lstr.{}",self.strings.entry(value.clone()).or_insert(next))
+            },
+            Value::Enum { name, variant, payload } => {
+                let ty = Ty::Enum(name.clone());
+                let name = self.constant(&variant_name(&ty, variant));
+                let quads = match payload {
+                    Some(payload) => format!("{}, {}, {}",name,self.constant(payload),payload_kind(&payload.ty())),
+                    None => format!("{}, 0, 0",name),
+                };
+                let next = self.enums.len();
+                format!(".Lenum.{}",self.enums.entry(quads).or_insert(next))
+            },
+            Value::Struct { .. } | Value::Array(_) => unreachable!("structs and arrays are not lowered to the ir"),
+        }
+    }
+
+    fn load(&mut self, frame: &Frame, reg: &str, operand: &Operand) {
+        match operand {
+            Operand::Reg(src) => self.inst(format!("mov {}, {}",reg,frame.reg(*src))),
+            Operand::Const(value @ (Value::Str(_) | Value::Enum { .. })) => {
+                let label = self.constant(value);
+                self.inst(format!("lea {}, [rip + {}]",reg,label));
+            },
+            Operand::Const(value) => {
+                let value = self.constant(value);
+                self.inst(format!("movabs {}, {}",reg,value));
+            },
+        }
+    }
+
+    fn func(&mut self, index: usize, func: &IrFunc) {
+        let frame = Frame { index, func };
+        let size = 8 * (func.slots.len() + func.regs.len());
+        self.line("");
+        self.label(func_label(&func.name));
+        self.inst("push rbp");
+        self.inst("mov rbp, rsp");
+        if size > 0 {
+            self.inst(format!("sub rsp, {}",size.next_multiple_of(16)));
+        }
+        // the arguments arrive in %0..%n
+        let (mut ints, mut floats, mut stack) = (0, 0, 0);
+        for (reg, ty) in func.params.iter().enumerate() {
+            let dst = frame.reg(reg);
+            if *ty == Ty::Float && floats < FLOAT_ARGS {
+                self.inst(format!("movq {}, xmm{}",dst,floats));
+                floats += 1;
+            }else if *ty != Ty::Float && ints < INT_ARGS.len() {
+                self.inst(format!("mov {}, {}",dst,INT_ARGS[ints]));
+                ints += 1;
+            }else {
+                // past the saved rbp and the return address
+                self.inst(format!("mov rax, qword ptr [rbp + {}]",16 + 8 * stack));
+                self.inst(format!("mov {}, rax",dst));
+                stack += 1;
+            }
+        }
+        for (block, data) in func.blocks.iter().enumerate() {
+            self.label(frame.block(block));
+            for inst in &data.insts {
+                self.instruction(&frame, inst);
+            }
+            match &data.term {
+                Some(term) => self.terminator(&frame, term),
+                None => self.inst("ud2"),
+            }
+        }
+    }
+
+    fn instruction(&mut self, frame: &Frame, inst: &Inst) {
+        match inst {
+            Inst::Copy { dst, src, .. } => {
+                self.load(frame, "rax", src);
+                self.inst(format!("mov {}, rax",frame.reg(*dst)));
+            },
+            Inst::Binary { dst, op, lhs, rhs, .. } => {
+                self.load(frame, "rax", lhs);
+                self.load(frame, "rcx", rhs);
+                match frame.operand_type(lhs) {
+                    Ty::Float => self.float_binary(*op),
+                    Ty::Str => {
+                        self.inst("mov rdi, rax");
+                        self.inst("mov rsi, rcx");
+                        self.inst("call nemet_str_cmp");
+                        self.inst("xor ecx, ecx");
+                        self.compare(*op);
+                    },
+                    // only == and != are defined on enums
+                    Ty::Enum(_) => {
+                        self.inst("mov rdi, rax");
+                        self.inst("mov rsi, rcx");
+                        self.inst("call nemet_enum_eq");
+                        if *op == Op::NotEq {
+                            self.inst("xor rax, 1");
+                        }
+                    },
+                    _ => self.int_binary(*op),
+                }
+                self.inst(format!("mov {}, rax",frame.reg(*dst)));
+            },
+            Inst::Unary { dst, ty, op, operand } => {
+                self.load(frame, "rax", operand);
+                match (op, ty) {
+                    (Op::Sub, Ty::Float) => self.inst("btc rax, 63"),
+                    (Op::Sub, _) => {
+                        self.inst("neg rax");
+                        self.inst("jo nemet_panic_overflow");
+                    },
+                    (Op::Not, Ty::Bool) => self.inst("xor rax, 1"),
+                    (Op::Not, _) => self.inst("not rax"),
+                    _ => {},
+                }
+                self.inst(format!("mov {}, rax",frame.reg(*dst)));
+            },
+            Inst::Load { dst, place, .. } => {
+                self.inst(format!("mov rax, {}",frame.place(*place)));
+                self.inst(format!("mov {}, rax",frame.reg(*dst)));
+            },
+            Inst::Store { place, value } => {
+                self.load(frame, "rax", value);
+                self.inst(format!("mov {}, rax",frame.place(*place)));
+            },
+            Inst::Len { dst, value } => {
+                self.load(frame, "rax", value);
+                self.inst("mov rax, qword ptr [rax]");
+                self.inst(format!("mov {}, rax",frame.reg(*dst)));
+            },
+            Inst::Index { dst, base, index, .. } => {
+                self.load(frame, "rax", base);
+                self.load(frame, "rcx", index);
+                // unsigned, so negative indices are out of bounds too
+                self.inst("cmp rcx, qword ptr [rax]");
+                self.inst("jae nemet_panic_index");
+                self.inst("mov eax, dword ptr [rax + rcx * 4 + 8]");
+                self.inst(format!("mov {}, rax",frame.reg(*dst)));
+            },
+            Inst::Print { value } => {
+                self.load(frame, "rdi", value);
+                let print = match frame.operand_type(value) {
+                    Ty::Float => "nemet_print_float",
+                    Ty::Bool => "nemet_print_bool",
+                    Ty::Char => "nemet_print_char",
+                    Ty::Str => "nemet_print_str",
+                    Ty::Enum(_) => "nemet_print_enum",
+                    _ => "nemet_print_int",
+                };
+                self.inst(format!("call {}",print));
+            },
+            // arguments past the registers are pushed last to first, the same
+            // way the prologue reads them, padded so rsp stays 16 byte aligned
+            Inst::Call { dst, ty, func, args } => {
+                let (mut ints, mut floats, mut stack) = (Vec::new(), Vec::new(), Vec::new());
+                for arg in args {
+                    if frame.operand_type(arg) == Ty::Float && floats.len() < FLOAT_ARGS {
+                        floats.push(arg);
+                    }else if frame.operand_type(arg) != Ty::Float && ints.len() < INT_ARGS.len() {
+                        ints.push(arg);
+                    }else {
+                        stack.push(arg);
+                    }
+                }
+                let size = 8 * (stack.len() + stack.len() % 2);
+                if stack.len() % 2 == 1 {
+                    self.inst("sub rsp, 8");
+                }
+                for arg in stack.iter().rev() {
+                    self.load(frame, "rax", arg);
+                    self.inst("push rax");
+                }
+                for (i, arg) in floats.into_iter().enumerate() {
+                    self.load(frame, "rax", arg);
+                    self.inst(format!("movq xmm{}, rax",i));
+                }
+                for (reg, arg) in INT_ARGS.iter().zip(ints) {
+                    self.load(frame, reg, arg);
+                }
+                self.inst(format!("call {}",func_label(func)));
+                if size > 0 {
+                    self.inst(format!("add rsp, {}",size));
+                }
+                if let Some(dst) = dst {
+                    if *ty == Ty::Float {
+                        self.inst("movq rax, xmm0");
+                    }
+                    self.inst(format!("mov {}, rax",frame.reg(*dst)));
+                }
+            },
+            Inst::Variant { dst, ty, variant, payload } => {
+                self.inst("mov edi, 24");
+                self.inst("call nemet_alloc");
+                self.load(frame, "rcx", payload);
+                self.inst("mov qword ptr [rax + 8], rcx");
+                self.inst(format!("mov qword ptr [rax + 16], {}",payload_kind(&frame.operand_type(payload))));
+                self.load(frame, "rcx", &Operand::Const(variant_name(ty, variant)));
+                self.inst("mov qword ptr [rax], rcx");
+                self.inst(format!("mov {}, rax",frame.reg(*dst)));
+            },
+            // values of the same variant point at the same name
+            Inst::IsVariant { dst, value, variant } => {
+                self.load(frame, "rax", value);
+                self.load(frame, "rcx", &Operand::Const(variant_name(&frame.operand_type(value), variant)));
+                self.inst("cmp qword ptr [rax], rcx");
+                self.inst("sete al");
+                self.inst("movzx eax, al");
+                self.inst(format!("mov {}, rax",frame.reg(*dst)));
+            },
+            Inst::Payload { dst, value, .. } => {
+                self.load(frame, "rax", value);
+                self.inst("mov rax, qword ptr [rax + 8]");
+                self.inst(format!("mov {}, rax",frame.reg(*dst)));
+            },
+        }
+    }
+
+    // rax op rcx into rax, for int, bool and char operands
+    fn int_binary(&mut self, op: Op) {
+        match op {
+            Op::Add | Op::Sub | Op::Multi => {
+                let inst = match op { Op::Add => "add", Op::Sub => "sub", _ => "imul" };
+                self.inst(format!("{} rax, rcx",inst));
+                self.inst("jo nemet_panic_overflow");
+            },
+            Op::Divide | Op::Mod => {
+                self.inst("test rcx, rcx");
+                self.inst("jz nemet_panic_div");
+                // the smallest int divided by -1 does not fit
+                self.inst("cmp rcx, -1");
+                self.inst("jne 1f");
+                self.inst("movabs rdx, 0x8000000000000000");
+                self.inst("cmp rax, rdx");
+                self.inst("je nemet_panic_overflow");
+                self.line("1:");
+                self.inst("cqo");
+                self.inst("idiv rcx");
+                if op == Op::Mod {
+                    self.inst("mov rax, rdx");
+                }
+            },
+            Op::Lsh | Op::Rsh => {
+                self.inst("cmp rcx, 63");
+                self.inst("ja nemet_panic_shift");
+                self.inst(if op == Op::Lsh { "shl rax, cl" } else { "sar rax, cl" });
+            },
+            Op::And | Op::AndAnd => self.inst("and rax, rcx"),
+            Op::Or | Op::OrOr => self.inst("or rax, rcx"),
+            Op::Xor => self.inst("xor rax, rcx"),
+            _ => self.compare(op),
+        }
+    }
+
+    // signed comparison of rax and rcx into rax
+    fn compare(&mut self, op: Op) {
+        let set = match op {
+            Op::Eq => "sete",
+            Op::NotEq => "setne",
+            Op::Less => "setl",
+            Op::LessEq => "setle",
+            Op::More => "setg",
+            _ => "setge",
+        };
+        self.inst("cmp rax, rcx");
+        self.inst(format!("{} al",set));
+        self.inst("movzx eax, al");
+    }
+
+    fn float_binary(&mut self, op: Op) {
+        self.inst("movq xmm0, rax");
+        self.inst("movq xmm1, rcx");
+        match op {
+            Op::Add => self.inst("addsd xmm0, xmm1"),
+            Op::Sub => self.inst("subsd xmm0, xmm1"),
+            Op::Multi => self.inst("mulsd xmm0, xmm1"),
+            Op::Divide => self.inst("divsd xmm0, xmm1"),
+            Op::Mod => self.inst("call nemet_fmod"),
+            _ => {
+                // an unordered comparison sets zf, pf and cf, which only
+                // equality has to check for. less is done as more the other way round
+                match op {
+                    Op::Eq => {
+                        self.inst("ucomisd xmm0, xmm1");
+                        self.inst("sete al");
+                        self.inst("setnp cl");
+                        self.inst("and al, cl");
+                    },
+                    Op::NotEq => {
+                        self.inst("ucomisd xmm0, xmm1");
+                        self.inst("setne al");
+                        self.inst("setp cl");
+                        self.inst("or al, cl");
+                    },
+                    Op::Less | Op::LessEq => {
+                        self.inst("ucomisd xmm1, xmm0");
+                        self.inst(if op == Op::Less { "seta al" } else { "setae al" });
+                    },
+                    _ => {
+                        self.inst("ucomisd xmm0, xmm1");
+                        self.inst(if op == Op::More { "seta al" } else { "setae al" });
+                    },
+                }
+                self.inst("movzx eax, al");
+                return;
+            },
+        }
+        self.inst("movq rax, xmm0");
+    }
+
+    fn terminator(&mut self, frame: &Frame, term: &Terminator) {
+        match term {
+            Terminator::Jump(target) => self.inst(format!("jmp {}",frame.block(*target))),
+            Terminator::Branch { cond, then, otherwise } => {
+                self.load(frame, "rax", cond);
+                self.inst("test rax, rax");
+                self.inst(format!("jnz {}",frame.block(*then)));
+                self.inst(format!("jmp {}",frame.block(*otherwise)));
+            },
+            Terminator::Return(value) => {
+                if let Some(value) = value {
+                    self.load(frame, "rax", value);
+                    if frame.func.ret == Ty::Float {
+                        self.inst("movq xmm0, rax");
+                    }
+                }
+                self.inst("leave");
+                self.inst("ret");
+            },
+            Terminator::Unreachable => self.inst("ud2"),
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use std::fmt::Display;
+
+use crate::lexer::{LexError, LexErrorKind, Loc, Token};
+use crate::span::Span;
+
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Error => write!(f,"error"),
+            Self::Warning => write!(f,"warning"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: Option<&'static str>,
+    pub message: String,
+    pub file_path: String,
+    pub line: usize,
+    pub col: usize,
+    // underlined part of the source, starting at line:col
+    pub span: Span,
+    pub notes: Vec<String>,
+    pub helps: Vec<String>,
+}
+impl Diagnostic {
+    pub fn new(message: impl ToString, file_path: impl ToString, line: usize, col: usize) -> Self {
+        Self {
+            severity: Severity::Error,
+            code: None,
+            message: message.to_string(),
+            file_path: file_path.to_string(),
+            line,
+            col,
+            span: Span::default(),
+            notes: Vec::new(),
+            helps: Vec::new(),
+        }
+    }
+
+    pub fn at_loc(message: impl ToString, loc: Loc, span: Span) -> Self {
+        Self::new(message, loc.0, loc.1, loc.2).with_span(span)
+    }
+
+    pub fn at_token(message: impl ToString, token: &Token) -> Self {
+        Self::new(message, &token.file_path, token.line, token.col)
+            .with_span(token.span)
+    }
+
+    pub fn warning(mut self) -> Self {
+        self.severity = Severity::Warning;
+        self
+    }
+
+    pub fn with_code(mut self, code: &'static str) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.span = span;
+        self
+    }
+
+    pub fn with_note(mut self, note: impl ToString) -> Self {
+        self.notes.push(note.to_string());
+        self
+    }
+
+    pub fn with_help(mut self, help: impl ToString) -> Self {
+        self.helps.push(help.to_string());
+        self
+    }
+
+    // rustc style report, `source` is the content of `file_path`
+    //
+    // error[E0003]: char literal can not be empty
+    //  --> main.nmt:1:6
+    //   |
+    // 1 | @a : '';
+    //   |      ^
+    pub fn render(&self, source: &str, color: bool) -> String {
+        let paint = |style: &str, text: &str| {
+            if color { format!("{}{}{}",style,text,RESET) } else { text.to_string() }
+        };
+        let level_style = match self.severity {
+            Severity::Error => RED,
+            Severity::Warning => YELLOW,
+        };
+        let mut out = String::new();
+        let level = match self.code {
+            Some(code) => format!("{}[{}]",self.severity,code),
+            None => self.severity.to_string(),
+        };
+        out.push_str(&paint(level_style, &level));
+        out.push_str(&paint(BOLD, &format!(": {}",self.message)));
+        out.push('\n');
+
+        let gutter = " ".repeat(self.line.to_string().len());
+        out.push_str(&format!("{}{} {}:{}:{}\n",gutter,paint(BLUE, "-->"),self.file_path,self.line,self.col));
+        if let Some(text) = source.lines().nth(self.line.saturating_sub(1)) {
+            let bar = paint(BLUE, "|");
+            out.push_str(&format!("{} {}\n",gutter,bar));
+            out.push_str(&format!("{} {} {}\n",paint(BLUE, &self.line.to_string()),bar,text));
+            // keep tabs so the caret lines up with the source line
+            let padding: String = text.chars().take(self.col.saturating_sub(1))
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+            // spans running past the end of the line are cut at the line end
+            let rest = text.chars().count().saturating_sub(self.col - 1);
+            let width = source.get(self.span.start..self.span.end).map_or(0, |span| span.chars().count());
+            let carets = paint(level_style, &"^".repeat(width.min(rest).max(1)));
+            out.push_str(&format!("{} {} {}{}\n",gutter,bar,padding,carets));
+        }
+        for note in &self.notes {
+            out.push_str(&format!("{} {} note: {}\n",gutter,paint(BLUE, "="),note));
+        }
+        for help in &self.helps {
+            out.push_str(&format!("{} {} help: {}\n",gutter,paint(BLUE, "="),help));
+        }
+        out
+    }
+}
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f,"{} :{}:{}:{}",self.message,self.file_path,self.line,self.col)
+    }
+}
+impl From<LexError> for Diagnostic {
+    fn from(err: LexError) -> Self {
+        let (file_path, line, col) = err.loc;
+        let diagnostic = Self::new(err.kind.to_string(), file_path, line, col)
+            .with_code(err.kind.code())
+            .with_span(err.span);
+        match err.kind {
+            LexErrorKind::UnterminatedString =>
+                diagnostic.with_help("add a closing `\"` before the end of the line"),
+            LexErrorKind::UnterminatedChar | LexErrorKind::UnsupportedChar =>
+                diagnostic.with_note("char literals hold exactly one character")
+                    .with_help("use a string literal for more than one character"),
+            LexErrorKind::UnfinishedEscape | LexErrorKind::UnknownEscape(_) =>
+                diagnostic.with_help("supported escape sequences are \\n \\t \\r \\\\ and the escaped quote"),
+            _ => diagnostic,
+        }
+    }
+}
+impl<R: std::fmt::Debug + Copy + std::hash::Hash + Ord> From<pest::error::Error<R>> for Diagnostic {
+    fn from(err: pest::error::Error<R>) -> Self {
+        let (line, col) = match err.line_col {
+            pest::error::LineColLocation::Pos(pos) => pos,
+            pest::error::LineColLocation::Span(start, _) => start,
+        };
+        let span = match err.location {
+            pest::error::InputLocation::Pos(pos) => Span::new(0, pos, pos),
+            pest::error::InputLocation::Span((start, end)) => Span::new(0, start, end),
+        };
+        let file_path = err.path().unwrap_or("INTERNAL").to_string();
+        Self::new(err.variant.message(), file_path, line, col)
+            .with_code("E0100")
+            .with_span(span)
+    }
+}
@@ -0,0 +1,76 @@
+use crate::ast::Program;
+use crate::diagnostics::Diagnostic;
+use crate::lexer::Lexer;
+use crate::pest_parser;
+
+// the two parsers for the same language, both build an ast::Program
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Frontend {
+    Manual,
+    Pest,
+}
+impl Frontend {
+    pub fn get(name: &str) -> Option<Self> {
+        match name {
+            "manual" => Some(Self::Manual),
+            "pest" => Some(Self::Pest),
+            _ => None,
+        }
+    }
+
+    // errors end up in program.diagnostics for both front-ends
+    pub fn parse(&self, file_path: &str, source: &str) -> Program {
+        self.parse_file(file_path, source, 0)
+    }
+
+    // parse for the file registered as file_id in a SourceMap
+    pub fn parse_file(&self, file_path: &str, source: &str, file_id: usize) -> Program {
+        match self {
+            Self::Manual => Program::new(&mut Lexer::from_source(file_path, source).with_file_id(file_id)),
+            Self::Pest => pest_parser::parse_file(file_path, source, file_id).unwrap_or_else(|diagnostic| Program {
+                shebang: String::new(),
+                body: Vec::new(),
+                diagnostics: vec![diagnostic],
+            }),
+        }
+    }
+}
+
+// parses source with both front-ends and checks they agree. a program both
+// reject gives the diagnostics of the hand written parser, which recovers
+// and reports more than pest does
+pub fn cross_check(file_path: &str, source: &str) -> Result<Program, Vec<Diagnostic>> {
+    cross_check_file(file_path, source, 0)
+}
+
+pub fn cross_check_file(file_path: &str, source: &str, file_id: usize) -> Result<Program, Vec<Diagnostic>> {
+    let manual = Frontend::Manual.parse_file(file_path, source, file_id);
+    let pest = Frontend::Pest.parse_file(file_path, source, file_id);
+    match (manual.has_errors(), pest.has_errors()) {
+        (true, true) => return Err(manual.diagnostics),
+        (true, false) => return Err(vec![disagree(file_path, "only the pest front-end accepts this program")
+            .with_note(format!("the hand written parser reported: {}", manual.diagnostics[0]))]),
+        (false, true) => return Err(vec![disagree(file_path, "only the hand written front-end accepts this program")
+            .with_note(format!("the pest parser reported: {}", pest.diagnostics[0]))]),
+        (false, false) => (),
+    }
+    if manual.body.len() != pest.body.len() {
+        return Err(vec![disagree(file_path, format!("front-ends found {} and {} items",
+            manual.body.len(), pest.body.len()))]);
+    }
+    let lexer = Lexer::from_source(file_path, source).with_file_id(file_id);
+    for (left, right) in manual.body.iter().zip(pest.body.iter()) {
+        if left != right {
+            let span = left.span();
+            return Err(vec![Diagnostic::at_loc("front-ends built different trees for this item", lexer.locate(span), span)
+                .with_code("E0106")
+                .with_note(format!("hand written: {:?}", left))
+                .with_note(format!("pest: {:?}", right))]);
+        }
+    }
+    Ok(manual)
+}
+
+fn disagree(file_path: &str, msg: impl ToString) -> Diagnostic {
+    Diagnostic::new(msg, file_path, 1, 1).with_code("E0106")
+}
@@ -0,0 +1,449 @@
+use std::collections::HashMap;
+use std::io::Write;
+
+use crate::ast::{Expr, Func, MatchArm, Node, Op, Pattern, Program, Stmt, VariableDelclear};
+use crate::diagnostics::Diagnostic;
+use crate::semantic::Analysis;
+use crate::semantic::resolve::{Resolution, SymbolId, SymbolKind};
+use crate::semantic::types::Ty;
+use crate::span::{SourceMap, Span};
+use crate::value::Value;
+
+// runs a program that went through semantic::analyze without errors.
+// every name is looked up through the Resolution, so variables are keyed by
+// SymbolId. globals and statics live as long as the interpreter, everything
+// else in the frame of the call that declared it
+pub struct Interpreter<'a, W: Write> {
+    analysis: &'a Analysis,
+    sources: &'a SourceMap,
+    funcs: HashMap<SymbolId, &'a Func>,
+    globals: HashMap<SymbolId, Value>,
+    frames: Vec<HashMap<SymbolId, Value>>,
+    // where `# expr;` prints
+    out: W,
+}
+
+// what a statement tells the enclosing statements to do
+#[derive(Debug, PartialEq)]
+enum Flow {
+    Next,
+    Break,
+    Continue,
+    Return(Option<Value>),
+}
+
+pub type RunResult<T> = Result<T, Diagnostic>;
+
+// calls nested deeper than this stop the program before the stack of the
+// interpreter itself overflows
+pub const MAX_DEPTH: usize = 1000;
+
+// every nemet call recurses through call, block, stmt and expr, which take
+// tens of kilobytes in a debug build. reserved, not used up front
+const STACK_SIZE: usize = 256 * 1024 * 1024;
+
+// runs f on a thread with a stack that holds MAX_DEPTH nested calls, so
+// programs run the same whatever thread the library is used from
+pub fn with_stack<T: Send>(f: impl FnOnce() -> T + Send) -> T {
+    std::thread::scope(|scope| {
+        let thread = std::thread::Builder::new().stack_size(STACK_SIZE).spawn_scoped(scope, f);
+        match thread.expect("could not start the interpreter thread").join() {
+            Ok(value) => value,
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    })
+}
+
+// runs the top level declarations then `main`, returning what main returned
+pub fn run(program: &Program, analysis: &Analysis, sources: &SourceMap, out: impl Write + Send) -> RunResult<Option<Value>> {
+    with_stack(|| run_here(program, analysis, sources, out))
+}
+
+fn run_here(program: &Program, analysis: &Analysis, sources: &SourceMap, out: impl Write) -> RunResult<Option<Value>> {
+    let mut interpreter = Interpreter::new(program, analysis, sources, out)?;
+    let Some(main) = interpreter.func("main") else {
+        let path = sources.get(0).map_or(String::new(), |file| file.path.clone());
+        return Err(Diagnostic::new("there is no `main` function to run", path, 1, 1).with_code("E0500"));
+    };
+    if !main.args.is_empty() {
+        return Err(interpreter.error("`main` cannot take arguments", main.span));
+    }
+    interpreter.call(main, Vec::new())
+}
+
+impl<'a, W: Write> Interpreter<'a, W> {
+    // declares the functions and initialises the top level variables in order
+    pub fn new(program: &'a Program, analysis: &'a Analysis, sources: &'a SourceMap, out: W) -> RunResult<Self> {
+        let mut interpreter = Self::empty(program, analysis, sources, out);
+        for node in &program.body {
+            if let Node::VariableDelclear { var } = node {
+                interpreter.variable(var)?;
+            }
+        }
+        Ok(interpreter)
+    }
+
+    // only declares the functions, the globals are left without a value
+    pub fn empty(program: &'a Program, analysis: &'a Analysis, sources: &'a SourceMap, out: W) -> Self {
+        let mut funcs = HashMap::new();
+        for node in &program.body {
+            if let Node::Func { var } = node {
+                if let Some(id) = analysis.resolution.defs.get(&var.span) {
+                    funcs.insert(*id, var);
+                }
+            }
+        }
+        Self { analysis, sources, funcs, globals: HashMap::new(), frames: Vec::new(), out }
+    }
+
+    // top level variables and their values
+    pub fn globals(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.globals.iter()
+            .map(|(id, value)| (self.analysis.resolution.symbol(*id), value))
+            .filter(|(symbol, _)| symbol.kind == SymbolKind::Global)
+            .map(|(symbol, value)| (symbol.name.as_str(), value))
+    }
+
+    pub fn set_global(&mut self, name: &str, value: Value) {
+        let symbols = &self.analysis.resolution.symbols;
+        if let Some(id) = symbols.iter().position(|symbol| symbol.kind == SymbolKind::Global && symbol.name == name) {
+            self.globals.insert(id, value);
+        }
+    }
+
+    // by the qualified name, `main` of an included file is `module.main`
+    pub fn func(&self, name: &str) -> Option<&'a Func> {
+        self.funcs.iter().find(|(id, _)| self.analysis.resolution.symbol(**id).name == name).map(|(_, func)| *func)
+    }
+
+    fn error(&self, msg: impl ToString, span: Span) -> Diagnostic {
+        Diagnostic::at_loc(msg, self.sources.locate(span), span).with_code("E0500")
+    }
+
+    pub fn call(&mut self, func: &Func, args: Vec<Value>) -> RunResult<Option<Value>> {
+        let mut frame = HashMap::new();
+        for (arg, value) in func.args.iter().zip(args) {
+            if let Some(id) = self.analysis.resolution.defs.get(&arg.span) {
+                frame.insert(*id, value);
+            }
+        }
+        self.frames.push(frame);
+        let flow = self.block(&func.block);
+        self.frames.pop();
+        match flow? {
+            Flow::Return(value) => Ok(value),
+            _ => Ok(None),
+        }
+    }
+
+    fn is_global(&self, id: SymbolId) -> bool {
+        let symbol = self.analysis.resolution.symbol(id);
+        symbol.kind == SymbolKind::Global || symbol.is_static
+    }
+
+    fn store(&mut self, id: SymbolId, value: Value) {
+        if self.is_global(id) || self.frames.is_empty() {
+            self.globals.insert(id, value);
+        }else if let Some(frame) = self.frames.last_mut() {
+            frame.insert(id, value);
+        }
+    }
+
+    fn load(&self, id: SymbolId, span: Span) -> RunResult<Value> {
+        let value = if self.is_global(id) { self.globals.get(&id) } else { self.frames.last().and_then(|frame| frame.get(&id)) };
+        value.cloned().ok_or_else(|| {
+            self.error(format!("`{}` is used before it has a value",self.analysis.resolution.symbol(id).name), span)
+        })
+    }
+
+    pub fn variable(&mut self, var: &VariableDelclear) -> RunResult<()> {
+        let Some(id) = self.analysis.resolution.defs.get(&var.span).copied() else {
+            return Ok(());
+        };
+        // statics are set up once, with the value folded at compile time
+        if var.is_static && self.globals.contains_key(&id) {
+            return Ok(());
+        }
+        let value = match (self.analysis.consts.values.get(&id), &var.init_value) {
+            (Some(value), _) => value.clone(),
+            (None, Some(init)) => self.expr(init)?,
+            (None, None) => self.analysis.types.zero(self.analysis.types.symbols.get(&id).unwrap_or(&Ty::Int)),
+        };
+        self.store(id, value);
+        Ok(())
+    }
+
+    // what `# expr;` does, span is the statement printing
+    pub fn print(&mut self, value: &Value, span: Span) -> RunResult<()> {
+        writeln!(self.out, "{}", value).map_err(|e| self.error(format!("could not print: {}",e), span))
+    }
+
+    // runs statements outside of any function, in a frame of their own
+    pub fn exec(&mut self, body: &[Stmt]) -> RunResult<()> {
+        self.frames.push(HashMap::new());
+        let flow = self.block(body);
+        self.frames.pop();
+        flow.map(|_| ())
+    }
+
+    fn block(&mut self, body: &[Stmt]) -> RunResult<Flow> {
+        for stmt in body {
+            let flow = self.stmt(stmt)?;
+            if flow != Flow::Next {
+                return Ok(flow);
+            }
+        }
+        Ok(Flow::Next)
+    }
+
+    // runs a loop body, Some when the loop has to stop with that flow
+    fn iteration(&mut self, body: &[Stmt]) -> RunResult<Option<Flow>> {
+        match self.block(body)? {
+            Flow::Break => Ok(Some(Flow::Next)),
+            Flow::Return(value) => Ok(Some(Flow::Return(value))),
+            Flow::Next | Flow::Continue => Ok(None),
+        }
+    }
+
+    fn condition(&mut self, cond: &Expr) -> RunResult<bool> {
+        match self.expr(cond)? {
+            Value::Bool(value) => Ok(value),
+            value => Err(self.error(format!("expected a bool condition found {}",value), cond.span())),
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) -> RunResult<Flow> {
+        match stmt {
+            Stmt::VariableDelclear { var } => self.variable(var)?,
+            Stmt::Assign { lhs, op, rhs, span } => {
+                let Some((target, steps)) = place(lhs, &self.analysis.resolution) else {
+                    return Err(self.error("cannot assign to this expression", *span));
+                };
+                let id = self.symbol(target)?;
+                let mut value = self.expr(rhs)?;
+                if steps.is_empty() && op.binary_op().is_none() {
+                    self.store(id, value);
+                    return Ok(Flow::Next);
+                }
+                // `a.b[i] += value` stores a copy of a with the element replaced,
+                // i is evaluated once for reading and writing the element
+                let indices = steps.iter().map(|step| match step {
+                    Expr::Index { index, .. } => self.index(index).map(Some),
+                    _ => Ok(None),
+                }).collect::<RunResult<Vec<_>>>()?;
+                let mut root = self.load(id, target)?;
+                let mut part = &mut root;
+                for (step, index) in steps.iter().zip(indices) {
+                    part = match (step, index) {
+                        (Expr::Field { ident, .. }, _) => part.field_mut(ident)
+                            .ok_or_else(|| self.error("cannot assign to a field that does not exist", lhs.span()))?,
+                        (_, Some(index)) => part.index_mut(index).map_err(|msg| self.error(msg, step.span()))?,
+                        _ => unreachable!("steps are fields and indices"),
+                    };
+                }
+                if let Some(op) = op.binary_op() {
+                    value = Value::binary(op, part, &value).map_err(|msg| self.error(msg, *span))?;
+                }
+                *part = value;
+                self.store(id, root);
+            },
+            // the only place a call can return nothing
+            Stmt::Expr { expr: Expr::Call { args, span, .. }, .. } => {
+                self.call_at(args, *span)?;
+            },
+            Stmt::Expr { expr, .. } => {
+                self.expr(expr)?;
+            },
+            Stmt::DebugPrint { expr, span } => {
+                let value = self.expr(expr)?;
+                self.print(&value, *span)?;
+            },
+            Stmt::Return { value, .. } => {
+                let value = value.as_ref().map(|value| self.expr(value)).transpose()?;
+                return Ok(Flow::Return(value));
+            },
+            Stmt::Block { body, .. } => return self.block(body),
+            Stmt::If { cond, then_block, else_block, .. } => {
+                if self.condition(cond)? {
+                    return self.block(then_block);
+                }
+                if let Some(branch) = else_block {
+                    return self.stmt(branch);
+                }
+            },
+            Stmt::While { cond, body, .. } => {
+                while self.condition(cond)? {
+                    if let Some(flow) = self.iteration(body)? {
+                        return Ok(flow);
+                    }
+                }
+            },
+            Stmt::Loop { body, .. } => loop {
+                if let Some(flow) = self.iteration(body)? {
+                    return Ok(flow);
+                }
+            },
+            // `to` is exclusive, `for i 0 to 3` runs 0, 1 and 2
+            Stmt::ForRange { from, to, body, span, .. } => {
+                let (Value::Int(from), Value::Int(to)) = (self.expr(from)?, self.expr(to)?) else {
+                    return Err(self.error("range bounds have to be integers", *span));
+                };
+                let id = self.symbol_def(*span)?;
+                for i in from..to {
+                    self.store(id, Value::Int(i));
+                    if let Some(flow) = self.iteration(body)? {
+                        return Ok(flow);
+                    }
+                }
+            },
+            Stmt::ForIn { iter, body, span, .. } => {
+                let items = match self.expr(iter)? {
+                    Value::Str(value) => value.chars().map(Value::Char).collect(),
+                    Value::Array(elements) => elements,
+                    _ => return Err(self.error("only strings and arrays can be iterated over", iter.span())),
+                };
+                let id = self.symbol_def(*span)?;
+                for item in items {
+                    self.store(id, item);
+                    if let Some(flow) = self.iteration(body)? {
+                        return Ok(flow);
+                    }
+                }
+            },
+            Stmt::Match { value, arms, .. } => {
+                let value = self.expr(value)?;
+                let body = self.arm(&value, arms, stmt.span())?;
+                return self.block(body);
+            },
+            Stmt::Break { .. } => return Ok(Flow::Break),
+            Stmt::Continue { .. } => return Ok(Flow::Continue),
+        }
+        Ok(Flow::Next)
+    }
+
+    // body of the first arm matching value, with the payload stored in the binding
+    fn arm<'b, T>(&mut self, value: &Value, arms: &'b [MatchArm<T>], span: Span) -> RunResult<&'b T> {
+        let Value::Enum { variant, payload, .. } = value else {
+            return Err(self.error(format!("cannot match on {}",value), span));
+        };
+        for arm in arms {
+            match &arm.pattern {
+                Pattern::Wildcard { .. } => return Ok(&arm.body),
+                Pattern::Variant { variant: expected, binding, span, .. } if expected == variant => {
+                    if binding.is_some() {
+                        let id = self.symbol_def(*span)?;
+                        let payload = payload.as_deref().ok_or_else(|| self.error(format!("{} has no value to bind",value), *span))?;
+                        self.store(id, payload.clone());
+                    }
+                    return Ok(&arm.body);
+                },
+                _ => (),
+            }
+        }
+        Err(self.error(format!("no arm matches {}",value), span))
+    }
+
+    // symbol used by the path at span
+    fn symbol(&self, span: Span) -> RunResult<SymbolId> {
+        self.analysis.resolution.uses.get(&span).copied().ok_or_else(|| self.error("unresolved name", span))
+    }
+
+    // symbol declared by the node at span
+    fn symbol_def(&self, span: Span) -> RunResult<SymbolId> {
+        self.analysis.resolution.defs.get(&span).copied().ok_or_else(|| self.error("unresolved declaration", span))
+    }
+
+    // runs the function called at span with the values of args
+    fn call_at(&mut self, args: &[Expr], span: Span) -> RunResult<Option<Value>> {
+        let id = self.symbol(span)?;
+        let func = *self.funcs.get(&id).ok_or_else(|| self.error("unresolved function", span))?;
+        let args = args.iter().map(|arg| self.expr(arg)).collect::<RunResult<_>>()?;
+        if self.frames.len() >= MAX_DEPTH {
+            return Err(self.error(format!("too many nested calls, the limit is {}",MAX_DEPTH), span));
+        }
+        self.call(func, args)
+    }
+
+    // an index is checked against the length where it is used
+    fn index(&mut self, index: &Expr) -> RunResult<i64> {
+        match self.expr(index)? {
+            Value::Int(value) => Ok(value),
+            value => Err(self.error(format!("expected an integer index found {}",value), index.span())),
+        }
+    }
+
+    pub fn expr(&mut self, expr: &Expr) -> RunResult<Value> {
+        match expr {
+            Expr::Literal { value, .. } => Ok(Value::from_literal(value)),
+            Expr::Path { span, .. } | Expr::Field { span, .. } if self.analysis.resolution.uses.contains_key(span) => {
+                let id = self.symbol(*span)?;
+                if let Some(value) = self.analysis.consts.values.get(&id) {
+                    return Ok(value.clone());
+                }
+                self.load(id, *span)
+            },
+            Expr::Unary { op, operand, span } => {
+                let operand = self.expr(operand)?;
+                Value::unary(*op, &operand).map_err(|msg| self.error(msg, *span))
+            },
+            Expr::Binary { left, right, op, span } => {
+                let left = self.expr(left)?;
+                match (op, &left) {
+                    (Op::AndAnd, Value::Bool(false)) | (Op::OrOr, Value::Bool(true)) => return Ok(left),
+                    _ => (),
+                }
+                let right = self.expr(right)?;
+                Value::binary(*op, &left, &right).map_err(|msg| self.error(msg, *span))
+            },
+            Expr::Path { span, .. } => Err(self.error("unresolved name", *span)),
+            Expr::StructLiteral { ident, fields, span } => {
+                let fields = fields.iter()
+                    .map(|field| Ok((field.ident.clone(), self.expr(&field.value)?)))
+                    .collect::<RunResult<_>>()?;
+                Ok(self.analysis.types.struct_value(self.analysis.resolution.name(*span, ident), fields))
+            },
+            Expr::Field { base, ident, span } => {
+                let base = self.expr(base)?;
+                base.field(ident).cloned().ok_or_else(|| self.error(format!("{} has no field `{}`",base,ident), *span))
+            },
+            Expr::Variant { ident, variant, payload, span } => {
+                let payload = payload.as_ref().map(|payload| self.expr(payload)).transpose()?;
+                let name = self.analysis.resolution.name(*span, ident).to_string();
+                Ok(Value::Enum { name, variant: variant.clone(), payload: payload.map(Box::new) })
+            },
+            Expr::Match { value, arms, span } => {
+                let value = self.expr(value)?;
+                let body = self.arm(&value, arms, *span)?;
+                self.expr(body)
+            },
+            Expr::Array { elements, .. } => {
+                Ok(Value::Array(elements.iter().map(|element| self.expr(element)).collect::<RunResult<_>>()?))
+            },
+            Expr::Index { base, index, span } => {
+                let base = self.expr(base)?;
+                let index = self.index(index)?;
+                base.index(index).map_err(|msg| self.error(msg, *span))
+            },
+            Expr::Call { ident, args, span } => {
+                self.call_at(args, *span)?.ok_or_else(|| self.error(format!("`{}` returned no value",ident), *span))
+            },
+        }
+    }
+}
+
+// the variable an assignment stores to and the field and index expressions
+// leading to the value it replaces, `a.b[i]` is a with [a.b, a.b[i]] and
+// `module.a.b` is module.a with [module.a.b]
+fn place<'e>(expr: &'e Expr, resolution: &Resolution) -> Option<(Span, Vec<&'e Expr>)> {
+    match expr {
+        Expr::Path { span, .. } => Some((*span, Vec::new())),
+        Expr::Field { span, .. } if resolution.uses.contains_key(span) => Some((*span, Vec::new())),
+        Expr::Field { base, .. } | Expr::Index { base, .. } => {
+            let (span, mut steps) = place(base, resolution)?;
+            steps.push(expr);
+            Some((span, steps))
+        },
+        _ => None,
+    }
+}
+
@@ -0,0 +1,530 @@
+use std::collections::HashMap;
+
+use crate::ast::{Expr, Func, MatchArm, Node, Op, Pattern, Program, Stmt, VariableDelclear};
+use crate::diagnostics::Diagnostic;
+use crate::ir::{Block, BlockId, Global, GlobalId, INIT, Inst, IrFunc, Module, Operand, Place, Slot, Terminator, VReg};
+use crate::semantic::Analysis;
+use crate::semantic::resolve::SymbolId;
+use crate::semantic::types::Ty;
+use crate::span::{SourceMap, Span};
+use crate::value::Value;
+
+// what the ir cannot express yet, each struct reported where it is declared
+// and arrays once, at the first value or declaration that has one
+pub fn unsupported(program: &Program, analysis: &Analysis, sources: &SourceMap) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = program.body.iter().filter_map(|node| match node {
+        Node::Struct { var } => Some(
+            Diagnostic::at_loc(format!("struct `{}` cannot be lowered to the ir yet",var.ident), sources.locate(var.span), var.span)
+                .with_code("E0600")
+                .with_help("`nemet run` runs programs with structs")
+        ),
+        _ => None,
+    }).collect();
+    let is_array = |ty: &Ty| matches!(ty, Ty::Array(..) | Ty::Slice(_));
+    let resolution = &analysis.resolution;
+    let exprs = analysis.types.exprs.iter().filter(|(_, ty)| is_array(ty)).map(|(span, _)| *span);
+    let symbols = analysis.types.symbols.iter().filter(|(_, ty)| is_array(ty)).map(|(id, _)| resolution.symbol(*id).span);
+    let funcs = analysis.types.funcs.iter().filter(|(_, signature)| is_array(&signature.ret)).map(|(id, _)| resolution.symbol(*id).span);
+    if let Some(span) = exprs.chain(symbols).chain(funcs).min_by_key(|span| (span.file_id, span.start)) {
+        diagnostics.push(Diagnostic::at_loc("arrays cannot be lowered to the ir yet", sources.locate(span), span)
+            .with_code("E0600")
+            .with_help("`nemet run` runs programs with arrays"));
+    }
+    diagnostics
+}
+
+// lowers a program that went through semantic::analyze without errors.
+// uses of folded constants become constant operands
+pub fn lower(program: &Program, analysis: &Analysis) -> Module {
+    let mut module = Module::default();
+    let mut globals = HashMap::new();
+    let mut add_global = |module: &mut Module, var: &VariableDelclear, name: String| {
+        let Some(id) = analysis.resolution.defs.get(&var.span) else { return };
+        let ty = analysis.types.symbols.get(id).cloned().unwrap_or(Ty::Int);
+        let init = match (analysis.consts.values.get(id), &var.init_value) {
+            (Some(value), _) => Some(value.clone()),
+            (None, Some(_)) => None,
+            (None, None) => Some(analysis.types.zero(&ty)),
+        };
+        globals.insert(*id, module.globals.len());
+        module.globals.push(Global { name, ty, init });
+    };
+    for node in &program.body {
+        match node {
+            Node::VariableDelclear { var } => add_global(&mut module, var, analysis.resolution.declared(var.span, &var.ident).to_string()),
+            // statics of a function are globals named after it
+            Node::Func { var: func } => {
+                let mut statics = Vec::new();
+                find_statics(&func.block, &mut statics);
+                for var in statics {
+                    add_global(&mut module, var, format!("{}.{}",analysis.resolution.declared(func.span, &func.ident),var.ident));
+                }
+            },
+            Node::Include { .. } | Node::Struct { .. } | Node::Enum { .. } => (),
+        }
+    }
+
+    // globals that could not be folded are set in order by INIT
+    let mut init = Builder::new(analysis, &globals, INIT, Vec::new(), Ty::Void);
+    for node in &program.body {
+        if let Node::VariableDelclear { var } = node {
+            let id = analysis.resolution.defs.get(&var.span);
+            if let (Some(value), Some(global)) = (&var.init_value, id.and_then(|id| globals.get(id))) {
+                if module.globals[*global].init.is_none() {
+                    let value = init.expr(value);
+                    init.emit(Inst::Store { place: Place::Global(*global), value });
+                }
+            }
+        }
+    }
+    if init.func.blocks.iter().any(|block| !block.insts.is_empty()) {
+        module.funcs.push(init.finish());
+    }
+
+    for node in &program.body {
+        if let Node::Func { var } = node {
+            module.funcs.push(lower_func(analysis, &globals, var));
+        }
+    }
+    module
+}
+
+fn lower_func(analysis: &Analysis, globals: &HashMap<SymbolId, GlobalId>, func: &Func) -> IrFunc {
+    let params: Vec<Ty> = func.args.iter()
+        .map(|arg| analysis.resolution.defs.get(&arg.span).and_then(|id| analysis.types.symbols.get(id)).cloned().unwrap_or(Ty::Int))
+        .collect();
+    let ret = analysis.resolution.defs.get(&func.span)
+        .and_then(|id| analysis.types.funcs.get(id))
+        .map_or(Ty::Void, |signature| signature.ret.clone());
+    let name = analysis.resolution.declared(func.span, &func.ident);
+    let mut builder = Builder::new(analysis, globals, name, params, ret);
+    // arguments are copied into slots so they can be assigned like any variable
+    for (i, arg) in func.args.iter().enumerate() {
+        if let Some(id) = analysis.resolution.defs.get(&arg.span) {
+            let slot = builder.slot(*id, &arg.ident);
+            builder.emit(Inst::Store { place: slot, value: Operand::Reg(i) });
+        }
+    }
+    builder.block(&func.block);
+    builder.finish()
+}
+
+fn find_statics<'a>(body: &'a [Stmt], statics: &mut Vec<&'a VariableDelclear>) {
+    for stmt in body {
+        match stmt {
+            Stmt::VariableDelclear { var } if var.is_static => statics.push(var),
+            Stmt::Block { body, .. } | Stmt::Loop { body, .. } | Stmt::While { body, .. } |
+            Stmt::ForRange { body, .. } | Stmt::ForIn { body, .. } => find_statics(body, statics),
+            Stmt::If { then_block, else_block, .. } => {
+                find_statics(then_block, statics);
+                if let Some(branch) = else_block {
+                    find_statics(std::slice::from_ref(branch.as_ref()), statics);
+                }
+            },
+            Stmt::Match { arms, .. } => {
+                for arm in arms {
+                    find_statics(&arm.body, statics);
+                }
+            },
+            _ => (),
+        }
+    }
+}
+
+struct Builder<'a> {
+    analysis: &'a Analysis,
+    globals: &'a HashMap<SymbolId, GlobalId>,
+    func: IrFunc,
+    slots: HashMap<SymbolId, Place>,
+    current: BlockId,
+    // (continue target, break target) of the loops around the statement
+    loops: Vec<(BlockId, BlockId)>,
+}
+
+impl<'a> Builder<'a> {
+    fn new(analysis: &'a Analysis, globals: &'a HashMap<SymbolId, GlobalId>, name: &str, params: Vec<Ty>, ret: Ty) -> Self {
+        let func = IrFunc {
+            name: name.to_string(),
+            regs: params.clone(),
+            params,
+            ret,
+            slots: Vec::new(),
+            blocks: vec![Block { insts: Vec::new(), term: None }],
+        };
+        Self { analysis, globals, func, slots: HashMap::new(), current: 0, loops: Vec::new() }
+    }
+
+    // ends the last block and drops the blocks nothing jumps to
+    fn finish(mut self) -> IrFunc {
+        let end = if self.func.ret == Ty::Void { Terminator::Return(None) } else { Terminator::Unreachable };
+        self.terminate(end);
+        remove_unreachable(&mut self.func);
+        self.func
+    }
+
+    fn reg(&mut self, ty: Ty) -> VReg {
+        self.func.regs.push(ty);
+        self.func.regs.len() - 1
+    }
+
+    fn emit(&mut self, inst: Inst) {
+        self.func.blocks[self.current].insts.push(inst);
+    }
+
+    fn new_block(&mut self) -> BlockId {
+        self.func.blocks.push(Block { insts: Vec::new(), term: None });
+        self.func.blocks.len() - 1
+    }
+
+    // ends the current block, code after it goes to a new block that is
+    // removed again when nothing jumps to it
+    fn terminate(&mut self, term: Terminator) {
+        self.func.blocks[self.current].term = Some(term);
+        self.current = self.new_block();
+    }
+
+    fn jump_to(&mut self, target: BlockId) {
+        self.func.blocks[self.current].term = Some(Terminator::Jump(target));
+        self.current = target;
+    }
+
+    fn slot(&mut self, id: SymbolId, name: &str) -> Place {
+        let ty = self.symbol_type(id);
+        self.func.slots.push(Slot { name: name.to_string(), ty });
+        let place = Place::Slot(self.func.slots.len() - 1);
+        self.slots.insert(id, place);
+        place
+    }
+
+    // a slot for a value of the lowering itself, `.` keeps it apart from variables
+    fn hidden_slot(&mut self, name: &str, ty: Ty) -> Place {
+        self.func.slots.push(Slot { name: format!(".{}",name), ty });
+        Place::Slot(self.func.slots.len() - 1)
+    }
+
+    fn symbol_type(&self, id: SymbolId) -> Ty {
+        self.analysis.types.symbols.get(&id).cloned().unwrap_or(Ty::Int)
+    }
+
+    fn place(&self, id: SymbolId) -> Place {
+        match self.globals.get(&id) {
+            Some(global) => Place::Global(*global),
+            None => self.slots[&id],
+        }
+    }
+
+    fn load(&mut self, place: Place, ty: Ty) -> Operand {
+        let dst = self.reg(ty.clone());
+        self.emit(Inst::Load { dst, ty, place });
+        Operand::Reg(dst)
+    }
+
+    fn block(&mut self, body: &[Stmt]) {
+        for stmt in body {
+            self.stmt(stmt);
+        }
+    }
+
+    fn variable(&mut self, var: &VariableDelclear) {
+        let Some(id) = self.analysis.resolution.defs.get(&var.span).copied() else { return };
+        // statics are globals with a folded value
+        if var.is_static {
+            return;
+        }
+        let place = self.slot(id, &var.ident);
+        let value = match (self.analysis.consts.values.get(&id), &var.init_value) {
+            (Some(value), _) => Operand::Const(value.clone()),
+            (None, Some(init)) => self.expr(init),
+            (None, None) => Operand::Const(self.analysis.types.zero(&self.symbol_type(id))),
+        };
+        self.emit(Inst::Store { place, value });
+    }
+
+    // starts a loop, the header is where `continue` goes
+    fn in_loop(&mut self, header: BlockId, exit: BlockId, body: &[Stmt]) {
+        self.loops.push((header, exit));
+        self.block(body);
+        self.loops.pop();
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::VariableDelclear { var } => self.variable(var),
+            // a variable or a `module.name`
+            Stmt::Assign { lhs: Expr::Path { span: target, .. } | Expr::Field { span: target, .. }, op, rhs, .. }
+                if self.analysis.resolution.uses.contains_key(target) => {
+                let id = self.analysis.resolution.uses[target];
+                let place = self.place(id);
+                let mut value = self.expr(rhs);
+                if let Some(op) = op.binary_op() {
+                    let ty = self.symbol_type(id);
+                    let current = self.load(place, ty.clone());
+                    let dst = self.reg(ty.clone());
+                    self.emit(Inst::Binary { dst, ty, op, lhs: current, rhs: value });
+                    value = Operand::Reg(dst);
+                }
+                self.emit(Inst::Store { place, value });
+            },
+            Stmt::Assign { .. } => unreachable!("structs and arrays are rejected before lowering"),
+            Stmt::Expr { expr: Expr::Call { args, span, .. }, .. } => {
+                self.call(args, *span);
+            },
+            Stmt::Expr { expr, .. } => {
+                self.expr(expr);
+            },
+            Stmt::DebugPrint { expr, .. } => {
+                let value = self.expr(expr);
+                self.emit(Inst::Print { value });
+            },
+            Stmt::Return { value, .. } => {
+                let value = value.as_ref().map(|value| self.expr(value));
+                self.terminate(Terminator::Return(value));
+            },
+            Stmt::Block { body, .. } => self.block(body),
+            Stmt::If { cond, then_block, else_block, .. } => {
+                let cond = self.expr(cond);
+                let (then, merge) = (self.new_block(), self.new_block());
+                let otherwise = if else_block.is_some() { self.new_block() } else { merge };
+                self.func.blocks[self.current].term = Some(Terminator::Branch { cond, then, otherwise });
+                self.current = then;
+                self.block(then_block);
+                self.jump_to(merge);
+                if let Some(branch) = else_block {
+                    self.current = otherwise;
+                    self.stmt(branch);
+                    self.jump_to(merge);
+                }
+            },
+            Stmt::While { cond, body, .. } => {
+                let (header, body_block, exit) = (self.new_block(), self.new_block(), self.new_block());
+                self.jump_to(header);
+                let cond = self.expr(cond);
+                self.func.blocks[self.current].term = Some(Terminator::Branch { cond, then: body_block, otherwise: exit });
+                self.current = body_block;
+                self.in_loop(header, exit, body);
+                self.jump_to(header);
+                self.current = exit;
+            },
+            Stmt::Loop { body, .. } => {
+                let (body_block, exit) = (self.new_block(), self.new_block());
+                self.jump_to(body_block);
+                self.in_loop(body_block, exit, body);
+                self.jump_to(body_block);
+                self.current = exit;
+            },
+            Stmt::ForRange { ident, from, to, body, span } => {
+                let id = self.analysis.resolution.defs[span];
+                let from = self.expr(from);
+                let to = self.expr(to);
+                let var = self.slot(id, ident);
+                self.emit(Inst::Store { place: var, value: from });
+                self.counted_loop(var, to, body, |_, _| ());
+            },
+            Stmt::ForIn { ident, iter, body, span } => {
+                let id = self.analysis.resolution.defs[span];
+                let iter = self.expr(iter);
+                let len = self.reg(Ty::Int);
+                self.emit(Inst::Len { dst: len, value: iter.clone() });
+                let index = self.hidden_slot("index", Ty::Int);
+                self.emit(Inst::Store { place: index, value: Operand::Const(Value::Int(0)) });
+                let var = self.slot(id, ident);
+                let item_ty = self.symbol_type(id);
+                self.counted_loop(index, Operand::Reg(len), body, |builder, i| {
+                    let dst = builder.reg(item_ty.clone());
+                    builder.emit(Inst::Index { dst, ty: item_ty, base: iter.clone(), index: i });
+                    builder.emit(Inst::Store { place: var, value: Operand::Reg(dst) });
+                });
+            },
+            Stmt::Match { value, arms, .. } => {
+                let value = self.expr(value);
+                let merge = self.new_block();
+                self.arms(value, arms, merge, |builder, body| builder.block(body));
+            },
+            Stmt::Break { .. } => {
+                let (_, exit) = *self.loops.last().expect("break outside of a loop");
+                self.terminate(Terminator::Jump(exit));
+            },
+            Stmt::Continue { .. } => {
+                let (next, _) = *self.loops.last().expect("continue outside of a loop");
+                self.terminate(Terminator::Jump(next));
+            },
+        }
+    }
+
+    // `while counter < end { start(counter); body; counter += 1; }`
+    // where continue goes to the increment
+    fn counted_loop(&mut self, counter: Place, end: Operand, body: &[Stmt], start: impl FnOnce(&mut Self, Operand)) {
+        let (header, body_block, latch, exit) = (self.new_block(), self.new_block(), self.new_block(), self.new_block());
+        self.jump_to(header);
+        let i = self.load(counter, Ty::Int);
+        let cond = self.reg(Ty::Bool);
+        self.emit(Inst::Binary { dst: cond, ty: Ty::Bool, op: Op::Less, lhs: i.clone(), rhs: end });
+        self.func.blocks[self.current].term = Some(Terminator::Branch { cond: Operand::Reg(cond), then: body_block, otherwise: exit });
+        self.current = body_block;
+        start(self, i);
+        self.in_loop(latch, exit, body);
+        self.jump_to(latch);
+        let i = self.load(counter, Ty::Int);
+        let next = self.reg(Ty::Int);
+        self.emit(Inst::Binary { dst: next, ty: Ty::Int, op: Op::Add, lhs: i, rhs: Operand::Const(Value::Int(1)) });
+        self.emit(Inst::Store { place: counter, value: Operand::Reg(next) });
+        self.func.blocks[self.current].term = Some(Terminator::Jump(header));
+        self.current = exit;
+    }
+
+    // tests the arms in order and runs the body of the first that matches,
+    // then goes on at merge. the type checker made sure one of them does
+    fn arms<T>(&mut self, value: Operand, arms: &[MatchArm<T>], merge: BlockId, mut body: impl FnMut(&mut Self, &T)) {
+        for arm in arms {
+            let (then, next) = (self.new_block(), self.new_block());
+            let term = match &arm.pattern {
+                Pattern::Wildcard { .. } => Terminator::Jump(then),
+                Pattern::Variant { variant, .. } => {
+                    let cond = self.reg(Ty::Bool);
+                    self.emit(Inst::IsVariant { dst: cond, value: value.clone(), variant: variant.clone() });
+                    Terminator::Branch { cond: Operand::Reg(cond), then, otherwise: next }
+                },
+            };
+            self.func.blocks[self.current].term = Some(term);
+            self.current = then;
+            if let Pattern::Variant { binding: Some(binding), span, .. } = &arm.pattern {
+                let id = self.analysis.resolution.defs[span];
+                let place = self.slot(id, binding);
+                let ty = self.symbol_type(id);
+                let dst = self.reg(ty.clone());
+                self.emit(Inst::Payload { dst, ty, value: value.clone() });
+                self.emit(Inst::Store { place, value: Operand::Reg(dst) });
+            }
+            body(self, &arm.body);
+            self.jump_to(merge);
+            self.current = next;
+        }
+        self.func.blocks[self.current].term = Some(Terminator::Unreachable);
+        self.current = merge;
+    }
+
+    // the function called at span, with the name it is lowered under. None
+    // when it returns void
+    fn call(&mut self, args: &[Expr], span: Span) -> Option<Operand> {
+        let id = self.analysis.resolution.uses[&span];
+        let func = self.analysis.resolution.symbol(id).name.clone();
+        let ty = self.analysis.types.funcs.get(&id).map_or(Ty::Void, |signature| signature.ret.clone());
+        let args = args.iter().map(|arg| self.expr(arg)).collect();
+        let dst = (ty != Ty::Void).then(|| self.reg(ty.clone()));
+        self.emit(Inst::Call { dst, ty, func, args });
+        dst.map(Operand::Reg)
+    }
+
+    fn expr_type(&self, expr: &Expr) -> Ty {
+        self.analysis.types.expr(expr)
+    }
+
+    fn expr(&mut self, expr: &Expr) -> Operand {
+        match expr {
+            Expr::Literal { value, .. } => Operand::Const(Value::from_literal(value)),
+            Expr::Path { span, .. } | Expr::Field { span, .. } if self.analysis.resolution.uses.contains_key(span) => {
+                let id = self.analysis.resolution.uses[span];
+                if let Some(value) = self.analysis.consts.values.get(&id) {
+                    return Operand::Const(value.clone());
+                }
+                let place = self.place(id);
+                let ty = self.symbol_type(id);
+                self.load(place, ty)
+            },
+            Expr::Unary { op: Op::Add, operand, .. } => self.expr(operand),
+            Expr::Unary { op, operand, .. } => {
+                let operand = self.expr(operand);
+                let ty = self.expr_type(expr);
+                let dst = self.reg(ty.clone());
+                self.emit(Inst::Unary { dst, ty, op: *op, operand });
+                Operand::Reg(dst)
+            },
+            // the right side only runs when the left does not decide, the result goes through a slot
+            Expr::Binary { left, right, op: op @ (Op::AndAnd | Op::OrOr), .. } => {
+                let result = self.hidden_slot("cond", Ty::Bool);
+                let left = self.expr(left);
+                self.emit(Inst::Store { place: result, value: left.clone() });
+                let (rhs, merge) = (self.new_block(), self.new_block());
+                let (then, otherwise) = if *op == Op::AndAnd { (rhs, merge) } else { (merge, rhs) };
+                self.func.blocks[self.current].term = Some(Terminator::Branch { cond: left, then, otherwise });
+                self.current = rhs;
+                let right = self.expr(right);
+                self.emit(Inst::Store { place: result, value: right });
+                self.jump_to(merge);
+                self.load(result, Ty::Bool)
+            },
+            Expr::Binary { left, right, op, .. } => {
+                let lhs = self.expr(left);
+                let rhs = self.expr(right);
+                let ty = self.expr_type(expr);
+                let dst = self.reg(ty.clone());
+                self.emit(Inst::Binary { dst, ty, op: *op, lhs, rhs });
+                Operand::Reg(dst)
+            },
+            Expr::Path { .. } => unreachable!("every name was resolved"),
+            Expr::StructLiteral { .. } | Expr::Field { .. } => unreachable!("structs are rejected before lowering"),
+            Expr::Array { .. } => unreachable!("arrays are rejected before lowering"),
+            // only a str is left to index
+            Expr::Index { base, index, .. } => {
+                let base = self.expr(base);
+                let index = self.expr(index);
+                let dst = self.reg(Ty::Char);
+                self.emit(Inst::Index { dst, ty: Ty::Char, base, index });
+                Operand::Reg(dst)
+            },
+            Expr::Variant { ident, variant, payload: None, span } => {
+                let name = self.analysis.resolution.name(*span, ident).to_string();
+                Operand::Const(Value::Enum { name, variant: variant.clone(), payload: None })
+            },
+            Expr::Variant { variant, payload: Some(payload), .. } => {
+                let payload = self.expr(payload);
+                let ty = self.expr_type(expr);
+                let dst = self.reg(ty.clone());
+                self.emit(Inst::Variant { dst, ty, variant: variant.clone(), payload });
+                Operand::Reg(dst)
+            },
+            Expr::Call { args, span, .. } => self.call(args, *span).expect("the type checker rejects calls to void functions used as values"),
+            // the value of the arm that ran goes through a slot
+            Expr::Match { value, arms, .. } => {
+                let ty = self.expr_type(expr);
+                let result = self.hidden_slot("match", ty.clone());
+                let value = self.expr(value);
+                let merge = self.new_block();
+                self.arms(value, arms, merge, |builder, body| {
+                    let value = builder.expr(body);
+                    builder.emit(Inst::Store { place: result, value });
+                });
+                self.load(result, ty)
+            },
+        }
+    }
+}
+
+// drops blocks that can not be reached from the entry and renumbers the rest
+fn remove_unreachable(func: &mut IrFunc) {
+    let mut reachable = vec![false; func.blocks.len()];
+    let mut stack = vec![0];
+    while let Some(block) = stack.pop() {
+        if !reachable[block] {
+            reachable[block] = true;
+            stack.extend(func.successors(block));
+        }
+    }
+    let mut renumbered = vec![0; func.blocks.len()];
+    let mut next = 0;
+    for (block, keep) in reachable.iter().enumerate() {
+        if *keep {
+            renumbered[block] = next;
+            next += 1;
+        }
+    }
+    let blocks = std::mem::take(&mut func.blocks);
+    func.blocks = blocks.into_iter().zip(&reachable).filter(|(_, keep)| **keep).map(|(mut block, _)| {
+        block.term = block.term.map(|term| match term {
+            Terminator::Jump(target) => Terminator::Jump(renumbered[target]),
+            Terminator::Branch { cond, then, otherwise } => Terminator::Branch { cond, then: renumbered[then], otherwise: renumbered[otherwise] },
+            term => term,
+        });
+        block
+    }).collect();
+}
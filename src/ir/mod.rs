@@ -0,0 +1,295 @@
+pub mod lower;
+pub mod verify;
+
+use std::fmt::Display;
+
+use crate::ast::Op;
+use crate::semantic::types::Ty;
+use crate::value::Value;
+
+// a three-address code lowered from a checked program. variables live in
+// slots read and written with load and store, so every virtual register is
+// assigned exactly once and no phi nodes are needed
+
+// %n, a virtual register of the function
+pub type VReg = usize;
+// bbn, index into IrFunc::blocks
+pub type BlockId = usize;
+// $n, a local variable of the function
+pub type SlotId = usize;
+// @name, index into Module::globals
+pub type GlobalId = usize;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operand {
+    Reg(VReg),
+    Const(Value),
+}
+
+// where a variable lives
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Place {
+    Slot(SlotId),
+    Global(GlobalId),
+}
+
+// every instruction writing a register carries the type of that register
+#[derive(Debug, Clone, PartialEq)]
+pub enum Inst {
+    Copy { dst: VReg, ty: Ty, src: Operand },
+    Binary { dst: VReg, ty: Ty, op: Op, lhs: Operand, rhs: Operand },
+    Unary { dst: VReg, ty: Ty, op: Op, operand: Operand },
+    Load { dst: VReg, ty: Ty, place: Place },
+    Store { place: Place, value: Operand },
+    // number of elements of a string
+    Len { dst: VReg, value: Operand },
+    // element index of a string
+    Index { dst: VReg, ty: Ty, base: Operand, index: Operand },
+    // `# value;`
+    Print { value: Operand },
+    // the variant of the enum ty holding payload, variants without one are constants
+    Variant { dst: VReg, ty: Ty, variant: String, payload: Operand },
+    // whether the enum value is the variant
+    IsVariant { dst: VReg, value: Operand, variant: String },
+    // the payload of an enum value of a variant that has one
+    Payload { dst: VReg, ty: Ty, value: Operand },
+    // calls the function of the module named func, ty is what it returns.
+    // a function returning void writes no register
+    Call { dst: Option<VReg>, ty: Ty, func: String, args: Vec<Operand> },
+}
+impl Inst {
+    // register written by the instruction
+    pub fn def(&self) -> Option<(VReg, Ty)> {
+        match self {
+            Self::Copy { dst, ty, .. } | Self::Binary { dst, ty, .. } | Self::Unary { dst, ty, .. } |
+            Self::Load { dst, ty, .. } | Self::Index { dst, ty, .. } |
+            Self::Variant { dst, ty, .. } | Self::Payload { dst, ty, .. } => Some((*dst, ty.clone())),
+            Self::Len { dst, .. } => Some((*dst, Ty::Int)),
+            Self::IsVariant { dst, .. } => Some((*dst, Ty::Bool)),
+            Self::Call { dst, ty, .. } => dst.map(|dst| (dst, ty.clone())),
+            Self::Store { .. } | Self::Print { .. } => None,
+        }
+    }
+
+    pub fn operands(&self) -> Vec<&Operand> {
+        match self {
+            Self::Copy { src, .. } => vec![src],
+            Self::Binary { lhs, rhs, .. } => vec![lhs, rhs],
+            Self::Unary { operand, .. } => vec![operand],
+            Self::Load { .. } => vec![],
+            Self::Store { value, .. } | Self::Len { value, .. } | Self::Print { value } |
+            Self::IsVariant { value, .. } | Self::Payload { value, .. } => vec![value],
+            Self::Variant { payload, .. } => vec![payload],
+            Self::Index { base, index, .. } => vec![base, index],
+            Self::Call { args, .. } => args.iter().collect(),
+        }
+    }
+}
+
+// the explicit control flow edges leaving a block
+#[derive(Debug, Clone, PartialEq)]
+pub enum Terminator {
+    Jump(BlockId),
+    Branch { cond: Operand, then: BlockId, otherwise: BlockId },
+    Return(Option<Operand>),
+    // the end of a function the type checker proved is never reached
+    Unreachable,
+}
+impl Terminator {
+    pub fn successors(&self) -> Vec<BlockId> {
+        match self {
+            Self::Jump(target) => vec![*target],
+            Self::Branch { then, otherwise, .. } => vec![*then, *otherwise],
+            Self::Return(_) | Self::Unreachable => vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub insts: Vec<Inst>,
+    // None only while the block is being built
+    pub term: Option<Terminator>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Slot {
+    pub name: String,
+    pub ty: Ty,
+}
+
+// the arguments arrive in the registers %0..%n, block 0 is the entry
+#[derive(Debug, Clone, PartialEq)]
+pub struct IrFunc {
+    pub name: String,
+    pub params: Vec<Ty>,
+    pub ret: Ty,
+    pub slots: Vec<Slot>,
+    // type of every register
+    pub regs: Vec<Ty>,
+    pub blocks: Vec<Block>,
+}
+impl IrFunc {
+    pub fn successors(&self, block: BlockId) -> Vec<BlockId> {
+        self.blocks[block].term.as_ref().map_or(Vec::new(), Terminator::successors)
+    }
+
+    pub fn predecessors(&self) -> Vec<Vec<BlockId>> {
+        let mut preds = vec![Vec::new(); self.blocks.len()];
+        for block in 0..self.blocks.len() {
+            for succ in self.successors(block) {
+                if succ < preds.len() {
+                    preds[succ].push(block);
+                }
+            }
+        }
+        preds
+    }
+}
+
+// a top level variable or static. init is the folded value, globals
+// without one are set by the `init` function
+#[derive(Debug, Clone, PartialEq)]
+pub struct Global {
+    pub name: String,
+    pub ty: Ty,
+    pub init: Option<Value>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Module {
+    pub globals: Vec<Global>,
+    pub funcs: Vec<IrFunc>,
+}
+impl Module {
+    pub fn func(&self, name: &str) -> Option<&IrFunc> {
+        self.funcs.iter().find(|func| func.name == name)
+    }
+}
+
+// name of the function setting the globals that could not be folded,
+// `.` can not appear in a nemet identifier
+pub const INIT: &str = ".init";
+
+impl Display for Operand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Reg(reg) => write!(f,"%{}",reg),
+            Self::Const(Value::Str(value)) => write!(f,"{:?}",value),
+            Self::Const(Value::Char(value)) => write!(f,"{:?}",value),
+            Self::Const(value) => write!(f,"{}",value),
+        }
+    }
+}
+impl Display for Place {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Slot(slot) => write!(f,"${}",slot),
+            Self::Global(global) => write!(f,"@{}",global),
+        }
+    }
+}
+impl Display for Inst {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Copy { dst, ty, src } => write!(f,"%{} = copy {} {}",dst,ty,src),
+            Self::Binary { dst, ty, op, lhs, rhs } => write!(f,"%{} = {} {} {}, {}",dst,op_name(*op),ty,lhs,rhs),
+            Self::Unary { dst, ty, op, operand } => write!(f,"%{} = {} {} {}",dst,unary_name(*op),ty,operand),
+            Self::Load { dst, ty, place } => write!(f,"%{} = load {} {}",dst,ty,place),
+            Self::Store { place, value } => write!(f,"store {}, {}",place,value),
+            Self::Len { dst, value } => write!(f,"%{} = len {}",dst,value),
+            Self::Index { dst, ty, base, index } => write!(f,"%{} = index {} {}, {}",dst,ty,base,index),
+            Self::Print { value } => write!(f,"print {}",value),
+            Self::Variant { dst, ty, variant, payload } => write!(f,"%{} = variant {}::{} {}",dst,ty,variant,payload),
+            Self::IsVariant { dst, value, variant } => write!(f,"%{} = isvariant {}, {}",dst,value,variant),
+            Self::Payload { dst, ty, value } => write!(f,"%{} = payload {} {}",dst,ty,value),
+            Self::Call { dst, ty, func, args } => {
+                let args: Vec<String> = args.iter().map(ToString::to_string).collect();
+                match dst {
+                    Some(dst) => write!(f,"%{} = call {} {}({})",dst,ty,func,args.join(", ")),
+                    None => write!(f,"call {}({})",func,args.join(", ")),
+                }
+            },
+        }
+    }
+}
+impl Display for Terminator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Jump(target) => write!(f,"jmp bb{}",target),
+            Self::Branch { cond, then, otherwise } => write!(f,"br {}, bb{}, bb{}",cond,then,otherwise),
+            Self::Return(Some(value)) => write!(f,"ret {}",value),
+            Self::Return(None) => write!(f,"ret"),
+            Self::Unreachable => write!(f,"unreachable"),
+        }
+    }
+}
+impl Display for IrFunc {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let params: Vec<String> = self.params.iter().enumerate().map(|(i, ty)| format!("%{} {}",i,ty)).collect();
+        writeln!(f,"func {}({}) {} {{",self.name,params.join(", "),self.ret)?;
+        for (i, slot) in self.slots.iter().enumerate() {
+            writeln!(f,"  ${} {} {}",i,slot.name,slot.ty)?;
+        }
+        for (i, block) in self.blocks.iter().enumerate() {
+            writeln!(f,"bb{}:",i)?;
+            for inst in &block.insts {
+                writeln!(f,"  {}",inst)?;
+            }
+            match &block.term {
+                Some(term) => writeln!(f,"  {}",term)?,
+                None => writeln!(f,"  <no terminator>")?,
+            }
+        }
+        writeln!(f,"}}")
+    }
+}
+impl Display for Module {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, global) in self.globals.iter().enumerate() {
+            write!(f,"global @{} {} {}",i,global.name,global.ty)?;
+            match &global.init {
+                Some(value) => writeln!(f," = {}",Operand::Const(value.clone()))?,
+                None => writeln!(f)?,
+            }
+        }
+        for func in &self.funcs {
+            writeln!(f)?;
+            write!(f,"{}",func)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn op_name(op: Op) -> &'static str {
+    match op {
+        Op::Add => "add",
+        Op::Sub => "sub",
+        Op::Multi => "mul",
+        Op::Divide => "div",
+        Op::Mod => "rem",
+        Op::And => "and",
+        Op::Or => "or",
+        Op::Xor => "xor",
+        Op::Lsh => "shl",
+        Op::Rsh => "shr",
+        Op::Eq => "eq",
+        Op::NotEq => "ne",
+        Op::Less => "lt",
+        Op::LessEq => "le",
+        Op::More => "gt",
+        Op::MoreEq => "ge",
+        // lowered to branches, never in an instruction
+        Op::AndAnd => "andand",
+        Op::OrOr => "oror",
+        Op::Not => "not",
+    }
+}
+
+fn unary_name(op: Op) -> &'static str {
+    match op {
+        Op::Sub => "neg",
+        Op::Not => "not",
+        _ => "plus",
+    }
+}
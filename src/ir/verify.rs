@@ -0,0 +1,220 @@
+use crate::ir::{BlockId, Inst, IrFunc, Module, Operand, Place, Terminator};
+use crate::semantic::types::{Ty, binary_type, unary_type};
+
+// checks what every backend relies on:
+// - every block ends in a terminator jumping to blocks that exist and are reachable
+// - every register is written once and that write dominates every read
+// - instructions get operands of the types they expect
+// - calls go to functions of the module with arguments of their parameter types
+// returns one message per problem
+pub fn verify(module: &Module) -> Result<(), Vec<String>> {
+    let mut errors = Vec::new();
+    for global in &module.globals {
+        if let Some(value) = &global.init {
+            if value.ty() != global.ty {
+                errors.push(format!("global {}: initialised with a `{}`",global.name,value.ty()));
+            }
+        }
+    }
+    for func in &module.funcs {
+        Verifier { module, func, errors: &mut errors }.func();
+    }
+    if errors.is_empty() { Ok(()) } else { Err(errors) }
+}
+
+struct Verifier<'a> {
+    module: &'a Module,
+    func: &'a IrFunc,
+    errors: &'a mut Vec<String>,
+}
+
+impl Verifier<'_> {
+    fn error(&mut self, block: BlockId, msg: impl ToString) {
+        self.errors.push(format!("{} bb{}: {}",self.func.name,block,msg.to_string()));
+    }
+
+    fn func(&mut self) {
+        let blocks = self.func.blocks.len();
+        let errors = self.errors.len();
+        if blocks == 0 {
+            self.errors.push(format!("{}: has no blocks",self.func.name));
+            return;
+        }
+        for block in 0..blocks {
+            match &self.func.blocks[block].term {
+                None => self.error(block, "has no terminator"),
+                Some(term) => {
+                    for target in term.successors() {
+                        if target >= blocks {
+                            self.error(block, format!("jumps to bb{} which does not exist",target));
+                        }
+                    }
+                },
+            }
+        }
+        // the rest walks the edges
+        if self.errors.len() > errors {
+            return;
+        }
+        let dominators = dominators(self.func);
+        for (block, dominated_by) in dominators.iter().enumerate() {
+            if dominated_by.is_empty() {
+                self.error(block, "can not be reached from the entry");
+            }
+        }
+        self.registers(&dominators);
+        for block in 0..blocks {
+            self.types(block);
+        }
+    }
+
+    // every register is defined once, before every use on every path
+    fn registers(&mut self, dominators: &[Vec<bool>]) {
+        // (block, position) of the definition, arguments come before position 1
+        let mut defs: Vec<Option<(BlockId, usize)>> = vec![None; self.func.regs.len()];
+        for def in defs.iter_mut().take(self.func.params.len()) {
+            *def = Some((0, 0));
+        }
+        let func = self.func;
+        for (block, data) in func.blocks.iter().enumerate() {
+            for (i, inst) in data.insts.iter().enumerate() {
+                let Some((dst, _)) = inst.def() else { continue };
+                match defs.get(dst) {
+                    None => self.error(block, format!("%{} has no type",dst)),
+                    Some(Some(_)) => self.error(block, format!("%{} is assigned more than once",dst)),
+                    Some(None) => defs[dst] = Some((block, i + 1)),
+                }
+            }
+        }
+        for (block, data) in func.blocks.iter().enumerate() {
+            let term = data.term.iter().flat_map(term_operands);
+            let uses = data.insts.iter().enumerate().flat_map(|(i, inst)| inst.operands().into_iter().map(move |op| (i + 1, op)));
+            let uses: Vec<(usize, &Operand)> = uses.chain(term.map(|op| (data.insts.len() + 1, op))).collect();
+            for (at, operand) in uses {
+                let Operand::Reg(reg) = operand else { continue };
+                let dominated = match defs.get(*reg).copied().flatten() {
+                    None => false,
+                    Some((def_block, def_at)) if def_block == block => def_at < at,
+                    Some((def_block, _)) => dominators[block].get(def_block).copied().unwrap_or(false),
+                };
+                if !dominated {
+                    self.error(block, format!("%{} is used where it may not be defined",reg));
+                }
+            }
+        }
+    }
+
+    fn operand_type(&self, operand: &Operand) -> Ty {
+        match operand {
+            Operand::Reg(reg) => self.func.regs.get(*reg).cloned().unwrap_or(Ty::Error),
+            Operand::Const(value) => value.ty(),
+        }
+    }
+
+    fn place_type(&self, place: Place) -> Option<Ty> {
+        match place {
+            Place::Slot(slot) => self.func.slots.get(slot).map(|slot| slot.ty.clone()),
+            Place::Global(global) => self.module.globals.get(global).map(|global| global.ty.clone()),
+        }
+    }
+
+    fn types(&mut self, block: BlockId) {
+        let func = self.func;
+        let data = &func.blocks[block];
+        for inst in &data.insts {
+            if let Some((dst, ty)) = inst.def() {
+                if self.func.regs.get(dst).is_some_and(|reg| *reg != ty) {
+                    self.error(block, format!("`{}` writes a `{}` to %{} of type `{}`",inst,ty,dst,func.regs[dst]));
+                }
+            }
+            let valid = match inst {
+                Inst::Copy { ty, src, .. } => self.operand_type(src) == *ty,
+                Inst::Binary { ty, op, lhs, rhs, .. } => binary_type(*op, &self.operand_type(lhs), &self.operand_type(rhs)).as_ref() == Some(ty),
+                Inst::Unary { ty, op, operand, .. } => unary_type(*op, &self.operand_type(operand)).as_ref() == Some(ty),
+                Inst::Load { ty, place, .. } => self.place_type(*place).as_ref() == Some(ty),
+                Inst::Store { place, value } => self.place_type(*place) == Some(self.operand_type(value)),
+                Inst::Len { value, .. } => self.operand_type(value) == Ty::Str,
+                Inst::Index { ty, base, index, .. } => {
+                    *ty == Ty::Char && self.operand_type(base) == Ty::Str && self.operand_type(index) == Ty::Int
+                },
+                Inst::Print { value } => !matches!(self.operand_type(value), Ty::Void | Ty::Error),
+                // the module does not know the variants, only that it is an enum
+                Inst::Variant { ty, payload, .. } => {
+                    matches!(ty, Ty::Enum(_)) && !matches!(self.operand_type(payload), Ty::Void | Ty::Error)
+                },
+                Inst::IsVariant { value, .. } | Inst::Payload { value, .. } => matches!(self.operand_type(value), Ty::Enum(_)),
+                Inst::Call { dst, ty, func: name, args } => match self.module.func(name) {
+                    Some(callee) => {
+                        callee.ret == *ty && dst.is_some() == (*ty != Ty::Void) && callee.params.len() == args.len() &&
+                            callee.params.iter().zip(args).all(|(param, arg)| *param == self.operand_type(arg))
+                    },
+                    None => {
+                        self.error(block, format!("`{}` calls a function that does not exist",inst));
+                        continue;
+                    },
+                },
+            };
+            if !valid {
+                self.error(block, format!("`{}` has operands of the wrong type",inst));
+            }
+        }
+        let valid = match &data.term {
+            Some(Terminator::Branch { cond, .. }) => self.operand_type(cond) == Ty::Bool,
+            Some(Terminator::Return(value)) => value.as_ref().map_or(Ty::Void, |value| self.operand_type(value)) == self.func.ret,
+            _ => true,
+        };
+        if !valid {
+            let term = data.term.as_ref().map(ToString::to_string).unwrap_or_default();
+            self.error(block, format!("`{}` has an operand of the wrong type",term));
+        }
+    }
+}
+
+fn term_operands(term: &Terminator) -> Vec<&Operand> {
+    match term {
+        Terminator::Branch { cond, .. } => vec![cond],
+        Terminator::Return(Some(value)) => vec![value],
+        _ => vec![],
+    }
+}
+
+// dominators[b][d] is true when every path from the entry to b goes through d.
+// blocks that can not be reached have no dominators at all
+pub fn dominators(func: &IrFunc) -> Vec<Vec<bool>> {
+    let n = func.blocks.len();
+    let preds = func.predecessors();
+    let mut reachable = vec![false; n];
+    let mut stack = vec![0];
+    while let Some(block) = stack.pop() {
+        if block < n && !reachable[block] {
+            reachable[block] = true;
+            stack.extend(func.successors(block));
+        }
+    }
+    let mut doms: Vec<Vec<bool>> = (0..n).map(|block| if reachable[block] { vec![true; n] } else { Vec::new() }).collect();
+    if n == 0 {
+        return doms;
+    }
+    doms[0] = (0..n).map(|block| block == 0).collect();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for block in 1..n {
+            if !reachable[block] {
+                continue;
+            }
+            let mut new = vec![true; n];
+            for pred in preds[block].iter().filter(|pred| reachable[**pred]) {
+                for (d, dominates) in new.iter_mut().enumerate() {
+                    *dominates &= doms[*pred][d];
+                }
+            }
+            new[block] = true;
+            if new != doms[block] {
+                doms[block] = new;
+                changed = true;
+            }
+        }
+    }
+    doms
+}
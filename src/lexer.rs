@@ -1,7 +1,10 @@
-use std::{fs, process::exit, fmt::Display};
+use std::{fs, fmt::Display};
+
+use crate::diagnostics::Diagnostic;
+use crate::span::{Span, column};
 
 #[allow(dead_code)]
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum TToken {
     Identifier,
     Number,
@@ -9,6 +12,7 @@ pub enum TToken {
     CharLiteral,
     DOLLAR,
     ATSIGN,
+    HASH,
     OPAREN,
     CPAREN,
     OCURLY,
@@ -19,6 +23,7 @@ pub enum TToken {
     DOT,
     COLON,
     SEMICOLON,
+    FATARROW, // =>
     // relational oprators
     EQEQ, // ==
     NOTEQ, // !=
@@ -62,11 +67,17 @@ pub enum TToken {
     BREAK,
     CONTINUE,
     RETURN,
+    STATIC,
+    CONST,
+    MUT,
     INCLUDE,
     TO,
     IN,
     ENUM,
     STRUCT,
+    MATCH,
+    TRUE,
+    FALSE,
     EOF,
 }
 
@@ -90,12 +101,14 @@ impl TToken {
             b'!' => {Some(TToken::NOT)},
             b'$' => {Some(TToken::DOLLAR)},
             b'@' => {Some(TToken::ATSIGN)},
+            b'#' => {Some(TToken::HASH)},
             b':' => {Some(TToken::COLON)},
             b'=' => {Some(TToken::EQ)},
             b'<' => {Some(TToken::LESS)},
             b'>' => {Some(TToken::MORE)},
             b'&' => {Some(TToken::AND)},
             b'|' => {Some(TToken::OR)},
+            b'^' => {Some(TToken::XOR)},
             _ => {None}
         }
     }
@@ -123,22 +136,90 @@ impl TToken {
             b"<<" => {Some(TToken::LEFTSHIFT)},
             b">>" => {Some(TToken::RIGHTSHIFT)},
 
+            b"=>" => {Some(TToken::FATARROW)},
+
             _ => {None}
         }
     }
 
 }
 
-type Loc = (String,usize,usize);
+pub type Loc = (String,usize,usize);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LexErrorKind {
+    UnterminatedString,
+    UnterminatedChar,
+    EmptyChar,
+    UnsupportedChar,
+    UnfinishedEscape,
+    UnknownEscape(u8),
+    UnexpectedByte(u8),
+    UnexpectedEof { expected: Vec<TToken> },
+    UnexpectedToken { expected: Vec<TToken>, found: TToken },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LexError {
+    pub kind: LexErrorKind,
+    pub loc: Loc,
+    pub span: Span,
+}
+impl LexError {
+    pub fn new(kind: LexErrorKind, loc: Loc) -> Self {
+        Self { kind, loc, span: Span::default() }
+    }
+}
+impl LexErrorKind {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::UnterminatedString => "E0001",
+            Self::UnterminatedChar => "E0002",
+            Self::EmptyChar => "E0003",
+            Self::UnsupportedChar => "E0004",
+            Self::UnfinishedEscape => "E0005",
+            Self::UnknownEscape(_) => "E0006",
+            Self::UnexpectedByte(_) => "E0007",
+            Self::UnexpectedToken { .. } => "E0100",
+            Self::UnexpectedEof { .. } => "E0101",
+        }
+    }
+}
+impl Display for LexErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnterminatedString => write!(f,"string literal not closed"),
+            Self::UnterminatedChar => write!(f,"char literal not closed"),
+            Self::EmptyChar => write!(f,"char literal can not be empty"),
+            Self::UnsupportedChar => write!(f,"unsupported char"),
+            Self::UnfinishedEscape => write!(f,"unfinished escape sequence"),
+            Self::UnknownEscape(escape) => 
+                write!(f,"unsupported escape sequence (\\{})",*escape as char),
+            Self::UnexpectedByte(byte) => 
+                write!(f,"unexpected character ({})",*byte as char),
+            Self::UnexpectedEof { expected } => 
+                write!(f,"expect one of {:?} found EOF",expected),
+            Self::UnexpectedToken { expected, found } => 
+                write!(f,"expect one of {:?} found {:?}",expected,found),
+        }
+    }
+}
+impl Display for LexError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f,"{} :{}:{}:{}",self.kind,self.loc.0,self.loc.1,self.loc.2)
+    }
+}
 
 #[allow(dead_code)]
 #[derive(Debug)]
 pub struct Lexer {
     file_path: String,
+    file_id: usize,
     source: Vec<u8>,
     cur: usize,
     bol: usize,
     row: usize,
+    diagnostics: Vec<Diagnostic>,
 }
 
 #[allow(dead_code)]
@@ -149,6 +230,7 @@ pub struct Token {
     pub file_path : String,
     pub col: usize,
     pub line: usize,
+    pub span: Span,
 }
 impl Display for Token {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -163,7 +245,7 @@ impl Display for Token {
 }
 impl Token {
     pub fn new(ttype: TToken, literal: Vec<u8>,loc : Loc) -> Self {
-        Self {ttype, literal, file_path: loc.0, line: loc.1, col: loc.2}
+        Self {ttype, literal, file_path: loc.0, line: loc.1, col: loc.2, span: Span::default()}
     }
 
     pub fn get_literal_string(&self) -> String {
@@ -172,21 +254,78 @@ impl Token {
 }
 
 impl Lexer {
-    pub fn new(file_path: impl ToString) -> Self {
-        let buf = fs::read(file_path.to_string()).unwrap();
-        Self { file_path: file_path.to_string(), source: buf , cur: 0, bol: 0, row: 0 }
+    // reads the file, a missing file is the caller's to report
+    pub fn new(file_path: impl ToString) -> std::io::Result<Self> {
+        let buf = fs::read(file_path.to_string())?;
+        Ok(Self {
+            file_path: file_path.to_string(),
+            file_id: 0,
+            source: buf,
+            cur: 0,
+            bol: 0,
+            row: 0,
+            diagnostics: Vec::new(),
+        })
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(source: impl ToString) -> Self {
+        Self::from_source("INTERNAL", source)
+    }
+
+    // source that was already read, file_path is only used in diagnostics
+    pub fn from_source(file_path: impl ToString, source: impl ToString) -> Self {
         Self {
-            file_path: "INTERNAL".to_string(), 
-            source: source.to_string().as_bytes().to_vec(), 
-            cur: 0, 
-            bol: 0, 
-            row: 0
+            file_path: file_path.to_string(),
+            file_id: 0,
+            source: source.to_string().as_bytes().to_vec(),
+            cur: 0,
+            bol: 0,
+            row: 0,
+            diagnostics: Vec::new(),
         }
     }
 
+    // id of this file in the SourceMap, stored in every span the lexer makes
+    pub fn with_file_id(mut self, file_id: usize) -> Self {
+        self.file_id = file_id;
+        self
+    }
+
+    pub fn file_id(&self) -> usize {
+        self.file_id
+    }
+
+    pub fn source(&self) -> String {
+        String::from_utf8_lossy(&self.source).into_owned()
+    }
+
+    // file, line and column of a span made by this lexer
+    pub fn locate(&self, span: Span) -> Loc {
+        let before = &self.source[..span.start.min(self.source.len())];
+        let line = before.iter().filter(|c| **c == b'\n').count() + 1;
+        let bol = before.iter().rposition(|c| *c == b'\n').map_or(0, |i| i + 1);
+        (self.file_path.clone(), line, column(&before[bol..]))
+    }
+
+    pub fn peek_token(&mut self) -> Result<Token, LexError> {
+        let (cur, bol, row) = (self.cur, self.bol, self.row);
+        let token = self.next_token();
+        self.cur = cur;
+        self.bol = bol;
+        self.row = row;
+        token
+    }
+
+    // errors the parser recovered from, collected until the caller takes them
+    pub fn report(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+
+    pub fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.diagnostics)
+    }
+
     fn drop_char(&mut self) {
         if !self.is_empty() {
             let char = self.source[self.cur];
@@ -207,6 +346,13 @@ impl Lexer {
         }
     }
 
+    fn drop_word(&mut self) {
+        while !self.is_empty() &&
+            (self.source[self.cur].is_ascii_alphanumeric() || self.source[self.cur] == b'_') {
+                self.drop_char();
+        }
+    }
+
     fn is_empty(&self) -> bool {
         self.cur >= self.source.len()
     }
@@ -217,110 +363,100 @@ impl Lexer {
         }
     }
 
-    pub fn next_token(&mut self) -> Token {
+    pub fn next_token(&mut self) -> Result<Token, LexError> {
         self.trim_left();
-        while !self.is_empty() {
-            let sub = self.source[self.cur..self.cur+1].to_vec();
-            if sub != b"//" {break;}
+        while self.source[self.cur..].starts_with(b"//") {
             self.drop_line();
             self.trim_left();
         }
-        let loc = (self.file_path.to_string(), self.row + 1, self.cur - self.bol + 1);
-        if self.is_empty() {return Token::new(TToken::EOF, vec![], loc);}
+        let start = self.cur;
+        let loc = (self.file_path.to_string(), self.row + 1, column(&self.source[self.bol..self.cur]));
+        match self.lex_token(loc) {
+            Ok(mut token) => {
+                token.span = Span::new(self.file_id, start, self.cur);
+                Ok(token)
+            },
+            Err(mut err) => {
+                err.span = Span::new(self.file_id, start, self.cur);
+                Err(err)
+            },
+        }
+    }
+
+    fn lex_token(&mut self, loc: Loc) -> Result<Token, LexError> {
+        if self.is_empty() {return Ok(Token::new(TToken::EOF, vec![], loc));}
         
         let first = self.source[self.cur];
 
         if first.is_ascii_alphabetic() || first == b'_' {
             let index = self.cur;
-            while !self.is_empty() && 
-                (self.source[self.cur].is_ascii_alphanumeric() || self.source[self.cur] == b'_') {
-                    self.drop_char();
-            }
+            self.drop_word();
             let literal = self.source[index..self.cur].to_vec();
-            match literal.as_slice() {
-                b"if" => {return Token::new(TToken::IF,literal,loc);}
-                b"else" => {return Token::new(TToken::ELSE,literal,loc);}
-                b"for" => {return Token::new(TToken::FOR,literal,loc);}
-                b"while" => {return Token::new(TToken::WHILE,literal,loc);}
-                b"loop" => {return Token::new(TToken::LOOP,literal,loc);}
-                b"break" => {return Token::new(TToken::BREAK,literal,loc);}
-                b"continue" => {return Token::new(TToken::CONTINUE,literal,loc);}
-                b"return" => {return Token::new(TToken::RETURN,literal,loc);}
-                b"include" => {return Token::new(TToken::INCLUDE,literal,loc);}
-                b"to" => {return Token::new(TToken::TO,literal,loc);}
-                b"in" => {return Token::new(TToken::IN,literal,loc);}
-                b"enum" => {return Token::new(TToken::ENUM,literal,loc);}
-                b"fun" => {return Token::new(TToken::Fun,literal,loc);}
-                b"struct" => {return Token::new(TToken::STRUCT,literal,loc);}
-                _ => {
-                    return Token::new(TToken::Identifier,literal,loc);
-                }
-            }
+            let ttype = match literal.as_slice() {
+                b"if" => TToken::IF,
+                b"else" => TToken::ELSE,
+                b"for" => TToken::FOR,
+                b"while" => TToken::WHILE,
+                b"loop" => TToken::LOOP,
+                b"break" => TToken::BREAK,
+                b"continue" => TToken::CONTINUE,
+                b"return" => TToken::RETURN,
+                b"static" => TToken::STATIC,
+                b"const" => TToken::CONST,
+                b"mut" => TToken::MUT,
+                b"include" => TToken::INCLUDE,
+                b"to" => TToken::TO,
+                b"in" => TToken::IN,
+                b"enum" => TToken::ENUM,
+                b"fun" | b"func" => TToken::Fun,
+                b"struct" => TToken::STRUCT,
+                b"match" => TToken::MATCH,
+                b"true" => TToken::TRUE,
+                b"false" => TToken::FALSE,
+                _ => TToken::Identifier,
+            };
+            return Ok(Token::new(ttype,literal,loc));
         }
 
+        // the whole literal including a fraction and `f`, `b2`, `b8` or `b16`
+        // suffixes, the parser checks it with Literal::parse_number
         if first.is_ascii_digit() {
             let index = self.cur;
-            while !self.is_empty() && self.source[self.cur].is_ascii_digit() {
+            self.drop_word();
+            if self.source[self.cur..].starts_with(b".") &&
+                self.source.get(self.cur + 1).is_some_and(|c| c.is_ascii_digit()) {
                 self.drop_char();
+                self.drop_word();
             }
             let literal = self.source[index..self.cur].to_vec();
-            return Token::new(TToken::Number,literal,loc);
+            return Ok(Token::new(TToken::Number,literal,loc));
         }
         
         if first == b'\'' {
             self.drop_char();
-            let mut literal = Vec::<u8>::new();
+            if self.is_empty() {
+                return Err(LexError::new(LexErrorKind::UnterminatedChar, loc));
+            }
             let char = self.source[self.cur];
             if char == b'\'' {
-                println!("char literal can not be empty :{}:{}:{}",loc.0,loc.1,loc.2);
-                exit(1);
+                self.drop_char();
+                return Err(LexError::new(LexErrorKind::EmptyChar, loc));
             }
-            if char == b'\\' {
+            let literal = if char == b'\\' {
                 self.drop_char();
-                if self.is_empty() {
-                    println!("char literal unfinished escape sequence :{}:{}:{}",loc.0,loc.1,loc.2);
-                    exit(1);
-                }
-                let escape = self.source[self.cur];
-                match escape {
-                    b'n' => {
-                        literal.push(b'\n');
-                        self.drop_char();
-                    },
-                    b'\'' => {
-                        literal.push(b'\'');
-                        self.drop_char();
-                    },
-                    b't' => {
-                        literal.push(b'\t');
-                        self.drop_char();
-                    },
-                    b'r' => {
-                        literal.push(b'\r');
-                        self.drop_char();
-                    },
-                    b'\\' => {
-                        literal.push(b'\\');
-                        self.drop_char();
-                    },
-                    _ => {
-                        println!("unsupported escape sequence (\\{}) :{}:{}:{}",escape,loc.0,loc.1,loc.2);
-                        exit(1);
-                    }
-                }
+                vec![self.escape_sequence(b'\'', &loc)?]
             }else{
-                literal.push(char);
                 self.drop_char();
+                vec![char]
+            };
+            if self.is_empty() {
+                return Err(LexError::new(LexErrorKind::UnterminatedChar, loc));
             }
-
-            if !self.is_empty() {
-                if self.source[self.cur] != b'\'' {
-                    println!("unsupported char :{}:{}:{}",loc.0,loc.1,loc.2);
-                    exit(1);
-                }
-                self.drop_char();
-                return Token::new(TToken::CharLiteral,literal,loc);
+            if self.source[self.cur] != b'\'' {
+                return Err(LexError::new(LexErrorKind::UnsupportedChar, loc));
             }
+            self.drop_char();
+            return Ok(Token::new(TToken::CharLiteral,literal,loc));
         }
 
         if first == b'"' {
@@ -330,96 +466,87 @@ impl Lexer {
                 let char = self.source[self.cur];
                 if char == b'"' {break;}
                 if char == b'\n' {
-                    println!("string literal not closed before end of line :{}:{}:{}",loc.0,loc.1,loc.2);
-                    exit(1);
+                    return Err(LexError::new(LexErrorKind::UnterminatedString, loc));
                 }
+                self.drop_char();
                 if char == b'\\' {
-                    self.drop_char();
-                    if self.is_empty() {
-                        println!("string literal unfinished escape sequence :{}:{}:{}",loc.0,loc.1,loc.2);
-                        exit(1);
-                    }
-
-                    let escape = self.source[self.cur];
-                    match escape {
-                        b'n' => {
-                            literal.push(b'\n');
-                            self.drop_char();
-                        },
-                        b'"' => {
-                            literal.push(b'"');
-                            self.drop_char();
-                        },
-                        b't' => {
-                            literal.push(b'\t');
-                            self.drop_char();
-                        },
-                        b'r' => {
-                            literal.push(b'\r');
-                            self.drop_char();
-                        },
-                        b'\\' => {
-                            literal.push(b'\\');
-                            self.drop_char();
-                        },
-                        _ => {
-                            println!("unsupported escape sequence (\\{}) :{}:{}:{}",escape,loc.0,loc.1,loc.2);
-                            exit(1);
-                        }
-                    }
+                    literal.push(self.escape_sequence(b'"', &loc)?);
+                }else {
+                    literal.push(char);
                 }
-                literal.push(char);
-                self.drop_char();
             }
-            if !self.is_empty() {
-                self.drop_char();
-                return Token::new(TToken::StringLiteral,literal,loc);
+            if self.is_empty() {
+                return Err(LexError::new(LexErrorKind::UnterminatedString, loc));
             }
+            self.drop_char();
+            return Ok(Token::new(TToken::StringLiteral,literal,loc));
         }
 
-        match TToken::is_single_char_token(first) {
-            Some(tt) => {
-                self.drop_char();
-                if !self.is_empty(){
-                    let next = self.source[self.cur];
-                    if TToken::is_single_char_token(next).is_some() {
-                        match TToken::is_double_char_token(first, next) {
-                            Some(dtt) => {
-                                self.drop_char();
-                                return Token::new(dtt,vec![first,next],loc);
-                            },
-                            None => ()
-                        }
+        if let Some(tt) = TToken::is_single_char_token(first) {
+            self.drop_char();
+            if !self.is_empty(){
+                let next = self.source[self.cur];
+                if TToken::is_single_char_token(next).is_some() {
+                    if let Some(dtt) = TToken::is_double_char_token(first, next) {
+                        self.drop_char();
+                        return Ok(Token::new(dtt,vec![first,next],loc));
                     }
                 }
-                return Token::new(tt,[first].to_vec(),loc);
-            },
-            None => ()
+            }
+            return Ok(Token::new(tt,[first].to_vec(),loc));
         }
 
-        println!("string literal not closed before EOF :{}:{}:{}",loc.0,loc.1,loc.2);
-        exit(1);
+        self.drop_char();
+        Err(LexError::new(LexErrorKind::UnexpectedByte(first), loc))
+    }
+
+    // expects the backslash to be already consumed
+    fn escape_sequence(&mut self, quote: u8, loc: &Loc) -> Result<u8, LexError> {
+        if self.is_empty() {
+            return Err(LexError::new(LexErrorKind::UnfinishedEscape, loc.clone()));
+        }
+        let escape = self.source[self.cur];
+        let Some(char) = unescape(escape, quote) else {
+            return Err(LexError::new(LexErrorKind::UnknownEscape(escape), loc.clone()));
+        };
+        self.drop_char();
+        Ok(char)
+    }
+}
+
+// the char a `\` escape stands for inside a literal closed by quote
+pub fn unescape(escape: u8, quote: u8) -> Option<u8> {
+    match escape {
+        b'n' => Some(b'\n'),
+        b't' => Some(b'\t'),
+        b'r' => Some(b'\r'),
+        b'\\' => Some(b'\\'),
+        _ if escape == quote => Some(quote),
+        _ => None,
     }
 }
 
-pub fn expect_non_empty_token(token: &Token){
+pub fn expect_non_empty_token(token: &Token) -> Result<(), LexError> {
     if token.ttype == TToken::EOF {
-        println!("expect token found EOF {}:{}:{}",token.file_path,token.line,token.col);
-        exit(1);
+        return Err(LexError {
+            kind: LexErrorKind::UnexpectedEof { expected: Vec::new() },
+            loc: (token.file_path.clone(),token.line,token.col),
+            span: token.span,
+        });
     }
+    Ok(())
 }
 
-pub fn expect_token(lexer: &mut Lexer, types:Vec<TToken>) -> Token {
-    let token = lexer.next_token();
-    
+pub fn expect_token(lexer: &mut Lexer, types:Vec<TToken>) -> Result<Token, LexError> {
+    let token = lexer.next_token()?;
+    let loc = (token.file_path.clone(),token.line,token.col);
     if token.ttype == TToken::EOF {
-        println!("expect one of {:?} found EOF {}:{}:{}",types,token.file_path,token.line,token.col);
-        exit(1);
+        return Err(LexError { kind: LexErrorKind::UnexpectedEof { expected: types }, loc, span: token.span });
     }
     if types.contains(&token.ttype) {
-        return token;
+        Ok(token)
     }else {
-        println!("expect one of {:?} found {:?} {}:{}:{}",types,token.ttype,token.file_path,token.line,token.col);
-        exit(1);
+        let kind = LexErrorKind::UnexpectedToken { expected: types, found: token.ttype };
+        Err(LexError { kind, loc, span: token.span })
     }
 }
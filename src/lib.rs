@@ -0,0 +1,26 @@
+// parse results carry a full Diagnostic, errors are not on a hot path
+#![allow(clippy::result_large_err)]
+
+extern crate pest;
+#[macro_use]
+extern crate pest_derive;
+
+pub mod span;
+pub mod ast;
+pub mod lexer;
+pub mod diagnostics;
+pub mod value;
+pub mod parser;
+pub mod pest_parser;
+pub mod semantic;
+pub mod frontend;
+pub mod modules;
+pub mod interpreter;
+pub mod ir;
+pub mod codegen;
+pub mod bytecode;
+pub mod repl;
+pub mod cli;
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::ast::{Node, Program};
+use crate::diagnostics::Diagnostic;
+use crate::lexer::Lexer;
+use crate::span::{SourceMap, Span};
+
+// loads the file at path and every file it includes into one program. each
+// file is registered once in sources, however often it is included, and its
+// nodes come before the nodes of the first file including it. parse turns
+// (path, source, file id) into the nodes of one file
+pub fn load(path: &str, source: &str, sources: &mut SourceMap, parse: impl FnMut(&str, &str, usize) -> Program) -> Program {
+    let file_id = sources.add(path, source);
+    let mut loader = Loader { sources, parse, loaded: HashMap::new(), stack: Vec::new(), program: Program::default() };
+    loader.loaded.insert(canonical(Path::new(path)), file_id);
+    loader.file(file_id);
+    loader.program
+}
+
+struct Loader<'a, F> {
+    sources: &'a mut SourceMap,
+    parse: F,
+    // canonical path of every file loaded so far to its id
+    loaded: HashMap<PathBuf, usize>,
+    // the files whose includes are being loaded, outermost first
+    stack: Vec<usize>,
+    program: Program,
+}
+
+impl<F: FnMut(&str, &str, usize) -> Program> Loader<'_, F> {
+    fn file(&mut self, file_id: usize) {
+        let file = self.sources.get(file_id).unwrap();
+        let (path, source) = (file.path.clone(), file.source.clone());
+        self.stack.push(file_id);
+        let mut program = (self.parse)(&path, &source, file_id);
+        for node in &program.body {
+            if let Node::Include { var } = node {
+                self.include(&path, &var.path, var.span);
+            }
+        }
+        self.program.body.append(&mut program.body);
+        self.program.diagnostics.append(&mut program.diagnostics);
+        self.stack.pop();
+    }
+
+    fn include(&mut self, from: &str, include: &str, span: Span) {
+        let path = Path::new(from).parent().unwrap_or(Path::new("")).join(include);
+        let path = path.to_string_lossy().into_owned();
+        let canonical = canonical(Path::new(&path));
+        if let Some(file_id) = self.loaded.get(&canonical).copied() {
+            match self.stack.iter().position(|id| *id == file_id) {
+                Some(start) => {
+                    let diagnostic = self.cycle(&self.stack[start..], span);
+                    self.program.diagnostics.push(diagnostic);
+                },
+                None => self.sources.include(span, file_id),
+            }
+            return;
+        }
+        let lexer = match Lexer::new(&path) {
+            Ok(lexer) => lexer,
+            Err(e) => {
+                let diagnostic = Diagnostic::at_loc(format!("cannot read `{}`: {}",path,e), self.sources.locate(span), span)
+                    .with_code("E0107")
+                    .with_note(format!("the path is relative to `{}`",from));
+                self.program.diagnostics.push(diagnostic);
+                return;
+            },
+        };
+        // qualified names are `module.name`, two modules cannot share a name
+        let module = Path::new(&path).file_stem().map(|stem| stem.to_string_lossy().into_owned());
+        let taken = self.loaded.values().find(|id| self.sources.module(**id).map(str::to_string) == module);
+        if let Some(other) = taken {
+            let other = self.sources.get(*other).unwrap().path.clone();
+            let diagnostic = Diagnostic::at_loc(format!("module `{}` is already included from `{}`",module.unwrap_or_default(),other), self.sources.locate(span), span)
+                .with_code("E0109")
+                .with_help("rename one of the files, the module is named after the file");
+            self.program.diagnostics.push(diagnostic);
+            return;
+        }
+        let file_id = self.sources.add(&path, lexer.source());
+        self.sources.include(span, file_id);
+        self.loaded.insert(canonical, file_id);
+        self.file(file_id);
+    }
+
+    // files is the part of the stack from the file included again to the includer
+    fn cycle(&self, files: &[usize], span: Span) -> Diagnostic {
+        let mut chain: Vec<String> = files.iter().map(|id| format!("`{}`",self.sources.get(*id).unwrap().path)).collect();
+        chain.push(chain[0].clone());
+        Diagnostic::at_loc("include cycle", self.sources.locate(span), span)
+            .with_code("E0108")
+            .with_note(format!("{} includes itself through {}",chain[0],chain.join(" -> ")))
+            .with_help("move what the files share into a file that includes neither")
+    }
+}
+
+// two paths to the same file load it once
+fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
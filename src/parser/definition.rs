@@ -1,10 +1,58 @@
-#[derive(Debug,PartialEq)]
-pub struct Type {
-    pub name: String,
+use crate::ast::{Arg, Literal, Type};
+use crate::diagnostics::Diagnostic;
+use crate::lexer::{Lexer, TToken, Token, expect_token};
+use crate::parser::ParseResult;
+use crate::span::Span;
+
+impl Type {
+    // `@`? (ident (. ident)? | [ type (; len)? ])
+    pub fn new(lexer: &mut Lexer) -> ParseResult<Self> {
+        let token = expect_token(lexer, vec![TToken::Identifier, TToken::ATSIGN, TToken::OBRACE])?;
+        let start = token.span;
+        let first = match token.ttype {
+            TToken::ATSIGN => expect_token(lexer, vec![TToken::Identifier, TToken::OBRACE])?,
+            _ => token,
+        };
+        if first.ttype == TToken::OBRACE {
+            let element = Self::new(lexer)?;
+            let mut len = None;
+            if lexer.peek_token()?.ttype == TToken::SEMICOLON {
+                lexer.next_token()?;
+                let token = expect_token(lexer, vec![TToken::Number])?;
+                let length = match Literal::parse_number(&token.get_literal_string()) {
+                    Some(Literal::Int(value)) => u32::try_from(value).ok(),
+                    _ => None,
+                };
+                let Some(value) = length else {
+                    return Err(Diagnostic::at_token(format!("invalid array length `{}`",token.get_literal_string()), &token)
+                        .with_code("E0103")
+                        .with_note("the length of an array is an integer literal"));
+                };
+                len = Some(value);
+            }
+            let end = expect_token(lexer, vec![TToken::CBRACE])?.span;
+            return Ok(Self::array(element, len, start.to(end)));
+        }
+        let (name, end) = get_path(lexer, first)?;
+        Ok(Self::named(name, start.to(end)))
+    }
 }
-#[derive(Debug)]
-pub struct Arg {
-    pub ident: String,
-    pub kind: Type,
+
+impl Arg {
+    // ident type
+    pub fn new(lexer: &mut Lexer) -> ParseResult<Self> {
+        let token = expect_token(lexer, vec![TToken::Identifier])?;
+        let kind = Type::new(lexer)?;
+        Ok(Self { ident: token.get_literal_string(), span: token.span.to(kind.span), kind })
+    }
 }
 
+// `module.name` after the first identifier, returns the name and where it ends
+pub fn get_path(lexer: &mut Lexer, first: Token) -> ParseResult<(String, Span)> {
+    if lexer.peek_token()?.ttype != TToken::DOT {
+        return Ok((first.get_literal_string(), first.span));
+    }
+    lexer.next_token()?;
+    let second = expect_token(lexer, vec![TToken::Identifier])?;
+    Ok((format!("{}.{}",first.get_literal_string(),second.get_literal_string()), second.span))
+}
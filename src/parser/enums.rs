@@ -0,0 +1,104 @@
+use crate::ast::{Enum, Expr, MatchArm, Node, Pattern, Type, Variant};
+use crate::diagnostics::Diagnostic;
+use crate::lexer::{Lexer, TToken, expect_token};
+use crate::parser::{ParseResult, definition::get_path, expr::get_cond_expr};
+use crate::span::Span;
+
+impl Enum {
+    // ident { (variant (, variant)* ,?)? }
+    pub fn new(lexer: &mut Lexer) -> ParseResult<Self> {
+        let token = expect_token(lexer, vec![TToken::Identifier])?;
+        expect_token(lexer, vec![TToken::OCURLY])?;
+        let mut variants = Vec::<Variant>::new();
+        loop {
+            if lexer.peek_token()?.ttype == TToken::CCURLY {
+                break;
+            }
+            variants.push(Variant::new(lexer)?);
+            if lexer.peek_token()?.ttype != TToken::CCURLY {
+                expect_token(lexer, vec![TToken::COMMA, TToken::CCURLY])?;
+            }
+        }
+        let end = expect_token(lexer, vec![TToken::CCURLY])?.span;
+        Ok(Self { ident: token.get_literal_string(), variants, span: token.span.to(end) })
+    }
+    // after `enum`, the span starts at the keyword
+    pub fn get_node(lexer: &mut Lexer, keyword: Span) -> ParseResult<Node> {
+        let mut var = Self::new(lexer)?;
+        var.span = keyword.to(var.span);
+        Ok(Node::Enum { var })
+    }
+}
+
+impl Variant {
+    // ident (( type ))?
+    pub fn new(lexer: &mut Lexer) -> ParseResult<Self> {
+        let token = expect_token(lexer, vec![TToken::Identifier])?;
+        if lexer.peek_token()?.ttype != TToken::OPAREN {
+            return Ok(Self { ident: token.get_literal_string(), payload: None, span: token.span });
+        }
+        lexer.next_token()?;
+        let payload = Type::new(lexer)?;
+        let end = expect_token(lexer, vec![TToken::CPAREN])?.span;
+        Ok(Self { ident: token.get_literal_string(), payload: Some(payload), span: token.span.to(end) })
+    }
+}
+
+impl Pattern {
+    // _ | ident (. ident)? :: ident (( ident ))?
+    pub fn new(lexer: &mut Lexer) -> ParseResult<Self> {
+        let token = expect_token(lexer, vec![TToken::Identifier])?;
+        if token.get_literal_string() == "_" {
+            return Ok(Self::Wildcard { span: token.span });
+        }
+        let start = token.span;
+        let (ident, _) = get_path(lexer, token)?;
+        expect_path_separator(lexer)?;
+        let variant = expect_token(lexer, vec![TToken::Identifier])?;
+        if lexer.peek_token()?.ttype != TToken::OPAREN {
+            return Ok(Self::Variant { ident, variant: variant.get_literal_string(), binding: None, span: start.to(variant.span) });
+        }
+        lexer.next_token()?;
+        let binding = expect_token(lexer, vec![TToken::Identifier])?.get_literal_string();
+        let end = expect_token(lexer, vec![TToken::CPAREN])?.span;
+        Ok(Self::Variant { ident, variant: variant.get_literal_string(), binding: Some(binding), span: start.to(end) })
+    }
+}
+
+// `::` is lexed as two colons, they have to be next to each other
+pub fn expect_path_separator(lexer: &mut Lexer) -> ParseResult<Span> {
+    let first = expect_token(lexer, vec![TToken::COLON])?;
+    let second = expect_token(lexer, vec![TToken::COLON])?;
+    if second.span.start != first.span.end {
+        return Err(Diagnostic::at_token("expected `::` found `: :`", &second)
+            .with_span(first.span.to(second.span))
+            .with_code("E0100"));
+    }
+    Ok(first.span.to(second.span))
+}
+
+// `value { (pattern => body ,?)* }` after `match` at start. the arms of a
+// match expression have to be separated by commas, block arms do not.
+// returns the value, the arms and the span from `match` to the closing curly
+pub fn get_match<T>(lexer: &mut Lexer, start: Span, body: fn(&mut Lexer) -> ParseResult<(T, Span)>, commas: bool) -> ParseResult<(Expr, Vec<MatchArm<T>>, Span)> {
+    let value = get_cond_expr(lexer)?;
+    expect_token(lexer, vec![TToken::OCURLY])?;
+    let mut arms = Vec::new();
+    loop {
+        let token = lexer.peek_token()?;
+        if token.ttype == TToken::CCURLY {
+            lexer.next_token()?;
+            return Ok((value, arms, start.to(token.span)));
+        }
+        let pattern = Pattern::new(lexer)?;
+        expect_token(lexer, vec![TToken::FATARROW])?;
+        let (body, end) = body(lexer)?;
+        arms.push(MatchArm { span: pattern.span().to(end), pattern, body });
+        let token = lexer.peek_token()?;
+        if token.ttype == TToken::COMMA {
+            lexer.next_token()?;
+        }else if commas && token.ttype != TToken::CCURLY {
+            expect_token(lexer, vec![TToken::COMMA, TToken::CCURLY])?;
+        }
+    }
+}
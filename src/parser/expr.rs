@@ -1,65 +1,241 @@
-use crate::lexer::{Lexer, expect_non_empty_token, TToken};
+use crate::ast::{Expr, FieldValue, Literal, Op};
+use crate::diagnostics::Diagnostic;
+use crate::lexer::{Lexer, expect_non_empty_token, expect_token, TToken};
+use crate::parser::{ParseResult, enums::{expect_path_separator, get_match}};
+use crate::span::Span;
 
-pub fn get_expr(lexer : &mut Lexer) -> Expr {
-    let token = lexer.next_token();
-    expect_non_empty_token(&token);
-    match token.ttype {
-       TToken::OPAREN => {
-       },
-       TToken::Identifier => {
-       },
-       TToken::SUB => {
-       },
-       TToken::PLUS => {
-       },
-       TToken::Number => {
-           let op_token = lexer.next_token();
-           if op_token.ttype == TToken::PLUS || op_token.ttype == TToken::SUB {
-               let left_val = String::from_utf8(token.literal).unwrap();
-               return Expr::Binary {
-                   left: Box::new(Expr::Literal { 
-                       value: left_val.parse::<u32>().unwrap() 
-                   }),
-                   right: Box::new(get_expr(lexer)),
-                   op: op_token.ttype,
-               }
-           }else if op_token.ttype == TToken::MULTY || op_token.ttype == TToken::DEVIDE ||
-            op_token.ttype == TToken::MOD {
-                unimplemented!()
+// prefix operators bind tighter than every binary operator
+const UNARY_POWER: u8 = 20;
+
+// (left, right) binding power of binary operators, from the loosest
+// `||` to the tightest `* / %`. left < right makes them left associative
+fn infix_binding_power(ttype: &TToken) -> Option<(u8, u8)> {
+    let power = match ttype {
+        TToken::OROR => 1,
+        TToken::ANDAND => 3,
+        TToken::EQEQ | TToken::NOTEQ | TToken::LESS | TToken::LESSEQ |
+        TToken::MORE | TToken::MOREEQ => 5,
+        TToken::OR => 7,
+        TToken::XOR => 9,
+        TToken::AND => 11,
+        TToken::LEFTSHIFT | TToken::RIGHTSHIFT => 13,
+        TToken::PLUS | TToken::SUB => 15,
+        TToken::MULTY | TToken::DEVIDE | TToken::MOD => 17,
+        _ => return None,
+    };
+    Some((power, power + 1))
+}
+
+pub fn get_expr(lexer : &mut Lexer) -> ParseResult<Expr> {
+    get_expr_bp(lexer, 0, true)
+}
+
+// an expression followed by a block, the condition of `if` and `while` or
+// the header of `for`. `name {` starts the block there, not a struct literal,
+// unless the literal is in parentheses
+pub fn get_cond_expr(lexer : &mut Lexer) -> ParseResult<Expr> {
+    get_expr_bp(lexer, 0, false)
+}
+
+fn get_expr_bp(lexer: &mut Lexer, min_power: u8, structs: bool) -> ParseResult<Expr> {
+    let mut left = get_atom(lexer, structs)?;
+    loop {
+        let op_token = lexer.peek_token()?;
+        let Some((left_power, right_power)) = infix_binding_power(&op_token.ttype) else {
+            break;
+        };
+        if left_power < min_power {
+            break;
+        }
+        lexer.next_token()?;
+        let right = get_expr_bp(lexer, right_power, structs)?;
+        left = Expr::Binary {
+            span: left.span().to(right.span()),
+            left: Box::new(left),
+            right: Box::new(right),
+            op: Op::from_token(&op_token.ttype).unwrap(),
+        };
+    }
+    Ok(left)
+}
+
+// a primary expression followed by any number of `.field` and `[index]`
+fn get_atom(lexer: &mut Lexer, structs: bool) -> ParseResult<Expr> {
+    let token = lexer.peek_token()?;
+    let mut expr = get_primary(lexer, structs)?;
+    if matches!(token.ttype, TToken::SUB | TToken::PLUS | TToken::NOT) {
+        return Ok(expr);
+    }
+    loop {
+        let next = lexer.peek_token()?;
+        if next.ttype == TToken::OBRACE {
+            lexer.next_token()?;
+            let index = get_expr(lexer)?;
+            let end = expect_token(lexer, vec![TToken::CBRACE])?.span;
+            expr = Expr::Index { span: expr.span().to(end), base: Box::new(expr), index: Box::new(index) };
+            continue;
+        }
+        if next.ttype != TToken::DOT {
+            break;
+        }
+        lexer.next_token()?;
+        let field = expect_token(lexer, vec![TToken::Identifier])?;
+        // `module.name::variant` and `module.name { ... }` for the types of an
+        // included file, `module.name(...)` for its functions
+        if let Expr::Path { ident, span } = &expr {
+            if *span == token.span {
+                let path = format!("{}.{}",ident,field.get_literal_string());
+                let next = lexer.peek_token()?.ttype;
+                if next == TToken::OPAREN {
+                    expr = get_call(lexer, path, token.span)?;
+                    continue;
+                }
+                if next == TToken::COLON {
+                    expr = get_variant(lexer, path, token.span)?;
+                    continue;
+                }
+                if structs && next == TToken::OCURLY {
+                    expr = get_struct_literal(lexer, path, token.span)?;
+                    continue;
+                }
             }
-       }
-        _ => ()
+        }
+        expr = Expr::Field { span: expr.span().to(field.span), base: Box::new(expr), ident: field.get_literal_string() };
     }
-    todo!()
+    Ok(expr)
 }
 
-#[derive(Debug, PartialEq)]
-pub enum Expr {
-    Literal{
-        value: u32,
-    },
-    Term{
-        left : Box<Expr>,
-        right: Box<Expr>,
-        op   : TToken,
-    },
-    Binary{
-        left : Box<Expr>,
-        right: Box<Expr>,
-        op   : TToken,
-    },
-    Unary,
-    Path {
-        ident: String,
+// the offending token is left in place so a statement terminator can
+// still be used to recover from the error
+fn get_primary(lexer: &mut Lexer, structs: bool) -> ParseResult<Expr> {
+    let token = lexer.peek_token()?;
+    expect_non_empty_token(&token)?;
+    if !matches!(token.ttype, TToken::OPAREN | TToken::Identifier | TToken::SUB | TToken::PLUS |
+        TToken::NOT | TToken::Number | TToken::StringLiteral | TToken::CharLiteral | TToken::TRUE | TToken::FALSE | TToken::MATCH |
+        TToken::OBRACE) {
+        return Err(Diagnostic::at_token(format!("expected expression found ({:?})",token.ttype), &token)
+            .with_code("E0102"));
+    }
+    lexer.next_token()?;
+    match token.ttype {
+        TToken::OPAREN => {
+            let expr = get_expr_bp(lexer, 0, true)?;
+            expect_token(lexer, vec![TToken::CPAREN])?;
+            Ok(expr)
+        },
+        TToken::Identifier if lexer.peek_token()?.ttype == TToken::COLON => {
+            get_variant(lexer, token.get_literal_string(), token.span)
+        },
+        TToken::Identifier if lexer.peek_token()?.ttype == TToken::OPAREN => {
+            get_call(lexer, token.get_literal_string(), token.span)
+        },
+        TToken::Identifier if structs && lexer.peek_token()?.ttype == TToken::OCURLY => {
+            get_struct_literal(lexer, token.get_literal_string(), token.span)
+        },
+        TToken::Identifier => {
+            Ok(Expr::Path { ident: token.get_literal_string(), span: token.span })
+        },
+        TToken::SUB | TToken::PLUS | TToken::NOT => {
+            let operand = get_expr_bp(lexer, UNARY_POWER, structs)?;
+            Ok(Expr::Unary {
+                span: token.span.to(operand.span()),
+                op: Op::from_token(&token.ttype).unwrap(),
+                operand: Box::new(operand),
+            })
+        },
+        TToken::Number => {
+            let lit = token.get_literal_string();
+            let value = Literal::parse_number(&lit).ok_or_else(|| {
+                let diagnostic = Diagnostic::at_token("invalid number literal", &token).with_code("E0103");
+                if Literal::overflows(&lit) {
+                    diagnostic.with_note(format!("the largest integer literal is {}",i64::MAX))
+                }else {
+                    diagnostic
+                }
+            })?;
+            Ok(Expr::Literal { value, span: token.span })
+        },
+        TToken::StringLiteral => {
+            Ok(Expr::Literal { value: Literal::Str(token.get_literal_string()), span: token.span })
+        },
+        TToken::CharLiteral => {
+            Ok(Expr::Literal { value: Literal::Char(token.literal[0] as char), span: token.span })
+        },
+        TToken::TRUE | TToken::FALSE => {
+            Ok(Expr::Literal { value: Literal::Bool(token.ttype == TToken::TRUE), span: token.span })
+        },
+        TToken::OBRACE => get_array(lexer, token.span),
+        TToken::MATCH => {
+            let arm = |lexer: &mut Lexer| get_expr(lexer).map(|expr| { let span = expr.span(); (expr, span) });
+            let (value, arms, span) = get_match(lexer, token.span, arm, true)?;
+            Ok(Expr::Match { value: Box::new(value), arms, span })
+        },
+        _ => unreachable!(),
     }
 }
 
-#[derive(Debug, PartialEq)]
-pub struct ExprPath {}
+// `{ (ident: expr (, ident: expr)* ,?)? }` after the name of the struct
+fn get_struct_literal(lexer: &mut Lexer, ident: String, start: Span) -> ParseResult<Expr> {
+    expect_token(lexer, vec![TToken::OCURLY])?;
+    let mut fields = Vec::new();
+    loop {
+        if let Some(end) = lexer.peek_token().ok().filter(|token| token.ttype == TToken::CCURLY) {
+            lexer.next_token()?;
+            return Ok(Expr::StructLiteral { ident, fields, span: start.to(end.span) });
+        }
+        let name = expect_token(lexer, vec![TToken::Identifier])?;
+        expect_token(lexer, vec![TToken::COLON])?;
+        let value = get_expr(lexer)?;
+        fields.push(FieldValue { ident: name.get_literal_string(), span: name.span.to(value.span()), value });
+        let token = expect_token(lexer, vec![TToken::COMMA, TToken::CCURLY])?;
+        if token.ttype == TToken::CCURLY {
+            return Ok(Expr::StructLiteral { ident, fields, span: start.to(token.span) });
+        }
+    }
+}
 
-#[derive(Debug, PartialEq)]
-pub struct ExprAssign {
-    pub left: Expr,
-    pub right: Expr,
+// `(expr (, expr)* ,?)? ]` after the opening bracket
+fn get_array(lexer: &mut Lexer, start: Span) -> ParseResult<Expr> {
+    let mut elements = Vec::new();
+    loop {
+        if let Some(end) = lexer.peek_token().ok().filter(|token| token.ttype == TToken::CBRACE) {
+            lexer.next_token()?;
+            return Ok(Expr::Array { elements, span: start.to(end.span) });
+        }
+        elements.push(get_expr(lexer)?);
+        let token = expect_token(lexer, vec![TToken::COMMA, TToken::CBRACE])?;
+        if token.ttype == TToken::CBRACE {
+            return Ok(Expr::Array { elements, span: start.to(token.span) });
+        }
+    }
 }
 
+// `( (expr (, expr)*)? )` after the name of the function
+fn get_call(lexer: &mut Lexer, ident: String, start: Span) -> ParseResult<Expr> {
+    expect_token(lexer, vec![TToken::OPAREN])?;
+    let mut args = Vec::new();
+    if let Some(end) = lexer.peek_token().ok().filter(|token| token.ttype == TToken::CPAREN) {
+        lexer.next_token()?;
+        return Ok(Expr::Call { ident, args, span: start.to(end.span) });
+    }
+    loop {
+        args.push(get_expr(lexer)?);
+        let token = expect_token(lexer, vec![TToken::COMMA, TToken::CPAREN])?;
+        if token.ttype == TToken::CPAREN {
+            return Ok(Expr::Call { ident, args, span: start.to(token.span) });
+        }
+    }
+}
+
+// `:: ident (( expr ))?` after the name of the enum
+fn get_variant(lexer: &mut Lexer, ident: String, start: Span) -> ParseResult<Expr> {
+    expect_path_separator(lexer)?;
+    let variant = expect_token(lexer, vec![TToken::Identifier])?;
+    if lexer.peek_token()?.ttype != TToken::OPAREN {
+        return Ok(Expr::Variant { ident, variant: variant.get_literal_string(), payload: None, span: start.to(variant.span) });
+    }
+    lexer.next_token()?;
+    let payload = get_expr(lexer)?;
+    let end = expect_token(lexer, vec![TToken::CPAREN])?.span;
+    Ok(Expr::Variant { ident, variant: variant.get_literal_string(), payload: Some(Box::new(payload)), span: start.to(end) })
+}
@@ -1,61 +1,42 @@
+use crate::ast::{Arg, Func, Node, Type};
 use crate::lexer::{Lexer, TToken,expect_token};
-use crate::parser::{program::Node,expr::{ExprPath,get_expr},definition::{Arg,Type}};
+use crate::parser::{ParseResult, stmt::{get_block,check_loop_control}};
+use crate::span::Span;
 
-#[derive(Debug)]
-pub struct Func {
-    pub ident: String,
-    pub args: Vec<Arg>,
-    pub return_type: Type,
-    pub block: Vec<Node>,
-}
 impl Func {
-    pub fn new(lexer: &mut Lexer) -> Self {
-        let token = expect_token(lexer, vec![TToken::Identifier]);
+    // ident ( (arg (, arg)*)? ) type? block
+    pub fn new(lexer: &mut Lexer) -> ParseResult<Self> {
+        let token = expect_token(lexer, vec![TToken::Identifier])?;
+        let start = token.span;
         let ident = String::from_utf8(token.literal).unwrap();
-        expect_token(lexer, vec![TToken::OPAREN]);
+        expect_token(lexer, vec![TToken::OPAREN])?;
         let mut args = Vec::<Arg>::new();
-        loop{
-            let token = expect_token(lexer, vec![TToken::Identifier,TToken::CPAREN,TToken::COMMA]);
-            if token.ttype == TToken::Identifier {
-                let type_token = expect_token(lexer, vec![TToken::Identifier]);
-                args.push(Arg {
-                    ident: String::from_utf8(token.literal).unwrap(), 
-                    kind: Type {name: String::from_utf8(type_token.literal).unwrap()}});
-            }
-            else if token.ttype == TToken::CPAREN {break;}
-        }
-        let token = expect_token(lexer, vec![TToken::Identifier]);
-        let return_type = Type { name: String::from_utf8(token.literal).unwrap() };
-        expect_token(lexer, vec![TToken::OCURLY]);
-        loop {
-            let mut token = lexer.next_token();
-            if token.ttype == TToken::CCURLY {
-                break;
-            }
-            loop{
-                if token.ttype == TToken::SEMICOLON {
+        if lexer.peek_token()?.ttype == TToken::CPAREN {
+            lexer.next_token()?;
+        }else {
+            loop {
+                args.push(Arg::new(lexer)?);
+                if expect_token(lexer, vec![TToken::COMMA,TToken::CPAREN])?.ttype == TToken::CPAREN {
                     break;
                 }
-
-                if token.ttype == TToken::Identifier {
-                    let _ident_token = token;
-                    token = lexer.next_token();
-                    if token.ttype == TToken::EQ {
-                        let _left = ExprPath {};
-                        let _right = get_expr(lexer);
-                        todo!("ExprAssign  left, right ");
-                    }
-                }else {
-                    unimplemented!();
-                }
             }
-
         }
-        return Self { ident , args, return_type, block: Vec::new() } ;
+        let token = lexer.peek_token()?;
+        let return_type = if token.ttype == TToken::OCURLY {
+            // no return type, points right before the body
+            Type::named("void", Span::new(start.file_id, token.span.start, token.span.start))
+        }else {
+            Type::new(lexer)?
+        };
+        let (block, end) = get_block(lexer)?;
+        check_loop_control(lexer, &block, false);
+        Ok(Self { ident , args, return_type, block, span: start.to(end) })
     }
-    pub fn get_node(lexer: &mut Lexer) -> Node {
-        Node::Func { var: Self::new(lexer) }
+    // after `fun`, the span starts at the keyword
+    pub fn get_node(lexer: &mut Lexer, keyword: Span) -> ParseResult<Node> {
+        let mut var = Self::new(lexer)?;
+        var.span = keyword.to(var.span);
+        Ok(Node::Func { var })
     }
 
 }
-
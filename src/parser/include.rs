@@ -0,0 +1,19 @@
+use crate::ast::{Include, Node};
+use crate::lexer::{Lexer, TToken, expect_token};
+use crate::parser::ParseResult;
+use crate::span::Span;
+
+impl Include {
+    // "path" ;
+    pub fn new(lexer: &mut Lexer) -> ParseResult<Self> {
+        let path = expect_token(lexer, vec![TToken::StringLiteral])?;
+        let end = expect_token(lexer, vec![TToken::SEMICOLON])?.span;
+        Ok(Self { path: path.get_literal_string(), span: path.span.to(end) })
+    }
+    // after `include`, the span starts at the keyword
+    pub fn get_node(lexer: &mut Lexer, keyword: Span) -> ParseResult<Node> {
+        let mut var = Self::new(lexer)?;
+        var.span = keyword.to(var.span);
+        Ok(Node::Include { var })
+    }
+}
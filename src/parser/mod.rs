@@ -3,3 +3,44 @@ pub mod definition;
 pub mod program;
 pub mod variable;
 pub mod expr; 
+pub mod stmt;
+pub mod structs;
+pub mod enums;
+pub mod include;
+
+use crate::diagnostics::Diagnostic;
+use crate::lexer::{LexError, LexErrorKind, Lexer, TToken, Token, expect_token};
+
+pub type ParseResult<T> = Result<T, Diagnostic>;
+
+// skip tokens until the end of the broken item: right after one of `after`,
+// or right before one of `before` (a token that can only start a new item)
+pub fn synchronize(lexer: &mut Lexer, after: &[TToken], before: &[TToken]) {
+    loop {
+        let token = match lexer.peek_token() {
+            Ok(token) => token,
+            Err(_) => {
+                let _ = lexer.next_token();
+                continue;
+            }
+        };
+        if token.ttype == TToken::EOF || before.contains(&token.ttype) {
+            return;
+        }
+        let _ = lexer.next_token();
+        if after.contains(&token.ttype) {
+            return;
+        }
+    }
+}
+
+// the `;` ending a statement. a `}` in its place is left alone so it still
+// closes the block once the statement is skipped
+pub fn expect_semicolon(lexer: &mut Lexer) -> Result<Token, LexError> {
+    let token = lexer.peek_token()?;
+    if token.ttype == TToken::CCURLY {
+        let kind = LexErrorKind::UnexpectedToken { expected: vec![TToken::SEMICOLON], found: token.ttype };
+        return Err(LexError { kind, loc: (token.file_path.clone(), token.line, token.col), span: token.span });
+    }
+    expect_token(lexer, vec![TToken::SEMICOLON])
+}
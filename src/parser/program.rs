@@ -1,38 +1,41 @@
-use crate::exit;
+use crate::ast::{Enum, Func, Include, Node, Program, Struct, VariableDelclear};
+use crate::diagnostics::Diagnostic;
 use crate::lexer::{Lexer, TToken};
-use crate::parser::{function::Func, variable::VariableDelclear};
-
-#[derive(Debug)]
-pub enum Node {
-    Func {var: Func},
-    VariableDelclear { var: VariableDelclear }
-}
-
-#[derive(Debug)]
-pub struct Program {
-    pub shebang: String,
-    pub body: Vec<Node>,
-}
+use crate::parser::{ParseResult, synchronize};
 
 impl Program {
     pub fn new(lexer: &mut Lexer) -> Self {
         let mut body = Vec::<Node>::new();
         loop {
-            let token = lexer.next_token();
-            if token.ttype == TToken::Fun {
-                body.push(Func::get_node(lexer));
-            }else if token.ttype == TToken::ATSIGN {
-                body.push(VariableDelclear::get_node(lexer));
-            }else if token.ttype == TToken::EOF {
-                break;
-            }else {
-                println!("Syntax error unexpected token ({:?}) at {}:{}:{}",token.ttype,token.file_path,token.line,token.col);
-                exit(1);
+            match Self::get_node(lexer) {
+                Ok(Some(node)) => body.push(node),
+                Ok(None) => break,
+                Err(diagnostic) => {
+                    lexer.report(diagnostic);
+                    synchronize(lexer, &[TToken::SEMICOLON, TToken::CCURLY], &[TToken::Fun, TToken::ATSIGN, TToken::STATIC, TToken::STRUCT, TToken::ENUM, TToken::INCLUDE]);
+                }
             }
         }
         Self {
             shebang: String::new(),
             body,
+            diagnostics: lexer.take_diagnostics(),
+        }
+    }
+
+    fn get_node(lexer: &mut Lexer) -> ParseResult<Option<Node>> {
+        let token = lexer.next_token()?;
+        match token.ttype {
+            TToken::INCLUDE => Ok(Some(Include::get_node(lexer, token.span)?)),
+            TToken::Fun => Ok(Some(Func::get_node(lexer, token.span)?)),
+            TToken::ATSIGN | TToken::STATIC => Ok(Some(VariableDelclear::get_node(lexer, &token)?)),
+            TToken::STRUCT => Ok(Some(Struct::get_node(lexer, token.span)?)),
+            TToken::ENUM => Ok(Some(Enum::get_node(lexer, token.span)?)),
+            TToken::EOF => Ok(None),
+            _ => Err(Diagnostic::at_token(
+                format!("Syntax error unexpected token ({:?})",token.ttype), &token)
+                .with_code("E0100")
+                .with_help("a program can only include files (`include`) and define functions (`fun`), structs (`struct`), enums (`enum`) and variables (`@` or `static`)")),
         }
     }
 }
@@ -0,0 +1,186 @@
+use crate::ast::{AssignOp, Stmt, VariableDelclear};
+use crate::diagnostics::Diagnostic;
+use crate::lexer::{Lexer, TToken, expect_token};
+use crate::parser::{ParseResult, expect_semicolon, synchronize, enums::get_match, expr::{get_cond_expr, get_expr}};
+use crate::span::Span;
+
+impl Stmt {
+    pub fn new(lexer: &mut Lexer) -> ParseResult<Self> {
+        let token = lexer.peek_token()?;
+        match token.ttype {
+            TToken::ATSIGN | TToken::STATIC | TToken::CONST | TToken::MUT => {
+                lexer.next_token()?;
+                Ok(Self::VariableDelclear { var: VariableDelclear::with_keyword(lexer, &token)? })
+            },
+            TToken::HASH => {
+                lexer.next_token()?;
+                let expr = get_expr(lexer)?;
+                let end = expect_semicolon(lexer)?.span;
+                Ok(Self::DebugPrint { expr, span: token.span.to(end) })
+            },
+            TToken::OCURLY => {
+                let (body, span) = get_block(lexer)?;
+                Ok(Self::Block { body, span })
+            },
+            TToken::IF => Self::get_if(lexer),
+            TToken::WHILE => {
+                lexer.next_token()?;
+                let cond = get_cond_expr(lexer)?;
+                let (body, end) = get_block(lexer)?;
+                Ok(Self::While { cond, body, span: token.span.to(end) })
+            },
+            TToken::LOOP => {
+                lexer.next_token()?;
+                let (body, end) = get_block(lexer)?;
+                Ok(Self::Loop { body, span: token.span.to(end) })
+            },
+            TToken::FOR => {
+                lexer.next_token()?;
+                let ident = expect_token(lexer, vec![TToken::Identifier])?.get_literal_string();
+                if lexer.peek_token()?.ttype == TToken::IN {
+                    lexer.next_token()?;
+                    let iter = get_cond_expr(lexer)?;
+                    let (body, end) = get_block(lexer)?;
+                    return Ok(Self::ForIn { ident, iter, body, span: token.span.to(end) });
+                }
+                let from = get_cond_expr(lexer)?;
+                expect_token(lexer, vec![TToken::TO])?;
+                let to = get_cond_expr(lexer)?;
+                let (body, end) = get_block(lexer)?;
+                Ok(Self::ForRange { ident, from, to, body, span: token.span.to(end) })
+            },
+            TToken::MATCH => {
+                lexer.next_token()?;
+                let (value, arms, span) = get_match(lexer, token.span, get_block, false)?;
+                Ok(Self::Match { value, arms, span })
+            },
+            TToken::BREAK | TToken::CONTINUE => {
+                lexer.next_token()?;
+                let span = token.span.to(expect_semicolon(lexer)?.span);
+                if token.ttype == TToken::BREAK {
+                    Ok(Self::Break { span })
+                }else {
+                    Ok(Self::Continue { span })
+                }
+            },
+            TToken::RETURN => {
+                lexer.next_token()?;
+                let value = if lexer.peek_token()?.ttype == TToken::SEMICOLON {
+                    None
+                }else {
+                    Some(get_expr(lexer)?)
+                };
+                let end = expect_semicolon(lexer)?.span;
+                Ok(Self::Return { value, span: token.span.to(end) })
+            },
+            _ => {
+                let expr = get_expr(lexer)?;
+                let op_token = lexer.peek_token()?;
+                if let Some(op) = AssignOp::from_token(&op_token.ttype) {
+                    // parentheses are gone from the tree, `(a) = 1;` is not a place either
+                    if !expr.is_place() || token.ttype != TToken::Identifier {
+                        return Err(Diagnostic::at_token("invalid left-hand side of assignment", &op_token)
+                            .with_span(expr.span())
+                            .with_code("E0104")
+                            .with_help("only variables and their fields and elements can be assigned to"));
+                    }
+                    lexer.next_token()?;
+                    let rhs = get_expr(lexer)?;
+                    let end = expect_semicolon(lexer)?.span;
+                    return Ok(Self::Assign { span: token.span.to(end), lhs: expr, op, rhs });
+                }
+                let end = expect_semicolon(lexer)?.span;
+                Ok(Self::Expr { span: token.span.to(end), expr })
+            },
+        }
+    }
+
+    // if cond { } (else if cond { })* (else { })?
+    fn get_if(lexer: &mut Lexer) -> ParseResult<Self> {
+        let start = expect_token(lexer, vec![TToken::IF])?.span;
+        let cond = get_cond_expr(lexer)?;
+        let (then_block, mut end) = get_block(lexer)?;
+        let mut else_block = None;
+        if lexer.peek_token()?.ttype == TToken::ELSE {
+            lexer.next_token()?;
+            let branch = if lexer.peek_token()?.ttype == TToken::IF {
+                Self::get_if(lexer)?
+            }else {
+                let (body, span) = get_block(lexer)?;
+                Self::Block { body, span }
+            };
+            end = branch.span();
+            else_block = Some(Box::new(branch));
+        }
+        Ok(Self::If { cond, then_block, else_block, span: start.to(end) })
+    }
+}
+
+// reports every `break` and `continue` that is not inside a loop body
+pub fn check_loop_control(lexer: &mut Lexer, body: &[Stmt], in_loop: bool) {
+    for stmt in body {
+        match stmt {
+            Stmt::Break { span } | Stmt::Continue { span } if !in_loop => {
+                let keyword = if matches!(stmt, Stmt::Break { .. }) { "break" } else { "continue" };
+                let diagnostic = Diagnostic::at_loc(format!("`{}` outside of a loop",keyword), lexer.locate(*span), *span)
+                    .with_code("E0105")
+                    .with_help("`break` and `continue` can only be used inside `while`, `loop` and `for`");
+                lexer.report(diagnostic);
+            },
+            Stmt::Block { body, .. } => check_loop_control(lexer, body, in_loop),
+            Stmt::If { then_block, else_block, .. } => {
+                check_loop_control(lexer, then_block, in_loop);
+                if let Some(branch) = else_block {
+                    check_loop_control(lexer, std::slice::from_ref(branch.as_ref()), in_loop);
+                }
+            },
+            Stmt::Match { arms, .. } => {
+                for arm in arms {
+                    check_loop_control(lexer, &arm.body, in_loop);
+                }
+            },
+            Stmt::While { body, .. } | Stmt::Loop { body, .. } |
+            Stmt::ForRange { body, .. } | Stmt::ForIn { body, .. } => check_loop_control(lexer, body, true),
+            _ => (),
+        }
+    }
+}
+
+// `{ stmt* }`, a broken statement is reported and skipped so the rest of
+// the block still gets parsed
+pub fn get_block(lexer: &mut Lexer) -> ParseResult<(Vec<Stmt>, Span)> {
+    let start = expect_token(lexer, vec![TToken::OCURLY])?.span;
+    let mut body = Vec::<Stmt>::new();
+    loop {
+        let token = lexer.peek_token()?;
+        match token.ttype {
+            TToken::CCURLY => {
+                lexer.next_token()?;
+                return Ok((body, start.to(token.span)));
+            },
+            TToken::EOF => {
+                return Err(Diagnostic::at_token("unclosed block, expected `}` found EOF", &token)
+                    .with_span(start)
+                    .with_code("E0101"));
+            },
+            // only items start with these, the `}` went missing
+            TToken::Fun | TToken::STRUCT | TToken::ENUM | TToken::INCLUDE => {
+                return Err(Diagnostic::at_token("unclosed block, expected `}` before the next item", &token)
+                    .with_span(start)
+                    .with_code("E0101"));
+            },
+            _ => match Stmt::new(lexer) {
+                Ok(stmt) => body.push(stmt),
+                Err(diagnostic) => {
+                    lexer.report(diagnostic);
+                    synchronize(lexer, &[TToken::SEMICOLON], &[
+                        TToken::CCURLY, TToken::OCURLY, TToken::ATSIGN, TToken::RETURN, TToken::IF,
+                        TToken::WHILE, TToken::LOOP, TToken::FOR, TToken::BREAK, TToken::CONTINUE,
+                        TToken::STATIC, TToken::CONST, TToken::MUT, TToken::HASH, TToken::MATCH,
+                        TToken::Fun, TToken::STRUCT, TToken::ENUM, TToken::INCLUDE,
+                    ]);
+                },
+            },
+        }
+    }
+}
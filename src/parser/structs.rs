@@ -0,0 +1,30 @@
+use crate::ast::{Arg, Node, Struct};
+use crate::lexer::{Lexer, TToken, expect_token};
+use crate::parser::ParseResult;
+use crate::span::Span;
+
+impl Struct {
+    // ident { (arg (, arg)* ,?)? }
+    pub fn new(lexer: &mut Lexer) -> ParseResult<Self> {
+        let token = expect_token(lexer, vec![TToken::Identifier])?;
+        expect_token(lexer, vec![TToken::OCURLY])?;
+        let mut fields = Vec::<Arg>::new();
+        loop {
+            if lexer.peek_token()?.ttype == TToken::CCURLY {
+                break;
+            }
+            fields.push(Arg::new(lexer)?);
+            if lexer.peek_token()?.ttype != TToken::CCURLY {
+                expect_token(lexer, vec![TToken::COMMA, TToken::CCURLY])?;
+            }
+        }
+        let end = expect_token(lexer, vec![TToken::CCURLY])?.span;
+        Ok(Self { ident: token.get_literal_string(), fields, span: token.span.to(end) })
+    }
+    // after `struct`, the span starts at the keyword
+    pub fn get_node(lexer: &mut Lexer, keyword: Span) -> ParseResult<Node> {
+        let mut var = Self::new(lexer)?;
+        var.span = keyword.to(var.span);
+        Ok(Node::Struct { var })
+    }
+}
@@ -1,89 +1,69 @@
-use crate::lexer::{expect_token,Lexer,TToken};
-use crate::parser::{program::Node, definition::Type};
+use crate::ast::{Node, Type, VariableDelclear};
+use crate::diagnostics::Diagnostic;
+use crate::lexer::{expect_token,Lexer,TToken,Token};
+use crate::parser::{ParseResult, expect_semicolon, expr::get_expr};
+use crate::span::Span;
 
-
-#[derive(Debug,PartialEq)]
-pub struct VariableDelclear {
-    pub is_const: bool,
-    pub is_static: bool,
-    pub ident: String,
-    pub kind: Type,
-    // TODO: Change this shit
-    pub init_value: String,
-}
-
-// [ident, ident, semicolon]
-// [ident, ident, colon, colon, value, semicolon]
-// [ident, ident, colon, value, semicolon]
-// [ident, ident, equal, value, semicolon]
-// [ident, colon, value, semicolon]
-// [ident, equal, value, semicolon]
+// [ident, type, semicolon]
+// [ident, type, colon, colon, expr, semicolon]
+// [ident, type, colon, expr, semicolon]
+// [ident, type, equal, expr, semicolon]
+// [ident, colon, expr, semicolon]
+// [ident, equal, expr, semicolon]
 impl VariableDelclear {
-    pub fn new(lexer: &mut Lexer) -> Self {
-        let is_const: bool;
-        let is_static: bool;
-        let ident: String;
+    pub fn new(lexer: &mut Lexer) -> ParseResult<Self> {
+        let mut is_static = false;
         let kind: Type;
-        let init_value: String;
-        let mut token = expect_token(lexer, vec![TToken::Identifier]);
-        ident = token.get_literal_string();
-        token = expect_token(lexer, vec![TToken::Identifier,TToken::COLON,TToken::EQ]);
-        match token.ttype {
-            TToken::Identifier => {
-                kind = Type { name: token.get_literal_string() };
-                token = expect_token(lexer, vec![TToken::COLON,TToken::EQ,TToken::SEMICOLON]);
-                match token.ttype {
-                    TToken::COLON => {
-                        token = expect_token(lexer, vec![TToken::COLON,TToken::Number,TToken::StringLiteral,TToken::CharLiteral,TToken::Identifier]);
-                        if token.ttype == TToken::COLON {
-                            is_const = true;
-                            is_static = true;
-                            token = expect_token(lexer, vec![TToken::Number,TToken::StringLiteral,TToken::CharLiteral,TToken::Identifier]);
-                        }else {
-                            is_const = true;
-                            is_static = false;
-                        }
-                        init_value = token.get_literal_string();
-                        expect_token(lexer, vec![TToken::SEMICOLON]);
-                    },
-                    TToken::EQ => {
-                        is_const = false;
-                        is_static = false;
-                        token = expect_token(lexer, vec![TToken::Number,TToken::StringLiteral,TToken::CharLiteral,TToken::Identifier]);
-                        init_value = token.get_literal_string();
-                        expect_token(lexer, vec![TToken::SEMICOLON]);
-                    },
-                    TToken::SEMICOLON => {
-                        is_const = false;
-                        is_static = false;
-                        init_value = String::new();
-                    },
-                    _ => {unreachable!();}
-                }
-            },
-            TToken::COLON => {
-                kind = Type { name: "undifiend".to_string() };
-                is_const = true;
-                is_static = false;
-                token = expect_token(lexer, vec![TToken::Number,TToken::StringLiteral,TToken::CharLiteral,TToken::Identifier]);
-                init_value = token.get_literal_string();
-                expect_token(lexer, vec![TToken::SEMICOLON]);
-            },
-            TToken::EQ => {
-                kind = Type { name: "undifiend".to_string() };
-                is_const = false;
-                is_static = false;
-                token = expect_token(lexer, vec![TToken::Number,TToken::StringLiteral,TToken::CharLiteral,TToken::Identifier]);
-                init_value = token.get_literal_string();
-                expect_token(lexer, vec![TToken::SEMICOLON]);
-            },
-            _ => {unreachable!();},
+        let mut token = expect_token(lexer, vec![TToken::Identifier])?;
+        let ident = token.get_literal_string();
+        let start = token.span;
+        let next = lexer.peek_token()?;
+        if matches!(next.ttype, TToken::Identifier | TToken::ATSIGN | TToken::OBRACE) {
+            kind = Type::new(lexer)?;
+            token = expect_token(lexer, vec![TToken::COLON,TToken::EQ,TToken::SEMICOLON])?;
+            if token.ttype == TToken::SEMICOLON {
+                return Ok(Self { is_const: false, is_static, ident, kind, init_value: None, span: start.to(token.span) });
+            }
+            let next = lexer.peek_token()?;
+            if token.ttype == TToken::COLON && next.ttype == TToken::COLON && next.span.start == token.span.end {
+                lexer.next_token()?;
+                is_static = true;
+            }
+        }else {
+            token = expect_token(lexer, vec![TToken::Identifier,TToken::ATSIGN,TToken::OBRACE,TToken::COLON,TToken::EQ])?;
+            // inferred types point right after the identifier
+            kind = Type::named("undifiend", Span::new(start.file_id, start.end, start.end));
         }
-        return Self { is_const, is_static, ident, kind, init_value };
+        let is_const = token.ttype == TToken::COLON;
+        let init_value = get_expr(lexer)?;
+        let end = expect_semicolon(lexer)?.span;
+        Ok(Self { is_const, is_static, ident, kind, init_value: Some(init_value), span: start.to(end) })
     }
 
-    pub fn get_node(lexer: &mut Lexer) -> Node {
-        Node::VariableDelclear { var: Self::new(lexer) }
+    // after `@`, `static`, `const` or `mut`, the span starts at the keyword.
+    // the last three have to agree with the assignment used, `const` and
+    // `mut` may leave the value out
+    pub fn with_keyword(lexer: &mut Lexer, keyword: &Token) -> ParseResult<Self> {
+        let mut var = Self::new(lexer)?;
+        var.span = keyword.span.to(var.span);
+        let (valid, help) = match keyword.ttype {
+            TToken::ATSIGN => (true, ""),
+            TToken::STATIC => (var.is_static, "static variables need a type and `::`, `static name type :: value;`"),
+            TToken::CONST => (!var.is_static && (var.is_const || var.init_value.is_none()), "constants use `:`, `const name type? : value;`"),
+            TToken::MUT => (!var.is_const, "mutable variables use `=`, `mut name type? = value;`"),
+            _ => unreachable!(),
+        };
+        if !valid {
+            let keyword = format!("{:?}", keyword.ttype).to_lowercase();
+            return Err(Diagnostic::at_loc(format!("invalid `{}` declaration of ({})",keyword,var.ident), lexer.locate(var.span), var.span)
+                .with_code("E0100")
+                .with_help(help));
+        }
+        var.is_const |= keyword.ttype == TToken::CONST;
+        Ok(var)
     }
-}
 
+    pub fn get_node(lexer: &mut Lexer, keyword: &Token) -> ParseResult<Node> {
+        Ok(Node::VariableDelclear { var: Self::with_keyword(lexer, keyword)? })
+    }
+}
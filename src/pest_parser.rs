@@ -0,0 +1,454 @@
+use std::sync::OnceLock;
+
+use pest::{Parser, iterators::Pair};
+use pest::pratt_parser::{Assoc, Op as PrattOp, PrattParser};
+
+use crate::ast::{Arg, AssignOp, Enum, Expr, FieldValue, Func, Include, Literal, MatchArm, Node, Op, Pattern, Program, Stmt, Struct, Type, Variant, VariableDelclear};
+use crate::diagnostics::Diagnostic;
+use crate::lexer::{LexError, LexErrorKind, unescape};
+use crate::span::Span;
+
+#[derive(Parser)]
+#[grammar = "grammar.pest"]
+pub struct NemetParser;
+
+// parses a whole file into the same tree the hand written parser builds.
+// pest stops at the first error so the program never has diagnostics
+pub fn parse_program(file_path: impl ToString, source: &str) -> Result<Program, Diagnostic> {
+    parse_file(file_path, source, 0)
+}
+
+// parse_program for the file registered as file_id in a SourceMap
+pub fn parse_file(file_path: impl ToString, source: &str, file_id: usize) -> Result<Program, Diagnostic> {
+    let builder = Builder { file_path: file_path.to_string(), file_id };
+    let pairs = NemetParser::parse(Rule::program_file, source).map_err(|e| {
+        let mut diagnostic = Diagnostic::from(e.with_path(&builder.file_path));
+        diagnostic.span.file_id = file_id;
+        diagnostic
+    })?;
+    let mut body = Vec::<Node>::new();
+    for pair in pairs {
+        match pair.as_rule() {
+            Rule::include_defin => body.push(Node::Include { var: builder.include(pair)? }),
+            Rule::variable_declear | Rule::static_variable_declear => {
+                body.push(Node::VariableDelclear { var: builder.variable(pair)? });
+            },
+            Rule::function_defin => body.push(Node::Func { var: builder.func(pair)? }),
+            Rule::struct_defin => body.push(Node::Struct { var: builder.structure(pair)? }),
+            Rule::enum_defin => body.push(Node::Enum { var: builder.enumeration(pair)? }),
+            Rule::EOI => break,
+            _ => unreachable!("Program File Can Only Include Files And Define Static Variables, Structs, Enums And Functions"),
+        }
+    }
+    Ok(Program { shebang: String::new(), body, diagnostics: Vec::new() })
+}
+
+// parses a single expression, mostly useful for tests
+pub fn parse_expr(source: &str) -> Result<Expr, Diagnostic> {
+    let builder = Builder { file_path: "INTERNAL".to_string(), file_id: 0 };
+    let mut pairs = NemetParser::parse(Rule::expr, source).map_err(Diagnostic::from)?;
+    builder.expr(pairs.next().unwrap())
+}
+
+// binding power grows from the first to the last op, matching the
+// hand written parser: || < && < comparisons < | < ^ < & < shifts < + - < * / % < unary
+fn pratt_parser() -> &'static PrattParser<Rule> {
+    static PRATT: OnceLock<PrattParser<Rule>> = OnceLock::new();
+    PRATT.get_or_init(|| {
+        PrattParser::new()
+            .op(PrattOp::infix(Rule::oror, Assoc::Left))
+            .op(PrattOp::infix(Rule::andand, Assoc::Left))
+            .op(PrattOp::infix(Rule::eqeq, Assoc::Left) | PrattOp::infix(Rule::noteq, Assoc::Left) |
+                PrattOp::infix(Rule::less, Assoc::Left) | PrattOp::infix(Rule::lesseq, Assoc::Left) |
+                PrattOp::infix(Rule::more, Assoc::Left) | PrattOp::infix(Rule::moreeq, Assoc::Left))
+            .op(PrattOp::infix(Rule::or, Assoc::Left))
+            .op(PrattOp::infix(Rule::xor, Assoc::Left))
+            .op(PrattOp::infix(Rule::and, Assoc::Left))
+            .op(PrattOp::infix(Rule::lsh, Assoc::Left) | PrattOp::infix(Rule::rsh, Assoc::Left))
+            .op(PrattOp::infix(Rule::add, Assoc::Left) | PrattOp::infix(Rule::sub, Assoc::Left))
+            .op(PrattOp::infix(Rule::multi, Assoc::Left) | PrattOp::infix(Rule::divide, Assoc::Left) |
+                PrattOp::infix(Rule::r#mod, Assoc::Left))
+            .op(PrattOp::prefix(Rule::unary_op))
+    })
+}
+
+// turns pairs into ast nodes. pest pairs of rules with an optional tail
+// also cover the whitespace after them, so spans that end in an optional
+// part are taken from the last child instead
+struct Builder {
+    file_path: String,
+    file_id: usize,
+}
+
+impl Builder {
+    fn span(&self, pair: &Pair<Rule>) -> Span {
+        Span::new(self.file_id, pair.as_span().start(), pair.as_span().end())
+    }
+
+    fn error(&self, msg: impl ToString, pair: &Pair<Rule>) -> Diagnostic {
+        let (line, col) = pair.line_col();
+        Diagnostic::new(msg, self.file_path.clone(), line, col).with_span(self.span(pair))
+    }
+
+    // `name` or `module.name`
+    fn path(&self, pair: Pair<Rule>) -> String {
+        pair.into_inner().map(|ident| ident.as_str()).collect::<Vec<_>>().join(".")
+    }
+
+    fn include(&self, pair: Pair<Rule>) -> Result<Include, Diagnostic> {
+        let span = self.span(&pair);
+        let literal = pair.into_inner().next().unwrap();
+        Ok(Include { path: String::from_utf8(self.unescape(&literal, b'"')?).unwrap(), span })
+    }
+
+    fn func(&self, pair: Pair<Rule>) -> Result<Func, Diagnostic> {
+        let span = self.span(&pair);
+        let mut pairs = pair.into_inner();
+        let ident = pairs.next().unwrap();
+        let mut args = Vec::<Arg>::new();
+        let mut return_type = None;
+        let mut block = Vec::new();
+        for pair in pairs {
+            match pair.as_rule() {
+                Rule::function_arg => args.push(self.arg(pair)?),
+                Rule::r#type => return_type = Some(self.kind(pair)?),
+                Rule::block => {
+                    // no return type, points right before the body
+                    let start = pair.as_span().start();
+                    return_type.get_or_insert(Type::named("void", Span::new(self.file_id, start, start)));
+                    block = self.block(pair, false)?;
+                },
+                _ => unreachable!("Unexpected Rule In Function Definition"),
+            }
+        }
+        Ok(Func { ident: ident.as_str().to_string(), args, return_type: return_type.unwrap(), block, span })
+    }
+
+    fn structure(&self, pair: Pair<Rule>) -> Result<Struct, Diagnostic> {
+        let span = self.span(&pair);
+        let mut pairs = pair.into_inner();
+        let ident = pairs.next().unwrap();
+        Ok(Struct { ident: ident.as_str().to_string(), fields: pairs.map(|pair| self.arg(pair)).collect::<Result<_, _>>()?, span })
+    }
+
+    fn enumeration(&self, pair: Pair<Rule>) -> Result<Enum, Diagnostic> {
+        let span = self.span(&pair);
+        let mut pairs = pair.into_inner();
+        let ident = pairs.next().unwrap();
+        Ok(Enum { ident: ident.as_str().to_string(), variants: pairs.map(|pair| self.variant(pair)).collect::<Result<_, _>>()?, span })
+    }
+
+    fn variant(&self, pair: Pair<Rule>) -> Result<Variant, Diagnostic> {
+        let mut pairs = pair.into_inner();
+        let ident = pairs.next().unwrap();
+        let mut span = self.span(&ident);
+        let payload = match pairs.next() {
+            Some(pair) => {
+                span = span.to(self.span(&pair));
+                Some(self.kind(pair.into_inner().next().unwrap())?)
+            },
+            None => None,
+        };
+        Ok(Variant { ident: ident.as_str().to_string(), payload, span })
+    }
+
+    fn pattern(&self, pair: Pair<Rule>) -> Pattern {
+        let mut pairs = pair.into_inner();
+        let ident = pairs.next().unwrap();
+        if ident.as_rule() == Rule::wildcard {
+            return Pattern::Wildcard { span: self.span(&ident) };
+        }
+        let variant = pairs.next().unwrap();
+        let mut span = self.span(&ident).to(self.span(&variant));
+        let binding = pairs.next().map(|pair| {
+            span = span.to(self.span(&pair));
+            pair.into_inner().next().unwrap().as_str().to_string()
+        });
+        Pattern::Variant { ident: self.path(ident), variant: variant.as_str().to_string(), binding, span }
+    }
+
+    fn arg(&self, pair: Pair<Rule>) -> Result<Arg, Diagnostic> {
+        let span = self.span(&pair);
+        let mut pairs = pair.into_inner();
+        let ident = pairs.next().unwrap().as_str().to_string();
+        Ok(Arg { ident, kind: self.kind(pairs.next().unwrap())?, span })
+    }
+
+    fn kind(&self, pair: Pair<Rule>) -> Result<Type, Diagnostic> {
+        let span = self.span(&pair);
+        let inner = pair.into_inner().next().unwrap();
+        if inner.as_rule() == Rule::path {
+            return Ok(Type::named(self.path(inner), span));
+        }
+        let mut pairs = inner.into_inner();
+        let element = self.kind(pairs.next().unwrap())?;
+        let len = match pairs.next() {
+            Some(len) => match Literal::parse_number(len.as_str()) {
+                Some(Literal::Int(value)) if u32::try_from(value).is_ok() => Some(value as u32),
+                _ => return Err(self.error(format!("invalid array length `{}`",len.as_str()), &len)
+                    .with_code("E0103")
+                    .with_note("the length of an array is an integer literal")),
+            },
+            None => None,
+        };
+        Ok(Type::array(element, len, span))
+    }
+
+    // `@`, `static`, `const` and `mut` declarations
+    fn variable(&self, pair: Pair<Rule>) -> Result<VariableDelclear, Diagnostic> {
+        let rule = pair.as_rule();
+        let span = self.span(&pair);
+        let mut pairs = pair.into_inner();
+        let ident = pairs.next().unwrap();
+        let start = self.span(&ident);
+        let mut kind = Type::named("undifiend", Span::new(self.file_id, start.end, start.end));
+        let mut is_static = rule == Rule::static_variable_declear;
+        let mut is_const = is_static || rule == Rule::const_variable_declear;
+        let mut init_value = None;
+        for pair in pairs {
+            match pair.as_rule() {
+                Rule::r#type => kind = self.kind(pair)?,
+                Rule::static_value | Rule::const_value | Rule::mut_value => {
+                    is_static |= pair.as_rule() == Rule::static_value;
+                    is_const |= pair.as_rule() != Rule::mut_value;
+                    init_value = Some(self.expr(pair.into_inner().next().unwrap())?);
+                },
+                _ => unreachable!("Unexpected Rule In Variable Declaration"),
+            }
+        }
+        Ok(VariableDelclear { is_const, is_static, ident: ident.as_str().to_string(), kind, init_value, span })
+    }
+
+    // statements of a `block` pair, `in_loop` tells if break and continue are allowed
+    fn block(&self, block: Pair<Rule>, in_loop: bool) -> Result<Vec<Stmt>, Diagnostic> {
+        block.into_inner().map(|pair| self.stmt(pair, in_loop)).collect()
+    }
+
+    fn stmt(&self, pair: Pair<Rule>, in_loop: bool) -> Result<Stmt, Diagnostic> {
+        let span = self.span(&pair);
+        let rule = pair.as_rule();
+        let stmt = match rule {
+            Rule::variable_declear | Rule::static_variable_declear |
+            Rule::const_variable_declear | Rule::mut_variable_declear => {
+                Stmt::VariableDelclear { var: self.variable(pair)? }
+            },
+            Rule::assignment_expression => {
+                let mut pairs = pair.into_inner();
+                let mut place = pairs.next().unwrap().into_inner();
+                let ident = place.next().unwrap();
+                let base = Expr::Path { ident: ident.as_str().to_string(), span: self.span(&ident) };
+                let lhs = place.try_fold(base, |base, postfix| self.postfix(base, postfix))?;
+                let op = AssignOp::get(pairs.next().unwrap().as_str().to_string());
+                let rhs = self.expr(pairs.next().unwrap())?;
+                Stmt::Assign { lhs, op, rhs, span }
+            },
+            Rule::expr_stmt => Stmt::Expr { expr: self.expr(pair.into_inner().next().unwrap())?, span },
+            Rule::return_stmt => {
+                let value = pair.into_inner().next().map(|pair| self.expr(pair)).transpose()?;
+                Stmt::Return { value, span }
+            },
+            Rule::block => Stmt::Block { body: self.block(pair, in_loop)?, span },
+            Rule::debug_print => Stmt::DebugPrint { expr: self.expr(pair.into_inner().next().unwrap())?, span },
+            Rule::if_stmt => {
+                let mut pairs = pair.into_inner();
+                let cond = self.expr(pairs.next().unwrap())?;
+                let then = pairs.next().unwrap();
+                let mut end = self.span(&then);
+                let then_block = self.block(then, in_loop)?;
+                let else_block = match pairs.next() {
+                    Some(pair) => {
+                        let branch = self.stmt(pair, in_loop)?;
+                        end = branch.span();
+                        Some(Box::new(branch))
+                    },
+                    None => None,
+                };
+                Stmt::If { cond, then_block, else_block, span: Span::new(self.file_id, span.start, end.end) }
+            },
+            Rule::while_stmt => {
+                let mut pairs = pair.into_inner();
+                let cond = self.expr(pairs.next().unwrap())?;
+                Stmt::While { cond, body: self.block(pairs.next().unwrap(), true)?, span }
+            },
+            Rule::loop_stmt => Stmt::Loop { body: self.block(pair.into_inner().next().unwrap(), true)?, span },
+            Rule::for_range_stmt => {
+                let mut pairs = pair.into_inner();
+                let ident = pairs.next().unwrap().as_str().to_string();
+                let from = self.expr(pairs.next().unwrap())?;
+                let to = self.expr(pairs.next().unwrap())?;
+                Stmt::ForRange { ident, from, to, body: self.block(pairs.next().unwrap(), true)?, span }
+            },
+            Rule::for_in_stmt => {
+                let mut pairs = pair.into_inner();
+                let ident = pairs.next().unwrap().as_str().to_string();
+                let iter = self.expr(pairs.next().unwrap())?;
+                Stmt::ForIn { ident, iter, body: self.block(pairs.next().unwrap(), true)?, span }
+            },
+            Rule::match_stmt => {
+                let mut pairs = pair.into_inner();
+                let value = self.expr(pairs.next().unwrap())?;
+                let arms = pairs.map(|arm| {
+                    let mut pairs = arm.into_inner();
+                    let pattern = self.pattern(pairs.next().unwrap());
+                    let body = pairs.next().unwrap();
+                    let span = pattern.span().to(self.span(&body));
+                    Ok(MatchArm { pattern, body: self.block(body, in_loop)?, span })
+                }).collect::<Result<_, Diagnostic>>()?;
+                Stmt::Match { value, arms, span }
+            },
+            Rule::break_stmt | Rule::continue_stmt => {
+                let keyword = if rule == Rule::break_stmt { "break" } else { "continue" };
+                if !in_loop {
+                    return Err(self.error(format!("`{}` outside of a loop",keyword), &pair)
+                        .with_code("E0105")
+                        .with_help("`break` and `continue` can only be used inside `while`, `loop` and `for`"));
+                }
+                if rule == Rule::break_stmt { Stmt::Break { span } } else { Stmt::Continue { span } }
+            },
+            _ => unreachable!("Unexpected Rule In Block"),
+        };
+        Ok(stmt)
+    }
+
+    // builds the tree of an `expr` or `cond_expr` pair
+    fn expr(&self, pair: Pair<Rule>) -> Result<Expr, Diagnostic> {
+        pratt_parser()
+            .map_primary(|primary| self.primary(primary))
+            .map_prefix(|op, operand| {
+                let operand = operand?;
+                Ok(Expr::Unary {
+                    span: self.span(&op).to(operand.span()),
+                    op: Op::get(op.as_str().to_string()),
+                    operand: Box::new(operand),
+                })
+            })
+            .map_infix(|left, op, right| {
+                let (left, right) = (left?, right?);
+                Ok(Expr::Binary {
+                    span: left.span().to(right.span()),
+                    left: Box::new(left),
+                    op: Op::get(op.as_str().to_string()),
+                    right: Box::new(right),
+                })
+            })
+            .parse(pair.into_inner())
+    }
+
+    // a `primary` or `cond_primary` pair, the value followed by its field accesses and indices
+    fn primary(&self, pair: Pair<Rule>) -> Result<Expr, Diagnostic> {
+        let mut pairs = pair.into_inner();
+        let base = self.value(pairs.next().unwrap())?;
+        pairs.try_fold(base, |base, postfix| self.postfix(base, postfix))
+    }
+
+    // a `field_access` or `index` pair applied to base
+    fn postfix(&self, base: Expr, pair: Pair<Rule>) -> Result<Expr, Diagnostic> {
+        let span = base.span().to(self.span(&pair));
+        let rule = pair.as_rule();
+        let inner = pair.into_inner().next().unwrap();
+        if rule == Rule::index {
+            return Ok(Expr::Index { base: Box::new(base), index: Box::new(self.expr(inner)?), span });
+        }
+        Ok(Expr::Field { base: Box::new(base), ident: inner.as_str().to_string(), span })
+    }
+
+    fn value(&self, inner: Pair<Rule>) -> Result<Expr, Diagnostic> {
+        let span = self.span(&inner);
+        let lit = inner.as_str();
+        let value = match inner.as_rule() {
+            Rule::expr => return self.expr(inner),
+            Rule::struct_literal => {
+                let mut pairs = inner.into_inner();
+                let ident = self.path(pairs.next().unwrap());
+                let fields = pairs.map(|pair| {
+                    let mut pairs = pair.into_inner();
+                    let name = pairs.next().unwrap();
+                    let value = self.expr(pairs.next().unwrap())?;
+                    let span = Span::new(self.file_id, self.span(&name).start, value.span().end);
+                    Ok(FieldValue { ident: name.as_str().to_string(), value, span })
+                }).collect::<Result<_, Diagnostic>>()?;
+                return Ok(Expr::StructLiteral { ident, fields, span });
+            },
+            Rule::variant => {
+                let mut pairs = inner.into_inner();
+                let ident = pairs.next().unwrap();
+                let variant = pairs.next().unwrap();
+                let mut span = self.span(&ident).to(self.span(&variant));
+                let payload = match pairs.next() {
+                    Some(pair) => {
+                        span = span.to(self.span(&pair));
+                        Some(Box::new(self.expr(pair.into_inner().next().unwrap())?))
+                    },
+                    None => None,
+                };
+                return Ok(Expr::Variant { ident: self.path(ident), variant: variant.as_str().to_string(), payload, span });
+            },
+            Rule::call => {
+                let mut pairs = inner.into_inner();
+                let ident = self.path(pairs.next().unwrap());
+                let args = pairs.map(|pair| self.expr(pair)).collect::<Result<_, Diagnostic>>()?;
+                return Ok(Expr::Call { ident, args, span });
+            },
+            Rule::array_literal => {
+                let elements = inner.into_inner().map(|pair| self.expr(pair)).collect::<Result<_, Diagnostic>>()?;
+                return Ok(Expr::Array { elements, span });
+            },
+            Rule::match_expr => {
+                let mut pairs = inner.into_inner();
+                let value = self.expr(pairs.next().unwrap())?;
+                let arms = pairs.map(|arm| {
+                    let mut pairs = arm.into_inner();
+                    let pattern = self.pattern(pairs.next().unwrap());
+                    let body = self.expr(pairs.next().unwrap())?;
+                    Ok(MatchArm { span: pattern.span().to(body.span()), pattern, body })
+                }).collect::<Result<_, Diagnostic>>()?;
+                return Ok(Expr::Match { value: Box::new(value), arms, span });
+            },
+            Rule::ident => return Ok(Expr::Path { ident: lit.to_string(), span }),
+            Rule::integer_literal | Rule::float_literal | Rule::binary_literal => {
+                Literal::parse_number(lit).ok_or_else(|| {
+                    let diagnostic = self.error(format!("invalid number literal ({})",lit), &inner).with_code("E0103");
+                    if Literal::overflows(lit) {
+                        diagnostic.with_note(format!("the largest integer literal is {}",i64::MAX))
+                    }else {
+                        diagnostic
+                    }
+                })?
+            },
+            Rule::logical_literal => Literal::Bool(lit == "true"),
+            Rule::string_literal => {
+                Literal::Str(String::from_utf8(self.unescape(&inner, b'"')?).unwrap())
+            },
+            Rule::char_literal => {
+                let bytes = self.unescape(&inner, b'\'')?;
+                Literal::Char(String::from_utf8(bytes).unwrap().chars().next().unwrap())
+            },
+            _ => unreachable!("Unexpected Rule In Expression"),
+        };
+        Ok(Expr::Literal { value, span })
+    }
+
+    // contents of a string or char literal with the escapes replaced
+    fn unescape(&self, pair: &Pair<Rule>, quote: u8) -> Result<Vec<u8>, Diagnostic> {
+        let inner = pair.clone().into_inner().next();
+        let mut bytes = inner.as_ref().map_or("", |inner| inner.as_str()).bytes();
+        let mut literal = Vec::new();
+        while let Some(char) = bytes.next() {
+            if char != b'\\' {
+                literal.push(char);
+                continue;
+            }
+            let escape = bytes.next().unwrap();
+            let Some(char) = unescape(escape, quote) else {
+                let (line, col) = pair.line_col();
+                return Err(Diagnostic::from(LexError {
+                    kind: LexErrorKind::UnknownEscape(escape),
+                    loc: (self.file_path.clone(), line, col),
+                    span: self.span(pair),
+                }));
+            };
+            literal.push(char);
+        }
+        Ok(literal)
+    }
+}
@@ -0,0 +1,312 @@
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+
+use crate::ast::{Enum, Expr, Func, Node, Program, Stmt, Struct, Type};
+use crate::diagnostics::{Diagnostic, Severity};
+use crate::interpreter::{Interpreter, with_stack};
+use crate::lexer::{Lexer, TToken, Token};
+use crate::parser::ParseResult;
+use crate::parser::expr::get_expr;
+use crate::parser::stmt::check_loop_control;
+use crate::semantic::{Analysis, analyze};
+use crate::semantic::types::Ty;
+use crate::span::{SourceMap, Span};
+use crate::value::Value;
+
+pub const HELP: &str = "\
+enter declarations, statements or expressions, the value of an expression is printed.
+input continues on the next line while a `{` is not closed
+
+    :tokens <input>   print the tokens of the input
+    :ast <input>      print the syntax tree of the input
+    :type <expr>      print the type of an expression
+    :help             print this help
+    :quit             leave, like end of input
+";
+
+// one piece of input, functions and variables are kept for the next inputs
+#[derive(Debug, PartialEq)]
+pub enum Entry {
+    Item(Node),
+    Stmt(Stmt),
+}
+
+// everything declared so far. every input is its own file in sources so
+// diagnostics point into the input they came from
+#[derive(Debug, Default)]
+pub struct Session {
+    pub program: Program,
+    pub sources: SourceMap,
+    // values of the top level variables, by name as symbol ids change with every input
+    pub values: HashMap<String, Value>,
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lexer(&mut self, input: &str) -> Lexer {
+        let name = format!("<repl:{}>", self.sources.len() + 1);
+        let file_id = self.sources.add(&name, input);
+        Lexer::from_source(name, input).with_file_id(file_id)
+    }
+
+    // declarations become items, anything else a statement. a last
+    // statement without `;` gets one so `1 + 2` is an expression statement
+    pub fn parse(&mut self, input: &str) -> Result<Vec<Entry>, Vec<Diagnostic>> {
+        let trimmed = input.trim_end();
+        let input = if trimmed.is_empty() || trimmed.ends_with([';', '}']) { input.to_string() } else { format!("{};", trimmed) };
+        let mut lexer = self.lexer(&input);
+        let mut entries = Vec::new();
+        loop {
+            match parse_entry(&mut lexer) {
+                Ok(Some(entry)) => entries.push(entry),
+                Ok(None) => break,
+                Err(diagnostic) => {
+                    lexer.report(diagnostic);
+                    break;
+                },
+            }
+        }
+        let diagnostics = lexer.take_diagnostics();
+        if diagnostics.is_empty() { Ok(entries) } else { Err(diagnostics) }
+    }
+
+    // parses, checks and runs one complete input, printing the value of
+    // expression statements to out. on errors nothing of the input is kept
+    pub fn eval(&mut self, input: &str, out: &mut (impl Write + Send)) -> Vec<Diagnostic> {
+        let entries = match self.parse(input) {
+            Ok(entries) => entries,
+            Err(diagnostics) => return diagnostics,
+        };
+        let file_id = self.sources.len() - 1;
+        // a new definition replaces the old one with the same name
+        let mut replaced = Vec::new();
+        for entry in &entries {
+            let Entry::Item(node) = entry else { continue };
+            if let Some(pos) = self.program.body.iter().position(|old| item_name(old) == item_name(node)) {
+                replaced.push((pos, self.program.body.remove(pos)));
+            }
+        }
+        // statements are checked in a function of their own, removed again after running
+        let items = self.program.body.len();
+        for (i, entry) in entries.into_iter().enumerate() {
+            let node = match entry {
+                Entry::Item(node) => node,
+                Entry::Stmt(stmt) => wrapper(i, stmt),
+            };
+            self.program.body.push(node);
+        }
+
+        let mut diagnostics = Vec::new();
+        let analysis = analyze(&self.program, &self.sources, &mut diagnostics);
+        // warnings of earlier inputs were already shown
+        diagnostics.retain(|d| d.severity == Severity::Error || d.span.file_id == file_id);
+        if diagnostics.iter().any(|d| d.severity == Severity::Error) {
+            self.program.body.truncate(items);
+            for (pos, node) in replaced.into_iter().rev() {
+                self.program.body.insert(pos, node);
+            }
+            return diagnostics;
+        }
+        for (_, node) in &replaced {
+            self.values.remove(item_name(node));
+        }
+        if let Err(diagnostic) = with_stack(|| self.run(&analysis, items, out)) {
+            diagnostics.push(diagnostic);
+        }
+        self.program.body.retain(|node| !matches!(node, Node::Func { var } if is_wrapper(var)));
+        diagnostics
+    }
+
+    // runs the nodes of the input, which start at index first of the program
+    fn run(&mut self, analysis: &Analysis, first: usize, out: &mut impl Write) -> Result<(), Diagnostic> {
+        let mut interpreter = Interpreter::empty(&self.program, analysis, &self.sources, &mut *out);
+        for (name, value) in &self.values {
+            interpreter.set_global(name, value.clone());
+        }
+        let mut result = Ok(());
+        for node in &self.program.body[first..] {
+            result = match node {
+                Node::VariableDelclear { var } => interpreter.variable(var),
+                Node::Func { var } if !is_wrapper(var) => Ok(()),
+                Node::Include { .. } | Node::Struct { .. } | Node::Enum { .. } => Ok(()),
+                Node::Func { var } => match &var.block[0] {
+                    Stmt::Expr { expr, span } => interpreter.expr(expr).and_then(|value| interpreter.print(&value, *span)),
+                    stmt => interpreter.exec(std::slice::from_ref(stmt)),
+                },
+            };
+            if result.is_err() {
+                break;
+            }
+        }
+        self.values = interpreter.globals().map(|(name, value)| (name.to_string(), value.clone())).collect();
+        result
+    }
+
+    // type of an expression using everything declared so far
+    pub fn type_of(&mut self, input: &str) -> Result<Ty, Vec<Diagnostic>> {
+        let mut lexer = self.lexer(input);
+        let expr = parse_expr(&mut lexer).map_err(|diagnostic| vec![diagnostic])?;
+        let span = expr.span();
+        self.program.body.push(wrapper(0, Stmt::Expr { expr, span }));
+        let mut diagnostics = Vec::new();
+        let analysis = analyze(&self.program, &self.sources, &mut diagnostics);
+        self.program.body.pop();
+        diagnostics.retain(|d| d.severity == Severity::Error);
+        if !diagnostics.is_empty() {
+            return Err(diagnostics);
+        }
+        Ok(analysis.types.exprs.get(&span).cloned().unwrap_or(Ty::Error))
+    }
+
+    pub fn tokens(&mut self, input: &str) -> Result<Vec<Token>, Diagnostic> {
+        let mut lexer = self.lexer(input);
+        let mut tokens = Vec::new();
+        loop {
+            let token = lexer.next_token()?;
+            if token.ttype == TToken::EOF {
+                return Ok(tokens);
+            }
+            tokens.push(token);
+        }
+    }
+}
+
+fn parse_entry(lexer: &mut Lexer) -> ParseResult<Option<Entry>> {
+    let token = lexer.peek_token()?;
+    match token.ttype {
+        TToken::EOF => Ok(None),
+        TToken::Fun => {
+            lexer.next_token()?;
+            Ok(Some(Entry::Item(Func::get_node(lexer, token.span)?)))
+        },
+        TToken::STRUCT => {
+            lexer.next_token()?;
+            Ok(Some(Entry::Item(Struct::get_node(lexer, token.span)?)))
+        },
+        TToken::ENUM => {
+            lexer.next_token()?;
+            Ok(Some(Entry::Item(Enum::get_node(lexer, token.span)?)))
+        },
+        _ => match Stmt::new(lexer)? {
+            Stmt::VariableDelclear { var } => Ok(Some(Entry::Item(Node::VariableDelclear { var }))),
+            stmt => {
+                check_loop_control(lexer, std::slice::from_ref(&stmt), false);
+                Ok(Some(Entry::Stmt(stmt)))
+            },
+        },
+    }
+}
+
+fn parse_expr(lexer: &mut Lexer) -> ParseResult<Expr> {
+    let expr = get_expr(lexer)?;
+    let token = lexer.next_token()?;
+    if token.ttype != TToken::EOF {
+        return Err(Diagnostic::at_token(format!("unexpected token ({:?}) after the expression",token.ttype), &token).with_code("E0100"));
+    }
+    Ok(expr)
+}
+
+fn item_name(node: &Node) -> &str {
+    match node {
+        Node::Include { var } => &var.path,
+        Node::Func { var } => &var.ident,
+        Node::VariableDelclear { var } => &var.ident,
+        Node::Struct { var } => &var.ident,
+        Node::Enum { var } => &var.ident,
+    }
+}
+
+// `#` can not be part of an identifier, so user code never sees these
+fn wrapper(index: usize, stmt: Stmt) -> Node {
+    let start = stmt.span();
+    let span = Span::new(start.file_id, start.start, start.start);
+    let return_type = Type::named("void", span);
+    Node::Func { var: Func { ident: format!("repl#{}", index), args: Vec::new(), return_type, block: vec![stmt], span } }
+}
+
+fn is_wrapper(func: &Func) -> bool {
+    func.ident.starts_with("repl#")
+}
+
+// how many `{` are still open, input is complete at 0 or less
+fn open_braces(input: &str) -> i32 {
+    let mut lexer = Lexer::from_str(input);
+    let mut depth = 0;
+    while let Ok(token) = lexer.next_token() {
+        match token.ttype {
+            TToken::OCURLY => depth += 1,
+            TToken::CCURLY => depth -= 1,
+            TToken::EOF => break,
+            _ => (),
+        }
+    }
+    depth
+}
+
+// reads inputs until the end of input or `:quit`. prompts go to err so out
+// only has what the inputs printed
+pub fn repl(input: impl BufRead, out: &mut (impl Write + Send), err: &mut impl Write, color: bool) -> i32 {
+    let mut session = Session::new();
+    let mut buffer = String::new();
+    let mut lines = input.lines();
+    loop {
+        let _ = write!(err, "{}", if buffer.is_empty() { "> " } else { "... " });
+        let _ = err.flush();
+        let Some(Ok(line)) = lines.next() else { break };
+        buffer.push_str(&line);
+        buffer.push('\n');
+        if open_braces(&buffer) > 0 {
+            continue;
+        }
+        let input = std::mem::take(&mut buffer);
+        let trimmed = input.trim();
+        let (command, rest) = trimmed.split_once(char::is_whitespace).unwrap_or((trimmed, ""));
+        let diagnostics = match command {
+            "" => Vec::new(),
+            ":quit" | ":q" => break,
+            ":help" => {
+                let _ = write!(out, "{}", HELP);
+                Vec::new()
+            },
+            ":tokens" => match session.tokens(rest) {
+                Ok(tokens) => {
+                    for token in tokens {
+                        let _ = writeln!(out, "{}", token);
+                    }
+                    Vec::new()
+                },
+                Err(diagnostic) => vec![diagnostic],
+            },
+            ":ast" => match session.parse(rest) {
+                Ok(entries) => {
+                    for entry in entries {
+                        let _ = writeln!(out, "{:#?}", entry);
+                    }
+                    Vec::new()
+                },
+                Err(diagnostics) => diagnostics,
+            },
+            ":type" => match session.type_of(rest) {
+                Ok(ty) => {
+                    let _ = writeln!(out, "{}", ty);
+                    Vec::new()
+                },
+                Err(diagnostics) => diagnostics,
+            },
+            _ if command.starts_with(':') => {
+                let _ = writeln!(err, "error: unknown command `{}`, `:help` lists them", command);
+                Vec::new()
+            },
+            _ => session.eval(&input, out),
+        };
+        for diagnostic in diagnostics {
+            let source = session.sources.get(diagnostic.span.file_id).map_or("", |file| file.source.as_str());
+            let _ = write!(err, "{}", diagnostic.render(source, color));
+        }
+    }
+    let _ = writeln!(err);
+    0
+}
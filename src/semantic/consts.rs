@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+
+use crate::ast::{Expr, Func, Node, Op, Program, Stmt, VariableDelclear};
+use crate::diagnostics::{Diagnostic, Severity};
+use crate::semantic::resolve::{Resolution, SymbolId};
+use crate::semantic::types::TypeInfo;
+use crate::span::{SourceMap, Span};
+use crate::value::Value;
+
+// `:` constants and `::` statics can not be assigned to. statics need a value
+// known at compile time, values holds every constant that has one so uses of
+// it can be folded
+#[derive(Debug, Default)]
+pub struct Consts {
+    pub values: HashMap<SymbolId, Value>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+impl Consts {
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics.iter().any(|d| d.severity == Severity::Error)
+    }
+}
+
+pub fn check(program: &Program, resolution: &Resolution, types: &TypeInfo, sources: &SourceMap) -> Consts {
+    let mut folder = Folder {
+        resolution,
+        types,
+        sources,
+        decls: Vec::new(),
+        inits: HashMap::new(),
+        evaluating: Vec::new(),
+        failed: HashMap::new(),
+        consts: Consts::default(),
+    };
+    for node in &program.body {
+        match node {
+            Node::Func { var } => folder.func(var),
+            Node::VariableDelclear { var } => folder.variable(var),
+            Node::Include { .. } | Node::Struct { .. } | Node::Enum { .. } => (),
+        }
+    }
+    for (id, is_static) in folder.decls.clone() {
+        let symbol = resolution.symbol(id);
+        if let Err(Stop::NotConst(span)) = folder.evaluate(id, symbol.span) {
+            if is_static {
+                let diagnostic = Diagnostic::at_loc(format!("the value of static `{}` is not known at compile time",symbol.name), sources.locate(span), span)
+                    .with_code("E0401")
+                    .with_help("statics can only use literals, operators and other constants");
+                folder.consts.diagnostics.push(diagnostic);
+            }
+        }
+    }
+    folder.consts
+}
+
+// why a value could not be computed
+#[derive(Debug, Clone, Copy)]
+enum Stop {
+    // the expression at span is only known at run time
+    NotConst(Span),
+    // evaluation failed and was already reported
+    Failed,
+}
+
+struct Folder<'a> {
+    resolution: &'a Resolution,
+    types: &'a TypeInfo,
+    sources: &'a SourceMap,
+    // every constant in source order, with whether it is static
+    decls: Vec<(SymbolId, bool)>,
+    inits: HashMap<SymbolId, &'a Expr>,
+    // constants being evaluated, to find cycles
+    evaluating: Vec<SymbolId>,
+    failed: HashMap<SymbolId, Stop>,
+    consts: Consts,
+}
+
+impl<'a> Folder<'a> {
+    fn error(&self, code: &'static str, msg: impl ToString, span: Span) -> Diagnostic {
+        Diagnostic::at_loc(msg, self.sources.locate(span), span).with_code(code)
+    }
+
+    fn func(&mut self, func: &'a Func) {
+        self.block(&func.block);
+    }
+
+    fn variable(&mut self, var: &'a VariableDelclear) {
+        let (Some(id), Some(value)) = (self.resolution.defs.get(&var.span), &var.init_value) else {
+            return;
+        };
+        if var.is_const || var.is_static {
+            self.decls.push((*id, var.is_static));
+            self.inits.insert(*id, value);
+        }
+    }
+
+    fn block(&mut self, body: &'a [Stmt]) {
+        for stmt in body {
+            self.stmt(stmt);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &'a Stmt) {
+        match stmt {
+            Stmt::VariableDelclear { var } => self.variable(var),
+            Stmt::Assign { lhs, .. } => {
+                // `a.b[i] = ..` assigns to a, `module.a = ..` to module.a
+                let mut root = lhs;
+                loop {
+                    root = match root {
+                        Expr::Field { span, .. } if self.resolution.uses.contains_key(span) => break,
+                        Expr::Field { base, .. } | Expr::Index { base, .. } => base,
+                        _ => break,
+                    };
+                }
+                let span = root.span();
+                let Some(symbol) = self.resolution.resolved(span) else {
+                    return;
+                };
+                let ident = &symbol.name;
+                if !symbol.is_const && !symbol.is_static {
+                    return;
+                }
+                let what = if symbol.is_static { "static" } else { "constant" };
+                let (line, col) = self.sources.line_col(symbol.span).unwrap_or((1, 1));
+                // a definition in another file is located by its path
+                let file = match self.sources.get(symbol.span.file_id) {
+                    Some(file) if symbol.span.file_id != span.file_id => format!("{}:",file.path),
+                    _ => String::new(),
+                };
+                let diagnostic = self.error("E0400", format!("cannot assign to {} `{}`",what,ident), span)
+                    .with_note(format!("`{}` is defined at {}{}:{}",ident,file,line,col))
+                    .with_help(format!("declare `{}` with `=` to make it mutable",ident));
+                self.consts.diagnostics.push(diagnostic);
+            },
+            Stmt::Block { body, .. } | Stmt::Loop { body, .. } | Stmt::While { body, .. } |
+            Stmt::ForRange { body, .. } | Stmt::ForIn { body, .. } => self.block(body),
+            Stmt::If { then_block, else_block, .. } => {
+                self.block(then_block);
+                if let Some(branch) = else_block {
+                    self.stmt(branch);
+                }
+            },
+            Stmt::Match { arms, .. } => {
+                for arm in arms {
+                    self.block(&arm.body);
+                }
+            },
+            _ => (),
+        }
+    }
+
+    // value of the symbol used at span
+    fn symbol(&mut self, id: SymbolId, span: Span) -> Result<Value, Stop> {
+        // a constant computed from run time values is not known where it is used either
+        self.evaluate(id, span).map_err(|stop| match stop {
+            Stop::NotConst(_) => Stop::NotConst(span),
+            Stop::Failed => Stop::Failed,
+        })
+    }
+
+    // value of a constant computed once, span is where it is needed
+    fn evaluate(&mut self, id: SymbolId, span: Span) -> Result<Value, Stop> {
+        if let Some(value) = self.consts.values.get(&id) {
+            return Ok(value.clone());
+        }
+        if let Some(stop) = self.failed.get(&id) {
+            return Err(*stop);
+        }
+        let Some(init) = self.inits.get(&id).copied() else {
+            return Err(Stop::NotConst(span));
+        };
+        if self.evaluating.contains(&id) {
+            let name = &self.resolution.symbol(id).name;
+            let diagnostic = self.error("E0402", format!("the value of `{}` depends on itself",name), span);
+            self.consts.diagnostics.push(diagnostic);
+            return Err(Stop::Failed);
+        }
+        self.evaluating.push(id);
+        let result = self.expr(init);
+        self.evaluating.pop();
+        match &result {
+            Ok(value) => { self.consts.values.insert(id, value.clone()); },
+            Err(stop) => { self.failed.insert(id, *stop); },
+        }
+        result
+    }
+
+    fn expr(&mut self, expr: &Expr) -> Result<Value, Stop> {
+        let result = match expr {
+            Expr::Literal { value, .. } => return Ok(Value::from_literal(value)),
+            Expr::Path { span, .. } => {
+                let id = *self.resolution.uses.get(span).ok_or(Stop::Failed)?;
+                return self.symbol(id, *span);
+            },
+            // `module.name`
+            Expr::Field { span, .. } if self.resolution.uses.contains_key(span) => {
+                return self.symbol(self.resolution.uses[span], *span);
+            },
+            Expr::Unary { op, operand, .. } => {
+                let operand = self.expr(operand)?;
+                Value::unary(*op, &operand)
+            },
+            Expr::Binary { left, right, op, .. } => {
+                let left = self.expr(left)?;
+                // the right side of `&&` and `||` is not evaluated when the left decides
+                match (op, &left) {
+                    (Op::AndAnd, Value::Bool(false)) | (Op::OrOr, Value::Bool(true)) => return Ok(left),
+                    _ => (),
+                }
+                let right = self.expr(right)?;
+                Value::binary(*op, &left, &right)
+            },
+            Expr::StructLiteral { ident, fields, span } => {
+                let fields = fields.iter()
+                    .map(|field| Ok((field.ident.clone(), self.expr(&field.value)?)))
+                    .collect::<Result<_, Stop>>()?;
+                return Ok(self.types.struct_value(self.resolution.name(*span, ident), fields));
+            },
+            Expr::Field { base, ident, .. } => {
+                let base = self.expr(base)?;
+                base.field(ident).cloned().ok_or_else(|| format!("{} has no field `{}`",base,ident))
+            },
+            Expr::Variant { ident, variant, payload, span } => {
+                let payload = payload.as_ref().map(|payload| self.expr(payload)).transpose()?;
+                let name = self.resolution.name(*span, ident).to_string();
+                return Ok(Value::Enum { name, variant: variant.clone(), payload: payload.map(Box::new) });
+            },
+            // the bindings of the arms are not constants and functions only run at run time
+            Expr::Match { span, .. } | Expr::Call { span, .. } => return Err(Stop::NotConst(*span)),
+            Expr::Array { elements, .. } => {
+                return Ok(Value::Array(elements.iter().map(|element| self.expr(element)).collect::<Result<_, Stop>>()?));
+            },
+            Expr::Index { base, index, .. } => {
+                let base = self.expr(base)?;
+                match self.expr(index)? {
+                    Value::Int(index) => base.index(index),
+                    index => Err(format!("cannot index with {}",index)),
+                }
+            },
+        };
+        result.map_err(|msg| {
+            let diagnostic = self.error("E0402", format!("constant evaluation failed: {}",msg), expr.span());
+            self.consts.diagnostics.push(diagnostic);
+            Stop::Failed
+        })
+    }
+}
@@ -0,0 +1,34 @@
+pub mod resolve;
+pub mod types;
+pub mod consts;
+
+use crate::ast::Program;
+use crate::diagnostics::{Diagnostic, Severity};
+use crate::span::SourceMap;
+
+// results of every analysis pass, the diagnostics are moved out into analyze's caller
+#[derive(Debug, Default)]
+pub struct Analysis {
+    pub resolution: resolve::Resolution,
+    pub types: types::TypeInfo,
+    pub consts: consts::Consts,
+}
+
+// runs the passes in order, types are only checked once every name resolved
+// and constants once the types are right. passes that did not run are empty
+pub fn analyze(program: &Program, sources: &SourceMap, diagnostics: &mut Vec<Diagnostic>) -> Analysis {
+    let has_errors = |diagnostics: &Vec<Diagnostic>| diagnostics.iter().any(|d| d.severity == Severity::Error);
+    let mut analysis = Analysis { resolution: resolve::resolve(program, sources), ..Default::default() };
+    diagnostics.append(&mut analysis.resolution.diagnostics);
+    if has_errors(diagnostics) {
+        return analysis;
+    }
+    analysis.types = types::check(program, &analysis.resolution, sources);
+    diagnostics.append(&mut analysis.types.diagnostics);
+    if has_errors(diagnostics) {
+        return analysis;
+    }
+    analysis.consts = consts::check(program, &analysis.resolution, &analysis.types, sources);
+    diagnostics.append(&mut analysis.consts.diagnostics);
+    analysis
+}
@@ -0,0 +1,413 @@
+use std::collections::HashMap;
+
+use crate::ast::{Expr, Func, Include, MatchArm, Node, Pattern, Program, Stmt, Type, VariableDelclear};
+use crate::diagnostics::{Diagnostic, Severity};
+use crate::semantic::types::Ty;
+use crate::span::{SourceMap, Span};
+
+pub type SymbolId = usize;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SymbolKind {
+    Func,
+    // top level variables, static or not
+    Global,
+    Arg,
+    Local,
+    // the variable of a for loop
+    LoopVar,
+    Struct,
+    Enum,
+    // the payload bound by a match arm
+    Binding,
+    // a file loaded by `include`, named after the file
+    Module,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Symbol {
+    // `module.name` for the top level names of an included file
+    pub name: String,
+    pub kind: SymbolKind,
+    pub is_const: bool,
+    pub is_static: bool,
+    // span of the declaring node
+    pub span: Span,
+}
+
+// result of name resolution. uses maps the span of every Expr::Path,
+// Expr::StructLiteral, Expr::Variant, Pattern::Variant, Type naming a struct
+// or enum and Expr::Field that is a `module.name` to the symbol it refers
+// to, defs maps the span of every declaring node (Include, Func, Struct,
+// Enum, Arg, VariableDelclear, ForRange, ForIn and a Pattern::Variant with
+// a binding) to the symbol it declares
+#[derive(Debug, Default)]
+pub struct Resolution {
+    pub symbols: Vec<Symbol>,
+    pub uses: HashMap<Span, SymbolId>,
+    pub defs: HashMap<Span, SymbolId>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+impl Resolution {
+    pub fn symbol(&self, id: SymbolId) -> &Symbol {
+        &self.symbols[id]
+    }
+
+    // symbol used by the Expr::Path at span
+    pub fn resolved(&self, span: Span) -> Option<&Symbol> {
+        self.uses.get(&span).map(|id| self.symbol(*id))
+    }
+
+    // name of the symbol used at span, `module.name` for one of an included file
+    pub fn name<'a>(&'a self, span: Span, ident: &'a str) -> &'a str {
+        self.resolved(span).map_or(ident, |symbol| &symbol.name)
+    }
+
+    // name of the symbol declared at span
+    pub fn declared<'a>(&'a self, span: Span, ident: &'a str) -> &'a str {
+        self.defs.get(&span).map_or(ident, |id| &self.symbol(*id).name)
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics.iter().any(|d| d.severity == Severity::Error)
+    }
+}
+
+// functions and top level variables can be used anywhere in their file,
+// locals only after their declaration. the value of a top level variable is
+// worked out in order, so it cannot use a variable declared after it that
+// is not a constant. every block opens a scope. every
+// included file has its own top level scope and its names are used as
+// `module.name` elsewhere, the files nobody includes share one
+pub fn resolve(program: &Program, sources: &SourceMap) -> Resolution {
+    let mut resolver = Resolver {
+        sources,
+        files: HashMap::new(),
+        modules: HashMap::new(),
+        scopes: Vec::new(),
+        global: None,
+        resolution: Resolution::default(),
+    };
+    for node in &program.body {
+        resolver.in_file(node.span().file_id, |resolver| {
+            let id = match node {
+                Node::Include { var } => return resolver.include(var),
+                Node::Func { var } => resolver.declare(&var.ident, SymbolKind::Func, false, false, var.span),
+                Node::VariableDelclear { var } => resolver.declare(&var.ident, SymbolKind::Global, var.is_const, var.is_static, var.span),
+                Node::Struct { var } => resolver.declare(&var.ident, SymbolKind::Struct, false, false, var.span),
+                Node::Enum { var } => resolver.declare(&var.ident, SymbolKind::Enum, false, false, var.span),
+            };
+            if let Some(module) = sources.module(node.span().file_id) {
+                let symbol = &mut resolver.resolution.symbols[id];
+                symbol.name = format!("{}.{}",module,symbol.name);
+            }
+        });
+    }
+    for node in &program.body {
+        resolver.in_file(node.span().file_id, |resolver| match node {
+            Node::Func { var } => resolver.func(var),
+            Node::VariableDelclear { var } => {
+                // statics are folded at compile time whatever the order
+                resolver.global = resolver.resolution.defs.get(&var.span).copied().filter(|_| !var.is_static);
+                resolver.variable_type(var);
+                resolver.global = None;
+            },
+            Node::Struct { var } => {
+                for field in &var.fields {
+                    resolver.kind(&field.kind);
+                }
+            },
+            Node::Enum { var } => {
+                for payload in var.variants.iter().filter_map(|variant| variant.payload.as_ref()) {
+                    resolver.kind(payload);
+                }
+            },
+            Node::Include { .. } => (),
+        });
+    }
+    resolver.resolution
+}
+
+struct Resolver<'a> {
+    sources: &'a SourceMap,
+    // top level scope of every included file by id and of the other files
+    // as None, except the one being resolved
+    files: HashMap<Option<usize>, HashMap<String, SymbolId>>,
+    // the file each Module symbol stands for
+    modules: HashMap<SymbolId, usize>,
+    scopes: Vec<HashMap<String, SymbolId>>,
+    // the top level variable whose value is being resolved
+    global: Option<SymbolId>,
+    resolution: Resolution,
+}
+
+impl Resolver<'_> {
+    // runs body with the top level scope of file_id as the outermost scope
+    fn in_file(&mut self, file_id: usize, body: impl FnOnce(&mut Self)) {
+        let namespace = self.sources.module(file_id).map(|_| file_id);
+        self.scopes = vec![self.files.remove(&namespace).unwrap_or_default()];
+        body(self);
+        let scope = self.scopes.pop().unwrap_or_default();
+        self.files.insert(namespace, scope);
+    }
+
+    // a file included twice by the same file is one module
+    fn include(&mut self, include: &Include) {
+        let Some(file_id) = self.sources.included(include.span) else {
+            return;
+        };
+        let Some(name) = self.sources.module(file_id) else {
+            return;
+        };
+        if self.scopes[0].get(name).is_some_and(|id| self.modules.get(id) == Some(&file_id)) {
+            return;
+        }
+        let id = self.declare(name, SymbolKind::Module, false, false, include.span);
+        self.modules.insert(id, file_id);
+    }
+
+    fn declare(&mut self, name: &str, kind: SymbolKind, is_const: bool, is_static: bool, span: Span) -> SymbolId {
+        let id = self.resolution.symbols.len();
+        self.resolution.symbols.push(Symbol { name: name.to_string(), kind, is_const, is_static, span });
+        self.resolution.defs.insert(span, id);
+        let (scope, outer) = self.scopes.split_last_mut().unwrap();
+        if let Some(previous) = scope.get(name) {
+            let (_, line, col) = self.sources.locate(self.resolution.symbols[*previous].span);
+            let diagnostic = Diagnostic::at_loc(format!("`{}` is defined more than once",name), self.sources.locate(span), span)
+                .with_code("E0201")
+                .with_note(format!("first defined at {}:{}",line,col));
+            self.resolution.diagnostics.push(diagnostic);
+        }else if let Some(previous) = outer.iter().rev().find_map(|scope| scope.get(name)) {
+            let (_, line, col) = self.sources.locate(self.resolution.symbols[*previous].span);
+            let diagnostic = Diagnostic::at_loc(format!("`{}` shadows an earlier definition",name), self.sources.locate(span), span)
+                .warning()
+                .with_code("W0201")
+                .with_note(format!("previous definition at {}:{}",line,col));
+            self.resolution.diagnostics.push(diagnostic);
+        }
+        scope.insert(name.to_string(), id);
+        id
+    }
+
+    fn lookup(&self, name: &str) -> Option<SymbolId> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name).copied())
+    }
+
+    // a top level name of the module, the modules it includes are not part of it
+    fn member(&self, module: SymbolId, name: &str) -> Option<SymbolId> {
+        let scope = self.files.get(&Some(*self.modules.get(&module)?))?;
+        scope.get(name).copied().filter(|id| self.resolution.symbol(*id).kind != SymbolKind::Module)
+    }
+
+    // `name` or `module.name`, errors are reported at span
+    fn path(&mut self, ident: &str, span: Span) -> Option<SymbolId> {
+        let Some((module, name)) = ident.split_once('.') else {
+            return self.lookup(ident);
+        };
+        let msg = match self.lookup(module) {
+            Some(id) if self.resolution.symbol(id).kind == SymbolKind::Module => match self.member(id, name) {
+                Some(id) => return Some(id),
+                None => format!("cannot find `{}` in module `{}`",name,module),
+            },
+            Some(_) => format!("`{}` is not a module",module),
+            None => format!("cannot find module `{}` in this scope",module),
+        };
+        let diagnostic = Diagnostic::at_loc(msg, self.sources.locate(span), span).with_code("E0200");
+        self.resolution.diagnostics.push(diagnostic);
+        None
+    }
+
+    // a struct or enum named by a type, unknown types are reported by the type checker
+    fn kind(&mut self, kind: &Type) {
+        if let Some(element) = &kind.element {
+            return self.kind(element);
+        }
+        if Ty::from_name(&kind.name).is_some() || kind.name == "undifiend" {
+            return;
+        }
+        let id = match kind.name.split_once('.') {
+            Some((module, name)) => self.lookup(module).and_then(|module| self.member(module, name)),
+            None => self.scopes.iter().rev().filter_map(|scope| scope.get(&kind.name)).copied()
+                .find(|id| matches!(self.resolution.symbol(*id).kind, SymbolKind::Struct | SymbolKind::Enum)),
+        };
+        if let Some(id) = id {
+            self.resolution.uses.insert(kind.span, id);
+        }
+    }
+
+    fn variable_type(&mut self, var: &VariableDelclear) {
+        self.kind(&var.kind);
+        if let Some(value) = &var.init_value {
+            self.expr(value);
+        }
+    }
+
+    fn func(&mut self, func: &Func) {
+        self.kind(&func.return_type);
+        self.scopes.push(HashMap::new());
+        for arg in &func.args {
+            self.kind(&arg.kind);
+            self.declare(&arg.ident, SymbolKind::Arg, false, false, arg.span);
+        }
+        self.block(&func.block);
+        self.scopes.pop();
+    }
+
+    fn block(&mut self, body: &[Stmt]) {
+        self.scopes.push(HashMap::new());
+        for stmt in body {
+            self.stmt(stmt);
+        }
+        self.scopes.pop();
+    }
+
+    fn variable(&mut self, var: &VariableDelclear) {
+        // the value is resolved first, `@x = x + 1;` uses the outer x
+        self.variable_type(var);
+        self.declare(&var.ident, SymbolKind::Local, var.is_const, var.is_static, var.span);
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::VariableDelclear { var } => self.variable(var),
+            Stmt::Assign { lhs, rhs, .. } => {
+                self.expr(lhs);
+                self.expr(rhs);
+            },
+            Stmt::Expr { expr, .. } | Stmt::DebugPrint { expr, .. } => self.expr(expr),
+            Stmt::Return { value, .. } => {
+                if let Some(value) = value {
+                    self.expr(value);
+                }
+            },
+            Stmt::Block { body, .. } | Stmt::Loop { body, .. } => self.block(body),
+            Stmt::If { cond, then_block, else_block, .. } => {
+                self.expr(cond);
+                self.block(then_block);
+                if let Some(branch) = else_block {
+                    self.stmt(branch);
+                }
+            },
+            Stmt::While { cond, body, .. } => {
+                self.expr(cond);
+                self.block(body);
+            },
+            Stmt::ForRange { ident, from, to, body, span } => {
+                self.expr(from);
+                self.expr(to);
+                self.loop_var(ident, body, *span);
+            },
+            Stmt::ForIn { ident, iter, body, span } => {
+                self.expr(iter);
+                self.loop_var(ident, body, *span);
+            },
+            Stmt::Match { value, arms, .. } => {
+                self.expr(value);
+                for arm in arms {
+                    self.arm(arm, |resolver, body| resolver.block(body));
+                }
+            },
+            Stmt::Break { .. } | Stmt::Continue { .. } => (),
+        }
+    }
+
+    // the binding of the pattern is only visible in the body of its arm
+    fn arm<T>(&mut self, arm: &MatchArm<T>, body: impl FnOnce(&mut Self, &T)) {
+        self.scopes.push(HashMap::new());
+        if let Pattern::Variant { ident, binding, span, .. } = &arm.pattern {
+            self.use_symbol(ident, *span);
+            if let Some(binding) = binding {
+                self.declare(binding, SymbolKind::Binding, false, false, *span);
+            }
+        }
+        body(self, &arm.body);
+        self.scopes.pop();
+    }
+
+    fn loop_var(&mut self, ident: &str, body: &[Stmt], span: Span) {
+        self.scopes.push(HashMap::new());
+        self.declare(ident, SymbolKind::LoopVar, false, false, span);
+        self.block(body);
+        self.scopes.pop();
+    }
+
+    fn use_symbol(&mut self, ident: &str, span: Span) {
+        match self.path(ident, span) {
+            Some(id) => {
+                self.resolution.uses.insert(span, id);
+                self.initialised(id, span);
+            },
+            None if ident.contains('.') => (),
+            None => {
+                let diagnostic = Diagnostic::at_loc(format!("cannot find `{}` in this scope",ident), self.sources.locate(span), span)
+                    .with_code("E0200");
+                self.resolution.diagnostics.push(diagnostic);
+            },
+        }
+    }
+
+    // symbols are declared in the order of the program, which is the order
+    // the top level variables get their value in
+    fn initialised(&mut self, id: SymbolId, span: Span) {
+        let Some(global) = self.global else {
+            return;
+        };
+        let symbol = self.resolution.symbol(id);
+        if id <= global || symbol.kind != SymbolKind::Global || symbol.is_const || symbol.is_static {
+            return;
+        }
+        let (_, line, col) = self.sources.locate(symbol.span);
+        let diagnostic = Diagnostic::at_loc(format!("`{}` is used before it has a value",symbol.name), self.sources.locate(span), span)
+            .with_code("E0202")
+            .with_note(format!("`{}` is defined at {}:{}",symbol.name,line,col))
+            .with_help("declare it before this variable or make it a constant");
+        self.resolution.diagnostics.push(diagnostic);
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Literal { .. } => (),
+            Expr::Path { ident, span } => self.use_symbol(ident, *span),
+            Expr::StructLiteral { ident, fields, span } => {
+                self.use_symbol(ident, *span);
+                for field in fields {
+                    self.expr(&field.value);
+                }
+            },
+            Expr::Field { base, ident, span } => match base.as_ref() {
+                Expr::Path { ident: module, .. } if self.lookup(module).is_some_and(|id| self.modules.contains_key(&id)) => {
+                    self.use_symbol(&format!("{}.{}",module,ident), *span);
+                },
+                _ => self.expr(base),
+            },
+            Expr::Variant { ident, payload, span, .. } => {
+                self.use_symbol(ident, *span);
+                if let Some(payload) = payload {
+                    self.expr(payload);
+                }
+            },
+            Expr::Match { value, arms, .. } => {
+                self.expr(value);
+                for arm in arms {
+                    self.arm(arm, |resolver, body| resolver.expr(body));
+                }
+            },
+            Expr::Unary { operand, .. } => self.expr(operand),
+            Expr::Binary { left, right, .. } | Expr::Index { base: left, index: right, .. } => {
+                self.expr(left);
+                self.expr(right);
+            },
+            Expr::Array { elements, .. } => {
+                for element in elements {
+                    self.expr(element);
+                }
+            },
+            Expr::Call { ident, args, span } => {
+                self.use_symbol(ident, *span);
+                for arg in args {
+                    self.expr(arg);
+                }
+            },
+        }
+    }
+}
@@ -0,0 +1,852 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+
+use crate::ast::{AssignOp, Enum, Expr, FieldValue, Func, Literal, MatchArm, Node, Op, Pattern, Program, Stmt, Struct, Type, VariableDelclear};
+use crate::diagnostics::{Diagnostic, Severity};
+use crate::semantic::resolve::{Resolution, SymbolId, SymbolKind};
+use crate::span::{SourceMap, Span};
+use crate::value::Value;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Ty {
+    Int,
+    Float,
+    Bool,
+    Char,
+    Str,
+    Void,
+    // a struct, by name
+    Struct(String),
+    // an enum, by name
+    Enum(String),
+    // `[element; len]`, a fixed number of elements
+    Array(Box<Ty>, usize),
+    // `[element]`, any number of elements, an array of the element fits in it
+    Slice(Box<Ty>),
+    // type of anything that already has an error, never reported again
+    Error,
+}
+impl Ty {
+    // every integer name is the same 64 bit integer for now
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "i" | "int" | "i32" | "i64" | "u32" | "u64" => Some(Self::Int),
+            "f" | "float" | "f32" | "f64" => Some(Self::Float),
+            "bool" => Some(Self::Bool),
+            "char" => Some(Self::Char),
+            "str" | "string" => Some(Self::Str),
+            "void" => Some(Self::Void),
+            _ => None,
+        }
+    }
+
+    fn is_number(&self) -> bool {
+        matches!(self, Self::Int | Self::Float)
+    }
+
+    // a value of type found can be stored where self is expected. an array
+    // fits in a slice of its element and an error fits everywhere, so the
+    // empty array `[]` fits in any array of length 0
+    pub fn fits(&self, found: &Ty) -> bool {
+        match (self, found) {
+            (Self::Error, _) | (_, Self::Error) => true,
+            (Self::Array(expected, len), Self::Array(found, found_len)) => len == found_len && expected.fits(found),
+            (Self::Slice(expected), Self::Array(found, _) | Self::Slice(found)) => expected.fits(found),
+            _ => self == found,
+        }
+    }
+}
+impl Display for Ty {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Array(element, len) => return write!(f,"[{}; {}]",element,len),
+            Self::Slice(element) => return write!(f,"[{}]",element),
+            Self::Struct(name) | Self::Enum(name) => name,
+            Self::Int => "int",
+            Self::Float => "float",
+            Self::Bool => "bool",
+            Self::Char => "char",
+            Self::Str => "str",
+            Self::Void => "void",
+            Self::Error => "{error}",
+        };
+        write!(f,"{}",name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Signature {
+    pub args: Vec<Ty>,
+    pub ret: Ty,
+}
+
+// types of every symbol (by SymbolId), every expression (by span), the
+// signature of every function, the fields of every struct and the variants
+// of every enum (by name)
+#[derive(Debug, Default)]
+pub struct TypeInfo {
+    pub symbols: HashMap<SymbolId, Ty>,
+    pub exprs: HashMap<Span, Ty>,
+    pub funcs: HashMap<SymbolId, Signature>,
+    // fields in the order they are declared
+    pub structs: HashMap<String, Vec<(String, Ty)>>,
+    // variants in the order they are declared, with the type of their payload
+    pub enums: HashMap<String, Vec<(String, Option<Ty>)>>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+impl TypeInfo {
+    pub fn expr(&self, expr: &Expr) -> Ty {
+        self.exprs.get(&expr.span()).cloned().unwrap_or(Ty::Error)
+    }
+
+    pub fn field(&self, name: &str, field: &str) -> Option<&Ty> {
+        let fields = self.structs.get(name)?;
+        fields.iter().find(|(ident, _)| ident == field).map(|(_, ty)| ty)
+    }
+
+    // the payload type of a variant, Some(None) for a variant without one
+    pub fn variant(&self, name: &str, variant: &str) -> Option<&Option<Ty>> {
+        let variants = self.enums.get(name)?;
+        variants.iter().find(|(ident, _)| ident == variant).map(|(_, payload)| payload)
+    }
+
+    // a struct value with the fields in the order they are declared
+    pub fn struct_value(&self, name: &str, mut fields: Vec<(String, Value)>) -> Value {
+        if let Some(declared) = self.structs.get(name) {
+            fields.sort_by_key(|(ident, _)| declared.iter().position(|(field, _)| field == ident));
+        }
+        Value::Struct { name: name.to_string(), fields }
+    }
+
+    // value of a variable declared without one, the fields of a struct and
+    // the elements of an array are zero too, a slice is empty and an enum is
+    // its first variant with a zero payload
+    pub fn zero(&self, ty: &Ty) -> Value {
+        match ty {
+            Ty::Array(element, len) => Value::Array(vec![self.zero(element); *len]),
+            Ty::Slice(_) => Value::Array(Vec::new()),
+            Ty::Struct(name) => {
+                let fields = self.structs.get(name).map_or(&[][..], |fields| fields);
+                Value::Struct {
+                    name: name.clone(),
+                    fields: fields.iter().map(|(ident, ty)| (ident.clone(), self.zero(ty))).collect(),
+                }
+            },
+            Ty::Enum(name) => {
+                let (variant, payload) = self.enums.get(name).and_then(|variants| variants.first()).cloned().unwrap_or_default();
+                Value::Enum { name: name.clone(), variant, payload: payload.map(|ty| Box::new(self.zero(&ty))) }
+            },
+            ty => Value::zero(ty),
+        }
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics.iter().any(|d| d.severity == Severity::Error)
+    }
+}
+
+// the type of a binary expression, None when op does not apply to the operands.
+// there are no implicit conversions, both sides have the same type
+pub fn binary_type(op: Op, left: &Ty, right: &Ty) -> Option<Ty> {
+    if left != right {
+        return None;
+    }
+    match op {
+        Op::Add | Op::Sub | Op::Multi | Op::Divide | Op::Mod if left.is_number() => Some(left.clone()),
+        Op::And | Op::Or | Op::Xor if matches!(left, Ty::Int | Ty::Bool) => Some(left.clone()),
+        Op::Lsh | Op::Rsh if *left == Ty::Int => Some(Ty::Int),
+        Op::Eq | Op::NotEq if *left != Ty::Void => Some(Ty::Bool),
+        Op::Less | Op::LessEq | Op::More | Op::MoreEq if left.is_number() || *left == Ty::Char => Some(Ty::Bool),
+        Op::AndAnd | Op::OrOr if *left == Ty::Bool => Some(Ty::Bool),
+        _ => None,
+    }
+}
+
+// `!` is the logical not of a bool and the bitwise not of an int
+pub fn unary_type(op: Op, operand: &Ty) -> Option<Ty> {
+    match op {
+        Op::Sub | Op::Add if operand.is_number() => Some(operand.clone()),
+        Op::Not if matches!(operand, Ty::Int | Ty::Bool) => Some(operand.clone()),
+        _ => None,
+    }
+}
+
+pub fn check(program: &Program, resolution: &Resolution, sources: &SourceMap) -> TypeInfo {
+    let mut checker = Checker { resolution, sources, info: TypeInfo::default(), ret: Ty::Void, pending: HashMap::new() };
+    // struct and enum names first so fields, variants, signatures and globals can use any of them
+    let structs: Vec<&Struct> = program.body.iter().filter_map(|node| match node {
+        Node::Struct { var } => Some(var),
+        _ => None,
+    }).collect();
+    let enums: Vec<&Enum> = program.body.iter().filter_map(|node| match node {
+        Node::Enum { var } => Some(var),
+        _ => None,
+    }).collect();
+    // keyed by the qualified name, structs of two files can share a name
+    for var in &structs {
+        checker.info.structs.insert(resolution.declared(var.span, &var.ident).to_string(), Vec::new());
+    }
+    for var in &enums {
+        checker.info.enums.insert(resolution.declared(var.span, &var.ident).to_string(), Vec::new());
+    }
+    for var in &structs {
+        checker.fields(var);
+    }
+    for var in &enums {
+        checker.variants(var);
+    }
+    for (ident, span) in structs.iter().map(|var| (&var.ident, var.span)).chain(enums.iter().map(|var| (&var.ident, var.span))) {
+        checker.recursive(resolution.declared(span, ident), span);
+    }
+    // signatures and declared global types first, they can be used before their definition
+    for node in &program.body {
+        match node {
+            Node::Func { var } => {
+                let args = var.args.iter().map(|arg| checker.value_type(&arg.kind)).collect();
+                let ret = checker.resolve_type(&var.return_type);
+                if let Some(id) = resolution.defs.get(&var.span) {
+                    checker.info.funcs.insert(*id, Signature { args, ret });
+                }
+            },
+            Node::VariableDelclear { var } => {
+                if var.kind.name != "undifiend" {
+                    let ty = checker.value_type(&var.kind);
+                    checker.define(var.span, ty);
+                }
+                if let Some(id) = resolution.defs.get(&var.span) {
+                    checker.pending.insert(*id, var);
+                }
+            },
+            Node::Include { .. } | Node::Struct { .. } | Node::Enum { .. } => (),
+        }
+    }
+    for node in &program.body {
+        match node {
+            Node::Func { var } => checker.func(var),
+            Node::VariableDelclear { var } => checker.global(var.span),
+            Node::Include { .. } | Node::Struct { .. } | Node::Enum { .. } => (),
+        }
+    }
+    checker.info
+}
+
+struct Checker<'a> {
+    resolution: &'a Resolution,
+    sources: &'a SourceMap,
+    info: TypeInfo,
+    // return type of the function being checked
+    ret: Ty,
+    // globals not checked yet, an inferred one is checked when first used
+    pending: HashMap<SymbolId, &'a VariableDelclear>,
+}
+
+impl<'a> Checker<'a> {
+    // checks the global declared at span unless that already happened. while
+    // it is checked it is not pending, so a global used in its own value has no type
+    fn global(&mut self, decl: Span) {
+        let pending = self.resolution.defs.get(&decl).and_then(|id| self.pending.remove(id));
+        if let Some(var) = pending {
+            self.variable(var);
+        }
+    }
+
+    fn error(&mut self, code: &'static str, msg: impl ToString, span: Span) {
+        let diagnostic = Diagnostic::at_loc(msg, self.sources.locate(span), span).with_code(code);
+        self.info.diagnostics.push(diagnostic);
+    }
+
+    // reports a found that does not fit in expected
+    fn expect(&mut self, expected: &Ty, found: &Ty, span: Span) {
+        if !expected.fits(found) {
+            self.error("E0301", format!("mismatched types, expected `{}` found `{}`",expected,found), span);
+        }
+    }
+
+    fn resolve_type(&mut self, kind: &Type) -> Ty {
+        if let Some(element) = &kind.element {
+            return match (self.value_type(element), kind.len) {
+                (Ty::Error, _) => Ty::Error,
+                (element, Some(len)) => Ty::Array(Box::new(element), len as usize),
+                (element, None) => Ty::Slice(Box::new(element)),
+            };
+        }
+        if let Some(ty) = Ty::from_name(&kind.name) {
+            return ty;
+        }
+        // the resolver found the struct or enum the name stands for
+        match self.resolution.resolved(kind.span) {
+            Some(symbol) if symbol.kind == SymbolKind::Struct => return Ty::Struct(symbol.name.clone()),
+            Some(symbol) if symbol.kind == SymbolKind::Enum => return Ty::Enum(symbol.name.clone()),
+            _ => (),
+        }
+        self.error("E0300", format!("unknown type `{}`",kind.name), kind.span);
+        Ty::Error
+    }
+
+    fn fields(&mut self, var: &Struct) {
+        let mut fields = Vec::<(String, Ty)>::new();
+        for field in &var.fields {
+            let ty = self.value_type(&field.kind);
+            if fields.iter().any(|(ident, _)| *ident == field.ident) {
+                self.error("E0201", format!("field `{}` is defined more than once",field.ident), field.span);
+                continue;
+            }
+            fields.push((field.ident.clone(), ty));
+        }
+        self.info.structs.insert(self.resolution.declared(var.span, &var.ident).to_string(), fields);
+    }
+
+    fn variants(&mut self, var: &Enum) {
+        let mut variants = Vec::<(String, Option<Ty>)>::new();
+        for variant in &var.variants {
+            let payload = variant.payload.as_ref().map(|kind| self.value_type(kind));
+            if variants.iter().any(|(ident, _)| *ident == variant.ident) {
+                self.error("E0201", format!("variant `{}` is defined more than once",variant.ident), variant.span);
+                continue;
+            }
+            variants.push((variant.ident.clone(), payload));
+        }
+        let name = self.resolution.declared(var.span, &var.ident);
+        if variants.is_empty() {
+            self.error("E0306", format!("enum `{}` has no variants",name), var.span);
+        }
+        self.info.enums.insert(name.to_string(), variants);
+    }
+
+    // types held by a struct or enum, a variant without payload is void
+    fn parts(&self, name: &str) -> Vec<Ty> {
+        if let Some(fields) = self.info.structs.get(name) {
+            return fields.iter().map(|(_, ty)| ty.clone()).collect();
+        }
+        let variants = self.info.enums.get(name).map_or(&[][..], |variants| variants);
+        variants.iter().map(|(_, payload)| payload.clone().unwrap_or(Ty::Void)).collect()
+    }
+
+    // a struct or enum that holds itself, directly or through other structs
+    // and enums, has no size. the field or payload that closes the loop
+    // becomes an error so it is reported once at the name of the declaration
+    fn recursive(&mut self, ident: &str, span: Span) {
+        let mut stack = vec![ident.to_string()];
+        let mut seen = Vec::<String>::new();
+        while let Some(name) = stack.pop() {
+            for (i, mut ty) in self.parts(&name).into_iter().enumerate() {
+                // an array holds its elements, a slice can be empty
+                while let Ty::Array(element, _) = ty {
+                    ty = *element;
+                }
+                let (Ty::Struct(inner) | Ty::Enum(inner)) = ty else {
+                    continue;
+                };
+                if inner == ident {
+                    let kind = if self.info.structs.contains_key(ident) { "struct" } else { "enum" };
+                    self.error("E0300", format!("{} `{}` contains itself and would be infinitely large",kind,ident), span);
+                    if let Some(fields) = self.info.structs.get_mut(&name) {
+                        fields[i].1 = Ty::Error;
+                    }else if let Some(variants) = self.info.enums.get_mut(&name) {
+                        variants[i].1 = Some(Ty::Error);
+                    }
+                    return;
+                }
+                if !seen.contains(&inner) {
+                    seen.push(inner.clone());
+                    stack.push(inner);
+                }
+            }
+        }
+    }
+
+    // type of a variable or argument, void is not a value
+    fn value_type(&mut self, kind: &Type) -> Ty {
+        let ty = self.resolve_type(kind);
+        if ty == Ty::Void {
+            self.error("E0300", "`void` cannot be the type of a value", kind.span);
+            return Ty::Error;
+        }
+        ty
+    }
+
+    fn define(&mut self, decl: Span, ty: Ty) {
+        if let Some(id) = self.resolution.defs.get(&decl) {
+            self.info.symbols.insert(*id, ty);
+        }
+    }
+
+    fn func(&mut self, func: &Func) {
+        let Some(signature) = self.resolution.defs.get(&func.span).and_then(|id| self.info.funcs.get(id)).cloned() else {
+            return;
+        };
+        for (arg, ty) in func.args.iter().zip(signature.args) {
+            self.define(arg.span, ty);
+        }
+        self.ret = signature.ret;
+        self.block(&func.block);
+        if !matches!(self.ret, Ty::Void | Ty::Error) && !always_returns(&func.block) {
+            let span = func.return_type.span;
+            self.error("E0303", format!("function `{}` does not always return a `{}`",func.ident,self.ret), span);
+        }
+    }
+
+    fn variable(&mut self, var: &VariableDelclear) {
+        let value = var.init_value.as_ref().map(|value| self.expr(value));
+        let ty = if var.kind.name == "undifiend" {
+            // `name = value;` and `name : value;` take the type of the value
+            let ty = value.unwrap_or(Ty::Error);
+            if ty == Ty::Void {
+                self.error("E0301", "a `void` value cannot be stored", var.span);
+                Ty::Error
+            }else if matches!(&var.init_value, Some(Expr::Array { elements, .. }) if elements.is_empty()) {
+                self.error("E0301", "cannot infer the type of an empty array", var.span);
+                Ty::Error
+            }else {
+                ty
+            }
+        }else {
+            let ty = self.value_type(&var.kind);
+            if let (Some(found), Some(value)) = (value, &var.init_value) {
+                self.expect(&ty, &found, value.span());
+            }
+            ty
+        };
+        self.define(var.span, ty);
+    }
+
+    fn block(&mut self, body: &[Stmt]) {
+        for stmt in body {
+            self.stmt(stmt);
+        }
+    }
+
+    fn condition(&mut self, cond: &Expr) {
+        let ty = self.expr(cond);
+        self.expect(&Ty::Bool, &ty, cond.span());
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::VariableDelclear { var } => self.variable(var),
+            Stmt::Assign { lhs, op, rhs, .. } => {
+                let target = self.expr(lhs);
+                let value = self.expr(rhs);
+                if let Expr::Index { base, .. } = lhs {
+                    if self.info.exprs.get(&base.span()) == Some(&Ty::Str) {
+                        self.error("E0302", "the chars of a `str` cannot be assigned to", lhs.span());
+                        return;
+                    }
+                }
+                self.assign(target, *op, value, rhs.span());
+            },
+            // the only place a call can return nothing
+            Stmt::Expr { expr: Expr::Call { ident, args, span }, .. } => {
+                let ty = self.call(ident, args, *span);
+                self.info.exprs.insert(*span, ty);
+            },
+            Stmt::Expr { expr, .. } => {
+                self.expr(expr);
+            },
+            Stmt::DebugPrint { expr, .. } => {
+                if self.expr(expr) == Ty::Void {
+                    self.error("E0301", "a `void` value cannot be printed", expr.span());
+                }
+            },
+            Stmt::Return { value, span } => {
+                let (found, span) = match value {
+                    Some(value) => (self.expr(value), value.span()),
+                    None => (Ty::Void, *span),
+                };
+                let ret = self.ret.clone();
+                self.expect(&ret, &found, span);
+            },
+            Stmt::Block { body, .. } | Stmt::Loop { body, .. } => self.block(body),
+            Stmt::If { cond, then_block, else_block, .. } => {
+                self.condition(cond);
+                self.block(then_block);
+                if let Some(branch) = else_block {
+                    self.stmt(branch);
+                }
+            },
+            Stmt::While { cond, body, .. } => {
+                self.condition(cond);
+                self.block(body);
+            },
+            Stmt::ForRange { from, to, body, span, .. } => {
+                for bound in [from, to] {
+                    let ty = self.expr(bound);
+                    self.expect(&Ty::Int, &ty, bound.span());
+                }
+                self.define(*span, Ty::Int);
+                self.block(body);
+            },
+            Stmt::ForIn { iter, body, span, .. } => {
+                let item = match self.expr(iter) {
+                    Ty::Str => Ty::Char,
+                    Ty::Array(element, _) | Ty::Slice(element) => *element,
+                    Ty::Error => Ty::Error,
+                    ty => {
+                        self.error("E0302", format!("cannot iterate over `{}`",ty), iter.span());
+                        Ty::Error
+                    },
+                };
+                self.define(*span, item);
+                self.block(body);
+            },
+            Stmt::Match { value, arms, .. } => self.arms(value, arms, |checker, body| checker.block(body)),
+            Stmt::Break { .. } | Stmt::Continue { .. } => (),
+        }
+    }
+
+    fn assign(&mut self, target: Ty, op: AssignOp, value: Ty, span: Span) {
+        let Some(op) = op.binary_op() else {
+            self.expect(&target, &value, span);
+            return;
+        };
+        if target == Ty::Error || value == Ty::Error {
+            return;
+        }
+        match binary_type(op, &target, &value) {
+            Some(ty) => self.expect(&target, &ty, span),
+            None => self.error("E0302", format!("`{}=` cannot be applied to `{}` and `{}`",op,target,value), span),
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) -> Ty {
+        let ty = match expr {
+            Expr::Literal { value, .. } => match value {
+                Literal::Int(_) => Ty::Int,
+                Literal::Float(_) => Ty::Float,
+                Literal::Str(_) => Ty::Str,
+                Literal::Char(_) => Ty::Char,
+                Literal::Bool(_) => Ty::Bool,
+            },
+            Expr::Path { span, .. } => self.path(*span),
+            // `module.name`
+            Expr::Field { span, .. } if self.resolution.uses.contains_key(span) => self.path(*span),
+            Expr::Unary { op, operand, span } => {
+                let operand = self.expr(operand);
+                match unary_type(*op, &operand) {
+                    Some(ty) => ty,
+                    None if operand == Ty::Error => Ty::Error,
+                    None => {
+                        self.error("E0302", format!("`{}` cannot be applied to `{}`",op,operand), *span);
+                        Ty::Error
+                    },
+                }
+            },
+            Expr::Binary { left, right, op, span } => {
+                let (left, right) = (self.expr(left), self.expr(right));
+                match binary_type(*op, &left, &right) {
+                    Some(ty) => ty,
+                    None if left == Ty::Error || right == Ty::Error => Ty::Error,
+                    None => {
+                        self.error("E0302", format!("`{}` cannot be applied to `{}` and `{}`",op,left,right), *span);
+                        Ty::Error
+                    },
+                }
+            },
+            Expr::StructLiteral { ident, fields, span } => self.struct_literal(ident, fields, *span),
+            Expr::Field { base, ident, span } => match self.expr(base) {
+                Ty::Struct(name) => match self.info.field(&name, ident) {
+                    Some(ty) => ty.clone(),
+                    None => {
+                        let span = Span::new(span.file_id, span.end - ident.len(), span.end);
+                        self.error("E0305", format!("struct `{}` has no field `{}`",name,ident), span);
+                        Ty::Error
+                    },
+                },
+                Ty::Error => Ty::Error,
+                ty => {
+                    self.error("E0305", format!("`{}` has no field `{}`",ty,ident), *span);
+                    Ty::Error
+                },
+            },
+            Expr::Variant { ident, variant, payload, span } => self.variant(ident, variant, payload.as_deref(), *span),
+            // every element has the type of the first one that has no error
+            Expr::Array { elements, .. } => {
+                let mut ty = None::<Ty>;
+                for element in elements {
+                    let found = self.expr(element);
+                    match &ty {
+                        Some(expected) if *expected != Ty::Error => self.expect(expected, &found, element.span()),
+                        _ => ty = Some(found),
+                    }
+                }
+                Ty::Array(Box::new(ty.unwrap_or(Ty::Error)), elements.len())
+            },
+            Expr::Index { base, index, .. } => self.index(base, index),
+            Expr::Call { ident, args, span } => match self.call(ident, args, *span) {
+                Ty::Void => {
+                    let name = self.resolution.name(*span, ident).to_string();
+                    self.error("E0301", format!("function `{}` does not return a value",name), *span);
+                    Ty::Error
+                },
+                ty => ty,
+            },
+            // every arm has the type of the first one that has no error
+            Expr::Match { value, arms, .. } => {
+                let mut ty = None::<Ty>;
+                self.arms(value, arms, |checker, body| {
+                    let found = checker.expr(body);
+                    match &ty {
+                        Some(expected) if *expected != Ty::Error => checker.expect(expected, &found, body.span()),
+                        _ => ty = Some(found),
+                    }
+                });
+                ty.unwrap_or(Ty::Error)
+            },
+        };
+        self.info.exprs.insert(expr.span(), ty.clone());
+        ty
+    }
+
+    // the element at index of an array, slice or str. a literal index past
+    // the end of an array is known to be out of bounds before running
+    fn index(&mut self, base: &Expr, index: &Expr) -> Ty {
+        let ty = self.expr(base);
+        let found = self.expr(index);
+        self.expect(&Ty::Int, &found, index.span());
+        match ty {
+            Ty::Array(element, len) => {
+                if let Expr::Literal { value: Literal::Int(i), span } = index {
+                    if *i as usize >= len {
+                        self.error("E0302", format!("index {} is out of bounds for `{}`",i,Ty::Array(element.clone(), len)), *span);
+                    }
+                }
+                *element
+            },
+            Ty::Slice(element) => *element,
+            Ty::Str => Ty::Char,
+            Ty::Error => Ty::Error,
+            ty => {
+                self.error("E0302", format!("cannot index into `{}`",ty), base.span());
+                Ty::Error
+            },
+        }
+    }
+
+    // type of the value of the symbol used at span
+    fn path(&mut self, span: Span) -> Ty {
+        let Some(id) = self.resolution.uses.get(&span).copied() else {
+            return Ty::Error;
+        };
+        let symbol = self.resolution.symbol(id);
+        let what = match symbol.kind {
+            SymbolKind::Func => "function",
+            SymbolKind::Struct => "struct",
+            SymbolKind::Enum => "enum",
+            SymbolKind::Module => "module",
+            _ => "",
+        };
+        if !what.is_empty() {
+            self.error("E0304", format!("{} `{}` cannot be used as a value",what,symbol.name), span);
+            return Ty::Error;
+        }
+        match self.info.symbols.get(&id).cloned().or_else(|| {
+            self.global(symbol.span);
+            self.info.symbols.get(&id).cloned()
+        }) {
+            Some(ty) => ty,
+            None => {
+                self.error("E0304", format!("the type of `{}` is not known here",symbol.name), span);
+                Ty::Error
+            },
+        }
+    }
+
+    // the symbol at span is a function given one argument of the type of
+    // each of its parameters, returns what it returns
+    fn call(&mut self, ident: &str, args: &[Expr], span: Span) -> Ty {
+        let found: Vec<Ty> = args.iter().map(|arg| self.expr(arg)).collect();
+        let Some(id) = self.resolution.uses.get(&span).copied() else {
+            return Ty::Error;
+        };
+        if self.resolution.symbol(id).kind != SymbolKind::Func {
+            self.error("E0307", format!("`{}` is not a function",ident), span);
+            return Ty::Error;
+        }
+        let Some(signature) = self.info.funcs.get(&id).cloned() else {
+            return Ty::Error;
+        };
+        if found.len() != signature.args.len() {
+            let name = self.resolution.name(span, ident);
+            let noun = if signature.args.len() == 1 { "argument" } else { "arguments" };
+            let verb = if found.len() == 1 { "was" } else { "were" };
+            self.error("E0307", format!("function `{}` takes {} {} but {} {} given",name,signature.args.len(),noun,found.len(),verb), span);
+        }
+        for ((expected, found), arg) in signature.args.iter().zip(&found).zip(args) {
+            self.expect(expected, found, arg.span());
+        }
+        signature.ret
+    }
+
+    // every field of the struct is given exactly once with a value of its type
+    fn struct_literal(&mut self, ident: &str, fields: &[FieldValue], span: Span) -> Ty {
+        let values: Vec<Ty> = fields.iter().map(|field| self.expr(&field.value)).collect();
+        let Some(id) = self.resolution.uses.get(&span) else {
+            return Ty::Error;
+        };
+        if self.resolution.symbol(*id).kind != SymbolKind::Struct {
+            self.error("E0305", format!("`{}` is not a struct",ident), span);
+            return Ty::Error;
+        }
+        let ident = self.resolution.name(span, ident);
+        let declared = self.info.structs.get(ident).cloned().unwrap_or_default();
+        let mut given = Vec::<&str>::new();
+        for (field, value) in fields.iter().zip(values) {
+            if given.contains(&field.ident.as_str()) {
+                self.error("E0305", format!("field `{}` is given more than once",field.ident), field.ident_span());
+                continue;
+            }
+            given.push(&field.ident);
+            match declared.iter().find(|(name, _)| *name == field.ident) {
+                Some((_, ty)) => self.expect(ty, &value, field.value.span()),
+                None => self.error("E0305", format!("struct `{}` has no field `{}`",ident,field.ident), field.ident_span()),
+            }
+        }
+        let missing: Vec<String> = declared.iter()
+            .filter(|(name, _)| !given.contains(&name.as_str()))
+            .map(|(name, _)| format!("`{}`",name))
+            .collect();
+        if !missing.is_empty() {
+            let noun = if missing.len() == 1 { "field" } else { "fields" };
+            self.error("E0305", format!("missing {} {} in `{}`",noun,missing.join(", "),ident), span);
+        }
+        Ty::Struct(ident.to_string())
+    }
+
+    // the symbol at span is an enum with the variant, returns its payload type
+    fn enum_variant(&mut self, ident: &str, variant: &str, span: Span) -> Option<Option<Ty>> {
+        let id = self.resolution.uses.get(&span)?;
+        if self.resolution.symbol(*id).kind != SymbolKind::Enum {
+            self.error("E0306", format!("`{}` is not an enum",ident), span);
+            return None;
+        }
+        let payload = self.info.variant(ident, variant).cloned();
+        if payload.is_none() {
+            self.error("E0306", format!("enum `{}` has no variant `{}`",ident,variant), span);
+        }
+        payload
+    }
+
+    // a variant is given a payload exactly when it declares one
+    fn variant(&mut self, ident: &str, variant: &str, payload: Option<&Expr>, span: Span) -> Ty {
+        let value = payload.map(|payload| self.expr(payload));
+        let ident = self.resolution.name(span, ident);
+        let Some(declared) = self.enum_variant(ident, variant, span) else {
+            return Ty::Error;
+        };
+        match (declared, value, payload) {
+            (Some(ty), Some(found), Some(payload)) => self.expect(&ty, &found, payload.span()),
+            (Some(ty), _, _) => self.error("E0306", format!("`{}::{}` needs a `{}` value",ident,variant,ty), span),
+            (None, _, Some(payload)) => self.error("E0306", format!("`{}::{}` has no value",ident,variant), payload.span()),
+            (None, _, None) => (),
+        }
+        Ty::Enum(ident.to_string())
+    }
+
+    // the arms of a match take apart a value of an enum and cover every
+    // variant of it, an arm after the ones covering its variant is never
+    // reached. the binding of a pattern is defined before body checks the arm
+    fn arms<T>(&mut self, value: &Expr, arms: &[MatchArm<T>], mut body: impl FnMut(&mut Self, &T)) {
+        let name = match self.expr(value) {
+            Ty::Enum(name) => Some(name),
+            Ty::Error => None,
+            ty => {
+                self.error("E0306", format!("cannot match on `{}`, only on enums",ty), value.span());
+                None
+            },
+        };
+        let variants = name.as_ref().and_then(|name| self.info.enums.get(name)).cloned().unwrap_or_default();
+        let mut covered = Vec::<&str>::new();
+        let mut wildcard = false;
+        for arm in arms {
+            let variant = match &arm.pattern {
+                Pattern::Wildcard { .. } => None,
+                Pattern::Variant { ident, variant, binding, span } => {
+                    let ident = self.resolution.name(*span, ident);
+                    let payload = match &name {
+                        Some(name) if name != ident => {
+                            self.expect(&Ty::Enum(name.clone()), &Ty::Enum(ident.to_string()), *span);
+                            None
+                        },
+                        _ => self.enum_variant(ident, variant, *span),
+                    };
+                    let bound = match (&payload, binding) {
+                        (Some(None), Some(_)) => {
+                            self.error("E0306", format!("`{}::{}` has no value to bind",ident,variant), *span);
+                            Ty::Error
+                        },
+                        (Some(Some(ty)), _) => ty.clone(),
+                        _ => Ty::Error,
+                    };
+                    if binding.is_some() {
+                        self.define(*span, bound);
+                    }
+                    payload.map(|_| variant.as_str())
+                },
+            };
+            let is_wildcard = matches!(arm.pattern, Pattern::Wildcard { .. });
+            // a pattern with an error is not reported again
+            let reached = !wildcard && match variant {
+                Some(variant) => !covered.contains(&variant),
+                None if is_wildcard => name.is_none() || covered.len() < variants.len(),
+                None => true,
+            };
+            if !reached {
+                let span = arm.pattern.span();
+                let diagnostic = Diagnostic::at_loc("unreachable match arm", self.sources.locate(span), span)
+                    .warning()
+                    .with_code("W0306")
+                    .with_note("the variants it matches are covered by the arms before it");
+                self.info.diagnostics.push(diagnostic);
+            }
+            match variant {
+                Some(variant) if !covered.contains(&variant) => covered.push(variant),
+                Some(_) => (),
+                None => wildcard |= is_wildcard,
+            }
+            body(self, &arm.body);
+        }
+        let Some(name) = name.filter(|_| !wildcard) else {
+            return;
+        };
+        let missing: Vec<String> = variants.iter()
+            .filter(|(variant, _)| !covered.contains(&variant.as_str()))
+            .map(|(variant, _)| format!("`{}::{}`",name,variant))
+            .collect();
+        if !missing.is_empty() {
+            let span = value.span();
+            let verb = if missing.len() == 1 { "is" } else { "are" };
+            let diagnostic = Diagnostic::at_loc(format!("non-exhaustive match, {} {} not covered",missing.join(", "),verb), self.sources.locate(span), span)
+                .with_code("E0306")
+                .with_help("add an arm for every missing variant or a `_` arm");
+            self.info.diagnostics.push(diagnostic);
+        }
+    }
+}
+
+// true when every path through body ends in a return or an endless loop
+fn always_returns(body: &[Stmt]) -> bool {
+    body.iter().any(|stmt| match stmt {
+        Stmt::Return { .. } => true,
+        Stmt::Block { body, .. } => always_returns(body),
+        Stmt::If { then_block, else_block: Some(branch), .. } => {
+            always_returns(then_block) && always_returns(std::slice::from_ref(branch.as_ref()))
+        },
+        Stmt::Loop { body, .. } => !breaks(body),
+        // matches are exhaustive
+        Stmt::Match { arms, .. } => !arms.is_empty() && arms.iter().all(|arm| always_returns(&arm.body)),
+        _ => false,
+    })
+}
+
+// true when body has a `break` for the enclosing loop
+fn breaks(body: &[Stmt]) -> bool {
+    body.iter().any(|stmt| match stmt {
+        Stmt::Break { .. } => true,
+        Stmt::Block { body, .. } => breaks(body),
+        Stmt::If { then_block, else_block, .. } => {
+            breaks(then_block) || else_block.as_ref().is_some_and(|branch| breaks(std::slice::from_ref(branch.as_ref())))
+        },
+        Stmt::Match { arms, .. } => arms.iter().any(|arm| breaks(&arm.body)),
+        _ => false,
+    })
+}
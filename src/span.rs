@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::lexer::Loc;
+
+// byte range `start..end` inside the file registered as `file_id` in a SourceMap
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub file_id: usize,
+    pub start: usize,
+    pub end: usize,
+}
+impl Span {
+    pub fn new(file_id: usize, start: usize, end: usize) -> Self {
+        Self { file_id, start, end }
+    }
+
+    // smallest span covering both self and other
+    pub fn to(&self, other: Span) -> Self {
+        Self { file_id: self.file_id, start: self.start.min(other.start), end: self.end.max(other.end) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    pub fn contains(&self, offset: usize) -> bool {
+        self.start <= offset && offset < self.end
+    }
+}
+
+// 1 based column right after `line`, the text from the start of a line.
+// columns count chars, not bytes, the way editors and pest show them
+pub fn column(line: &[u8]) -> usize {
+    line.iter().filter(|c| **c & 0xc0 != 0x80).count() + 1
+}
+
+#[derive(Debug)]
+pub struct SourceFile {
+    pub path: String,
+    pub source: String,
+}
+
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+    // span of every `include` to the id of the file it loaded
+    includes: HashMap<Span, usize>,
+}
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // returns the file id used by the spans of this file
+    pub fn add(&mut self, path: impl ToString, source: impl ToString) -> usize {
+        self.files.push(SourceFile { path: path.to_string(), source: source.to_string() });
+        self.files.len() - 1
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    pub fn get(&self, file_id: usize) -> Option<&SourceFile> {
+        self.files.get(file_id)
+    }
+
+    pub fn find(&self, path: &str) -> Option<&SourceFile> {
+        self.files.iter().find(|file| file.path == path)
+    }
+
+    pub fn include(&mut self, span: Span, file_id: usize) {
+        self.includes.insert(span, file_id);
+    }
+
+    // file loaded by the include at span
+    pub fn included(&self, span: Span) -> Option<usize> {
+        self.includes.get(&span).copied()
+    }
+
+    // name of the namespace of an included file, the stem of its path.
+    // a file nobody includes has none and its names are not qualified
+    pub fn module(&self, file_id: usize) -> Option<&str> {
+        if !self.includes.values().any(|id| *id == file_id) {
+            return None;
+        }
+        Path::new(&self.get(file_id)?.path).file_stem()?.to_str()
+    }
+
+    pub fn snippet(&self, span: Span) -> Option<&str> {
+        self.get(span.file_id)?.source.get(span.start..span.end)
+    }
+
+    // 1 based line and column of the start of the span
+    pub fn line_col(&self, span: Span) -> Option<(usize, usize)> {
+        let source = self.get(span.file_id)?.source.as_bytes();
+        let before = source.get(..span.start)?;
+        let line = before.iter().filter(|c| **c == b'\n').count() + 1;
+        let bol = before.iter().rposition(|c| *c == b'\n').map_or(0, |i| i + 1);
+        Some((line, column(&before[bol..])))
+    }
+
+    // file, line and column of a span, for diagnostics of passes after parsing
+    pub fn locate(&self, span: Span) -> Loc {
+        let path = self.get(span.file_id).map_or(String::new(), |file| file.path.clone());
+        let (line, col) = self.line_col(span).unwrap_or((1, 1));
+        (path, line, col)
+    }
+}
@@ -1,78 +1,2044 @@
+#[cfg(test)]
+mod common {
+    use crate::ast::{Expr, Op, Program};
+    use crate::diagnostics::Diagnostic;
+    use crate::lexer::Lexer;
+    use crate::semantic::{Analysis, analyze};
+    use crate::span::SourceMap;
+
+    // parses source as `main.nmt`, which has to parse, and analyses it
+    fn analysis(source: &str) -> (Program, Analysis, SourceMap, Vec<Diagnostic>) {
+        let mut sources = SourceMap::new();
+        sources.add("main.nmt", source);
+        let program = Program::new(&mut Lexer::from_source("main.nmt", source));
+        assert!(!program.has_errors(), "{:?}", program.diagnostics);
+        let mut diagnostics = Vec::new();
+        let analysis = analyze(&program, &sources, &mut diagnostics);
+        (program, analysis, sources, diagnostics)
+    }
+
+    // a program without any diagnostic, ready to run
+    pub fn analyze_ok(source: &str) -> (Program, Analysis, SourceMap) {
+        let (program, analysis, sources, diagnostics) = analysis(source);
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+        (program, analysis, sources)
+    }
+
+    // an expression as an s-expression, `(op left right)` with op printed by `op`
+    pub fn sexpr(expr: &Expr, op: fn(Op) -> String) -> String {
+        match expr {
+            Expr::Literal { value, .. } => value.to_string(),
+            Expr::Path { ident, .. } => ident.clone(),
+            Expr::Unary { op: unary, operand, .. } => format!("({} {})", op(*unary), sexpr(operand, op)),
+            Expr::Binary { left, op: binary, right, .. } => format!("({} {} {})", op(*binary), sexpr(left, op), sexpr(right, op)),
+            Expr::StructLiteral { ident, fields, .. } => {
+                let fields: Vec<String> = fields.iter().map(|field| format!(" {}: {}", field.ident, sexpr(&field.value, op))).collect();
+                format!("({}{})", ident, fields.concat())
+            },
+            Expr::Field { base, ident, .. } => format!("(. {} {})", sexpr(base, op), ident),
+            Expr::Variant { ident, variant, payload: Some(payload), .. } => format!("({}::{} {})", ident, variant, sexpr(payload, op)),
+            Expr::Variant { ident, variant, .. } => format!("{}::{}", ident, variant),
+            Expr::Match { value, arms, .. } => {
+                let arms: Vec<String> = arms.iter().map(|arm| format!(" {}", sexpr(&arm.body, op))).collect();
+                format!("(match {}{})", sexpr(value, op), arms.concat())
+            },
+            Expr::Array { elements, .. } => {
+                let elements: Vec<String> = elements.iter().map(|element| format!(" {}", sexpr(element, op))).collect();
+                format!("([]{})", elements.concat())
+            },
+            Expr::Index { base, index, .. } => format!("([] {} {})", sexpr(base, op), sexpr(index, op)),
+            Expr::Call { ident, args, .. } => {
+                let args: Vec<String> = args.iter().map(|arg| format!(" {}", sexpr(arg, op))).collect();
+                format!("(call {}{})", ident, args.concat())
+            },
+        }
+    }
+}
+
 #[cfg(test)]
 mod parser_tests {
-    use crate::parser::variable::VariableDelclear;
-    use crate::parser::definition::Type;
+    use crate::ast::{Expr, Literal, Type, VariableDelclear};
     use crate::lexer::Lexer;
+    use crate::span::Span;
+
+    fn facts(start: usize, end: usize) -> Expr {
+        Expr::Literal { value: Literal::Str("facts".to_string()), span: Span::new(0, start, end) }
+    }
 
     #[test]
     fn dynamic_variable_declearation() {
         let mut lexer = Lexer::from_str("hello u32;\n");
-        assert_eq!(VariableDelclear::new(&mut lexer),VariableDelclear{
+        assert_eq!(VariableDelclear::new(&mut lexer).unwrap(),VariableDelclear{
             is_const: false,
             is_static: false,
             ident: "hello".to_string(),
-            kind: Type {
-                name: "u32".to_string(),
-            },
-            init_value: "".to_string(),
+            kind: Type::named("u32", Span::new(0, 6, 9)),
+            init_value: None,
+            span: Span::new(0, 0, 10),
         });
         let mut lexer = Lexer::from_str("hello = \"facts\";\n");
-        assert_eq!(VariableDelclear::new(&mut lexer),VariableDelclear{
+        assert_eq!(VariableDelclear::new(&mut lexer).unwrap(),VariableDelclear{
             is_const: false,
             is_static: false,
             ident: "hello".to_string(),
-            kind: Type {
-                name: "undifiend".to_string(),
-            },
-            init_value: "facts".to_string(),
+            kind: Type::named("undifiend", Span::new(0, 5, 5)),
+            init_value: Some(facts(8, 15)),
+            span: Span::new(0, 0, 16),
         });
         let mut lexer = Lexer::from_str("hello u32 = \"facts\";\n");
-        assert_eq!(VariableDelclear::new(&mut lexer),VariableDelclear{
+        assert_eq!(VariableDelclear::new(&mut lexer).unwrap(),VariableDelclear{
             is_const: false,
             is_static: false,
             ident: "hello".to_string(),
-            kind: Type {
-                name: "u32".to_string(),
-            },
-            init_value: "facts".to_string(),
+            kind: Type::named("u32", Span::new(0, 6, 9)),
+            init_value: Some(facts(12, 19)),
+            span: Span::new(0, 0, 20),
         });
     }
 
     #[test]
     fn const_variable_declearation() {
         let mut lexer = Lexer::from_str("hello : \"facts\";\n");
-        assert_eq!(VariableDelclear::new(&mut lexer),VariableDelclear{
+        assert_eq!(VariableDelclear::new(&mut lexer).unwrap(),VariableDelclear{
             is_const: true,
             is_static: false,
             ident: "hello".to_string(),
-            kind: Type {
-                name: "undifiend".to_string(),
-            },
-            init_value: "facts".to_string(),
+            kind: Type::named("undifiend", Span::new(0, 5, 5)),
+            init_value: Some(facts(8, 15)),
+            span: Span::new(0, 0, 16),
         });
         let mut lexer = Lexer::from_str("hello u32 : \"facts\";\n");
-        assert_eq!(VariableDelclear::new(&mut lexer),VariableDelclear{
+        assert_eq!(VariableDelclear::new(&mut lexer).unwrap(),VariableDelclear{
             is_const: true,
             is_static: false,
             ident: "hello".to_string(),
-            kind: Type {
-                name: "u32".to_string(),
-            },
-            init_value: "facts".to_string(),
+            kind: Type::named("u32", Span::new(0, 6, 9)),
+            init_value: Some(facts(12, 19)),
+            span: Span::new(0, 0, 20),
         });
     }
 
     #[test]
     fn static_variable_declearation() {
         let mut lexer = Lexer::from_str("hello u32 :: \"facts\";\n");
-        assert_eq!(VariableDelclear::new(&mut lexer),VariableDelclear{
+        assert_eq!(VariableDelclear::new(&mut lexer).unwrap(),VariableDelclear{
             is_const: true,
             is_static: true,
             ident: "hello".to_string(),
-            kind: Type {
-                name: "u32".to_string(),
+            kind: Type::named("u32", Span::new(0, 6, 9)),
+            init_value: Some(facts(13, 20)),
+            span: Span::new(0, 0, 21),
+        });
+    }
+}
+
+#[cfg(test)]
+mod pest_parser_tests {
+    use crate::ast::{AssignOp, Expr, Node, Stmt};
+    use crate::pest_parser::parse_program;
+    use crate::tests::common::sexpr;
+
+    fn parse_expr(source: &str) -> String {
+        sexpr(&crate::pest_parser::parse_expr(source).unwrap(), |op| format!("{:?}",op))
+    }
+
+    fn static_value(source: &str) -> String {
+        let program = parse_program("INTERNAL", source).unwrap();
+        let Node::VariableDelclear { var } = &program.body[0] else {
+            panic!("expected variable");
+        };
+        sexpr(var.init_value.as_ref().unwrap(), |op| format!("{:?}",op))
+    }
+
+    #[test]
+    fn static_variable_program() {
+        let program = parse_program("INTERNAL", "static name @u32 :: 110 + a - 2;\n").unwrap();
+        assert_eq!(program.body.len(), 1);
+        let Node::VariableDelclear { var } = &program.body[0] else {
+            panic!("expected variable");
+        };
+        assert_eq!(var.ident, "name");
+        assert!(var.is_static && var.is_const);
+        assert_eq!(var.kind.name, "u32");
+    }
+
+    #[test]
+    fn expression_precedence() {
+        assert_eq!(parse_expr("1 + 2 * 3"), "(Add 1 (Multi 2 3))");
+        assert_eq!(parse_expr("a - b - c"), "(Sub (Sub a b) c)");
+        assert_eq!(parse_expr("(a + b) % c"), "(Mod (Add a b) c)");
+        assert_eq!(parse_expr("a | b ^ c & d"), "(Or a (Xor b (And c d)))");
+        assert_eq!(parse_expr("1 << 2 + 3 >> b"), "(Rsh (Lsh 1 (Add 2 3)) b)");
+        assert_eq!(parse_expr("-a * --b / c"), "(Divide (Multi (Sub a) (Sub (Sub b))) c)");
+    }
+
+    #[test]
+    fn number_literals() {
+        assert_eq!(parse_expr("42"), "42");
+        assert_eq!(parse_expr("1.5"), "1.5");
+        assert_eq!(parse_expr("2f"), "2.0");
+        assert_eq!(parse_expr("1011b2 + 17b8"), "(Add 11 15)");
+        assert_eq!(parse_expr("1fb16"), "31");
+        assert_eq!(parse_expr("0ffb16"), "255");
+    }
+
+    #[test]
+    fn static_variable_value() {
+        assert_eq!(static_value("static name @u32 :: 110 + a - 2;\n"), "(Sub (Add 110 a) 2)");
+        let diagnostic = parse_program("main.nmt", "static big @i :: 99999999999999999999;\n").unwrap_err();
+        assert_eq!(diagnostic.code, Some("E0103"));
+        assert_eq!((diagnostic.file_path.as_str(), diagnostic.col), ("main.nmt", 18));
+        assert_eq!(diagnostic.notes, vec![format!("the largest integer literal is {}",i64::MAX)]);
+        // malformed rather than too large, the grammar does not take it
+        let diagnostic = parse_program("main.nmt", "static big @i :: 1e3;\n").unwrap_err();
+        assert_eq!((diagnostic.code, diagnostic.notes), (Some("E0100"), Vec::<String>::new()));
+    }
+
+    #[test]
+    fn function_arguments_and_return_type() {
+        let program = parse_program("INTERNAL", concat!(
+            "func add(a @i, b @f) @i {\n",
+            "    const total @i : a + b;\n",
+            "    mut count = 0;\n",
+            "    count += total;\n",
+            "    while count { # count; break; }\n",
+            "}\n",
+            "func main() { }\n",
+        )).unwrap();
+        let (Node::Func { var: add }, Node::Func { var: main }) = (&program.body[0], &program.body[1]) else {
+            panic!("expected functions");
+        };
+        assert_eq!(add.ident, "add");
+        assert_eq!(add.args.len(), 2);
+        assert_eq!(add.args[1].ident, "b");
+        assert_eq!(add.args[1].kind.name, "f");
+        assert_eq!(add.return_type.name, "i");
+        assert_eq!(add.block.len(), 4);
+        assert!(matches!(&add.block[0], Stmt::VariableDelclear { var } if var.is_const && var.kind.name == "i"));
+        assert!(matches!(&add.block[1], Stmt::VariableDelclear { var } if !var.is_const && var.kind.name == "undifiend"));
+        assert!(matches!(&add.block[2], Stmt::Assign { lhs: Expr::Path { ident, .. }, op: AssignOp::AddEq, .. } if ident == "count"));
+        assert!(matches!(&add.block[3], Stmt::While { body, .. } if body.len() == 2));
+        assert_eq!(main.return_type.name, "void");
+    }
+
+    #[test]
+    fn pest_rejects_break_outside_loop() {
+        let diagnostic = parse_program("main.nmt", "func main() {\n    if a { break; }\n}\n").unwrap_err();
+        assert_eq!(diagnostic.code, Some("E0105"));
+        assert_eq!(diagnostic.file_path, "main.nmt");
+        assert_eq!((diagnostic.line, diagnostic.col), (2, 12));
+    }
+}
+
+#[cfg(test)]
+mod lexer_tests {
+    use crate::lexer::{Lexer, LexErrorKind, TToken, expect_token};
+
+    #[test]
+    fn literal_escapes() {
+        let mut lexer = Lexer::from_str("'\\n' \"a\\\"b\\\\\"");
+        let token = lexer.next_token().unwrap();
+        assert_eq!(token.ttype, TToken::CharLiteral);
+        assert_eq!(token.literal, b"\n");
+        let token = lexer.next_token().unwrap();
+        assert_eq!(token.ttype, TToken::StringLiteral);
+        assert_eq!(token.get_literal_string(), "a\"b\\");
+        assert_eq!(lexer.next_token().unwrap().ttype, TToken::EOF);
+    }
+
+    #[test]
+    fn lex_errors() {
+        let kind = |source: &str| Lexer::from_str(source).next_token().unwrap_err().kind;
+        assert_eq!(kind("''"), LexErrorKind::EmptyChar);
+        assert_eq!(kind("'ab'"), LexErrorKind::UnsupportedChar);
+        assert_eq!(kind("'\\q'"), LexErrorKind::UnknownEscape(b'q'));
+        assert_eq!(kind("\"facts"), LexErrorKind::UnterminatedString);
+        assert_eq!(kind("\"fa\ncts\""), LexErrorKind::UnterminatedString);
+        assert_eq!(kind("\"\\"), LexErrorKind::UnfinishedEscape);
+        assert_eq!(kind("~"), LexErrorKind::UnexpectedByte(b'~'));
+    }
+
+    #[test]
+    fn lexer_continues_after_error() {
+        let mut lexer = Lexer::from_str("~ hello");
+        let err = lexer.next_token().unwrap_err();
+        assert_eq!(err.loc, ("INTERNAL".to_string(), 1, 1));
+        assert_eq!(lexer.next_token().unwrap().ttype, TToken::Identifier);
+    }
+
+    #[test]
+    fn unexpected_token() {
+        let mut lexer = Lexer::from_str("hello ;");
+        let err = expect_token(&mut lexer, vec![TToken::Number]).unwrap_err();
+        assert_eq!(err.kind, LexErrorKind::UnexpectedToken {
+            expected: vec![TToken::Number],
+            found: TToken::Identifier,
+        });
+        expect_token(&mut lexer, vec![TToken::SEMICOLON]).unwrap();
+        let err = expect_token(&mut lexer, vec![TToken::SEMICOLON]).unwrap_err();
+        assert_eq!(err.kind, LexErrorKind::UnexpectedEof { expected: vec![TToken::SEMICOLON] });
+    }
+}
+
+#[cfg(test)]
+mod program_tests {
+    use crate::ast::{Node, Program};
+    use crate::lexer::Lexer;
+
+    #[test]
+    fn reports_every_syntax_error() {
+        let mut lexer = Lexer::from_str(concat!(
+            "@a u32 = ;\n",
+            "@b u32 5;\n",
+            "fun 1() u32 {}\n",
+            "~\n",
+            "@c : 4;\n",
+            "fun main() u32 {}\n",
+            "@d : ;\n",
+        ));
+        let program = Program::new(&mut lexer);
+        assert!(program.has_errors());
+        let lines: Vec<usize> = program.diagnostics.iter().map(|d| d.line).collect();
+        assert_eq!(lines, vec![1, 2, 3, 4, 7]);
+        assert_eq!(program.body.len(), 2);
+        assert!(matches!(&program.body[0], Node::VariableDelclear { var } if var.ident == "c"));
+        assert!(matches!(&program.body[1], Node::Func { var } if var.ident == "main"));
+    }
+
+    #[test]
+    fn clean_program_has_no_diagnostics() {
+        let mut lexer = Lexer::from_str("@a u32 :: 1;\nfun main() u32 {}\n");
+        let program = Program::new(&mut lexer);
+        assert!(!program.has_errors());
+        assert_eq!(program.body.len(), 2);
+    }
+}
+
+#[cfg(test)]
+mod diagnostics_tests {
+    use crate::ast::Program;
+    use crate::diagnostics::Diagnostic;
+    use crate::lexer::Lexer;
+    use crate::pest_parser::parse_program;
+    use crate::span::{SourceMap, Span};
+
+    #[test]
+    fn render_lex_error() {
+        let source = "@a : 1;\n@b : '';\n";
+        let mut lexer = Lexer::from_str(source);
+        for _ in 0..8 {
+            lexer.next_token().unwrap();
+        }
+        let diagnostic = Diagnostic::from(lexer.next_token().unwrap_err());
+        assert_eq!(diagnostic.render(source, false), concat!(
+            "error[E0003]: char literal can not be empty\n",
+            " --> INTERNAL:2:6\n",
+            "  |\n",
+            "2 | @b : '';\n",
+            "  |      ^^\n",
+        ));
+    }
+
+    #[test]
+    fn render_notes_and_helps() {
+        let source = "fun main() u32 {\n\tx = 1;\n}\n";
+        let diagnostic = Diagnostic::new("unused value", "main.nmt", 2, 2)
+            .warning()
+            .with_span(Span::new(0, 18, 23))
+            .with_note("assigned here")
+            .with_help("remove the assignment");
+        assert_eq!(diagnostic.render(source, false), concat!(
+            "warning: unused value\n",
+            " --> main.nmt:2:2\n",
+            "  |\n",
+            "2 | \tx = 1;\n",
+            "  | \t^^^^^\n",
+            "  = note: assigned here\n",
+            "  = help: remove the assignment\n",
+        ));
+        assert!(diagnostic.render(source, true).contains("\x1b[1;33mwarning\x1b[0m"));
+    }
+
+    #[test]
+    fn columns_count_chars() {
+        let source = "@s = \"héllo wörld\"; # nope;\n";
+        let program = Program::new(&mut Lexer::from_str(source));
+        assert_eq!(program.diagnostics[0].col, 21);
+        assert!(program.diagnostics[0].render(source, false).contains(concat!(
+            "1 | @s = \"héllo wörld\"; # nope;\n",
+            "  |                     ^\n",
+        )));
+
+        let mut sources = SourceMap::new();
+        sources.add("main.nmt", source);
+        let start = source.find('#').unwrap();
+        assert_eq!(sources.line_col(Span::new(0, start, start + 1)), Some((1, 21)));
+        let literal = Span::new(0, 5, source.find(';').unwrap());
+        let (line, col) = sources.line_col(literal).unwrap();
+        assert_eq!(Diagnostic::new("a string", "main.nmt", line, col).with_span(literal).render(source, false), concat!(
+            "error: a string\n",
+            " --> main.nmt:1:6\n",
+            "  |\n",
+            "1 | @s = \"héllo wörld\"; # nope;\n",
+            "  |      ^^^^^^^^^^^^^\n",
+        ));
+    }
+
+    #[test]
+    fn pest_errors_become_diagnostics() {
+        let diagnostic = parse_program("main.nmt", "static name @u32 110;\n").unwrap_err();
+        assert_eq!(diagnostic.file_path, "main.nmt");
+        assert_eq!(diagnostic.line, 1);
+        assert_eq!(diagnostic.code, Some("E0100"));
+    }
+}
+
+#[cfg(test)]
+mod span_tests {
+    use crate::ast::{Node, Program};
+    use crate::frontend::cross_check;
+    use crate::lexer::{Lexer, TToken};
+    use crate::span::{SourceMap, Span};
+
+    #[test]
+    fn token_spans() {
+        let mut sources = SourceMap::new();
+        sources.add("a.nmt", "");
+        let id = sources.add("b.nmt", "@total u32 =\n  \"facts\";");
+        let mut lexer = Lexer::from_str(&sources.get(id).unwrap().source).with_file_id(id);
+        let spans: Vec<Span> = std::iter::from_fn(|| {
+            let token = lexer.next_token().unwrap();
+            (token.ttype != TToken::EOF).then_some(token.span)
+        }).collect();
+        assert_eq!(spans, vec![
+            Span::new(1, 0, 1), Span::new(1, 1, 6), Span::new(1, 7, 10),
+            Span::new(1, 11, 12), Span::new(1, 15, 22), Span::new(1, 22, 23),
+        ]);
+        assert_eq!(sources.snippet(spans[4]), Some("\"facts\""));
+        assert_eq!(sources.line_col(spans[4]), Some((2, 3)));
+    }
+
+    #[test]
+    fn node_spans() {
+        let source = concat!(
+            "include \"m.nmt\";\n@a u32 = 1;\nstatic b u32 :: 2;\nstruct P { x int }\nenum E { A }\n",
+            "fun main(x u32) u32 {\n    const c : 3;\n}\n",
+        );
+        let program = Program::new(&mut Lexer::from_str(source));
+        assert_eq!(program, cross_check("main.nmt", source).unwrap());
+        let spans: Vec<&str> = program.body.iter().map(|node| &source[node.span().start..node.span().end]).collect();
+        assert_eq!(spans, vec![
+            "include \"m.nmt\";", "@a u32 = 1;", "static b u32 :: 2;", "struct P { x int }", "enum E { A }",
+            "fun main(x u32) u32 {\n    const c : 3;\n}",
+        ]);
+        let Node::Func { var } = &program.body[5] else { panic!("expected a function") };
+        assert_eq!(&source[var.block[0].span().start..var.block[0].span().end], "const c : 3;");
+    }
+}
+
+#[cfg(test)]
+mod expr_tests {
+    use crate::lexer::{Lexer, TToken};
+    use crate::parser::expr::get_expr;
+    use crate::tests::common::sexpr;
+
+    fn parse(source: &str) -> String {
+        sexpr(&get_expr(&mut Lexer::from_str(source)).unwrap(), |op| op.to_string())
+    }
+
+    #[test]
+    fn arithmetic_precedence() {
+        assert_eq!(parse("1 + 2 * 3"), "(+ 1 (* 2 3))");
+        assert_eq!(parse("1 - 2 - 3"), "(- (- 1 2) 3)");
+        assert_eq!(parse("a / b % c * d"), "(* (% (/ a b) c) d)");
+        assert_eq!(parse("(1 + 2) * 3"), "(* (+ 1 2) 3)");
+    }
+
+    #[test]
+    fn unary_operators() {
+        assert_eq!(parse("-a * +b"), "(* (- a) (+ b))");
+        assert_eq!(parse("--1"), "(- (- 1))");
+        assert_eq!(parse("!a && b"), "(&& (! a) b)");
+    }
+
+    #[test]
+    fn bitwise_relational_and_logical() {
+        assert_eq!(parse("a | b ^ c & d"), "(| a (^ b (& c d)))");
+        assert_eq!(parse("1 << 2 + 3"), "(<< 1 (+ 2 3))");
+        assert_eq!(parse("a + 1 < b >> 2"), "(< (+ a 1) (>> b 2))");
+        assert_eq!(parse("a == b || c != d && e >= f"), "(|| (== a b) (&& (!= c d) (>= e f)))");
+        assert_eq!(parse("a <= b == c"), "(== (<= a b) c)");
+    }
+
+    #[test]
+    fn expression_stops_before_unknown_token() {
+        let mut lexer = Lexer::from_str("a + 1; b");
+        let expr = get_expr(&mut lexer).unwrap();
+        assert_eq!((expr.span().start, expr.span().end), (0, 5));
+        assert_eq!(lexer.next_token().unwrap().ttype, TToken::SEMICOLON);
+    }
+
+    #[test]
+    fn variant_paths_need_touching_colons() {
+        assert_eq!(parse("Shape::Circle(1.0 * r)"), "(Shape::Circle (* 1.0 r))");
+        let err = get_expr(&mut Lexer::from_str("Shape: :Empty")).unwrap_err();
+        assert_eq!((err.code, err.message.as_str(), err.col), (Some("E0100"), "expected `::` found `: :`", 8));
+    }
+
+    #[test]
+    fn expression_errors() {
+        let err = get_expr(&mut Lexer::from_str("1 + ;")).unwrap_err();
+        assert_eq!(err.code, Some("E0102"));
+        assert_eq!(err.col, 5);
+        let err = get_expr(&mut Lexer::from_str("(1 + 2")).unwrap_err();
+        assert_eq!(err.code, Some("E0101"));
+        let err = get_expr(&mut Lexer::from_str("99999999999999999999")).unwrap_err();
+        assert_eq!((err.code, err.notes), (Some("E0103"), vec![format!("the largest integer literal is {}",i64::MAX)]));
+        let err = get_expr(&mut Lexer::from_str("1ffffffffffffffffffb16")).unwrap_err();
+        assert_eq!(err.notes.len(), 1);
+        // malformed rather than too large
+        let err = get_expr(&mut Lexer::from_str("1e3")).unwrap_err();
+        assert_eq!((err.code, err.notes), (Some("E0103"), Vec::<String>::new()));
+        let err = get_expr(&mut Lexer::from_str("12b2")).unwrap_err();
+        assert_eq!((err.code, err.notes), (Some("E0103"), Vec::<String>::new()));
+    }
+}
+
+#[cfg(test)]
+mod stmt_tests {
+    use crate::ast::{AssignOp, Expr, Func, Node, Program, Stmt};
+    use crate::lexer::Lexer;
+
+    fn func(source: &str) -> Func {
+        Func::new(&mut Lexer::from_str(source)).unwrap()
+    }
+
+    #[test]
+    fn function_body_statements() {
+        let func = func(concat!(
+            "main(a u32) u32 {\n",
+            "    @total u32 = a * 2;\n",
+            "    @limit : 10;\n",
+            "    total += limit;\n",
+            "    { total = total - 1; }\n",
+            "    total;\n",
+            "    return total;\n",
+            "}\n",
+        ));
+        assert_eq!(func.block.len(), 6);
+        assert!(matches!(&func.block[0], Stmt::VariableDelclear { var } if var.ident == "total" && !var.is_const));
+        assert!(matches!(&func.block[1], Stmt::VariableDelclear { var } if var.ident == "limit" && var.is_const));
+        assert!(matches!(&func.block[2], Stmt::Assign { lhs: Expr::Path { ident, .. }, op: AssignOp::AddEq, .. } if ident == "total"));
+        assert!(matches!(&func.block[3], Stmt::Block { body, .. } if body.len() == 1));
+        assert!(matches!(&func.block[4], Stmt::Expr { expr: Expr::Path { .. }, .. }));
+        assert!(matches!(&func.block[5], Stmt::Return { value: Some(_), .. }));
+    }
+
+    #[test]
+    fn every_compound_assignment() {
+        let func = func("f() u32 { a = 1; a += 1; a -= 1; a *= 1; a /= 1; a %= 1; a &= 1; a |= 1; a ^= 1; return; }");
+        let ops: Vec<AssignOp> = func.block.iter().filter_map(|stmt| match stmt {
+            Stmt::Assign { op, .. } => Some(*op),
+            _ => None,
+        }).collect();
+        assert_eq!(ops, vec![
+            AssignOp::Eq, AssignOp::AddEq, AssignOp::SubEq, AssignOp::MultiEq, AssignOp::DivideEq,
+            AssignOp::ModEq, AssignOp::AndEq, AssignOp::OrEq, AssignOp::XorEq,
+        ]);
+        assert!(matches!(func.block.last(), Some(Stmt::Return { value: None, .. })));
+    }
+
+    #[test]
+    fn recovers_inside_function_body() {
+        let mut lexer = Lexer::from_str(concat!(
+            "main() u32 {\n",
+            "    @a = ;\n",
+            "    1 + 2 = a;\n",
+            "    @b = 2;\n",
+            "    b = * 3;\n",
+            "    return b;\n",
+            "}\n",
+        ));
+        let func = Func::new(&mut lexer).unwrap();
+        let lines: Vec<usize> = lexer.take_diagnostics().iter().map(|d| d.line).collect();
+        assert_eq!(lines, vec![2, 3, 5]);
+        assert_eq!(func.block.len(), 2);
+
+        // a missing `;` leaves the `}` to close the block, and a missing `}`
+        // stops at the next function
+        let program = Program::new(&mut Lexer::from_str(concat!(
+            "fun main() {\n",
+            "    @x = 1\n",
+            "}\n",
+            "fun other() {\n",
+            "    # y;\n",
+            "}\n",
+            "fun last() {\n",
+            "    # 1;\n",
+            "fun after() {\n",
+            "}\n",
+        )));
+        let errors: Vec<_> = program.diagnostics.iter().map(|d| (d.code, d.line, d.col)).collect();
+        assert_eq!(errors, vec![(Some("E0100"), 3, 1), (Some("E0101"), 9, 1)]);
+        let names: Vec<_> = program.body.iter().filter_map(|node| match node {
+            Node::Func { var } => Some(var.ident.as_str()),
+            _ => None,
+        }).collect();
+        assert_eq!(names, vec!["main", "other", "after"]);
+    }
+}
+
+#[cfg(test)]
+mod control_flow_tests {
+    use pest::Parser;
+
+    use crate::ast::{Expr, Func, Node, Program, Stmt};
+    use crate::lexer::Lexer;
+    use crate::pest_parser::{NemetParser, Rule};
+
+    #[test]
+    fn if_else_chain() {
+        let func = Func::new(&mut Lexer::from_str(concat!(
+            "main(a u32) u32 {\n",
+            "    if a < 1 { return 0; } else if a < 10 { return 1; } else { return 2; }\n",
+            "}\n",
+        ))).unwrap();
+        let Stmt::If { then_block, else_block: Some(else_if), .. } = &func.block[0] else {
+            panic!("expected if statement");
+        };
+        assert_eq!(then_block.len(), 1);
+        let Stmt::If { else_block: Some(else_block), .. } = else_if.as_ref() else {
+            panic!("expected else if");
+        };
+        assert!(matches!(else_block.as_ref(), Stmt::Block { body, .. } if body.len() == 1));
+    }
+
+    #[test]
+    fn if_condition_is_not_a_struct_literal() {
+        // `name {` after `if` starts the block
+        let program = Program::new(&mut Lexer::from_source("main.nmt", "fun main() { if p { } }"));
+        assert!(program.diagnostics.is_empty(), "{:?}", program.diagnostics);
+        let Node::Func { var } = &program.body[0] else { panic!("expected a function") };
+        assert!(matches!(&var.block[0], Stmt::If { cond: Expr::Path { ident, .. }, then_block, .. } if ident == "p" && then_block.is_empty()));
+    }
+
+    #[test]
+    fn loops() {
+        let func = Func::new(&mut Lexer::from_str(concat!(
+            "main(xs u32) u32 {\n",
+            "    while 1 { break; }\n",
+            "    loop { if 1 { continue; } break; }\n",
+            "    for i 0 to 10 + 1 { }\n",
+            "    for x in xs { total += x; }\n",
+            "}\n",
+        ))).unwrap();
+        assert!(matches!(&func.block[0], Stmt::While { body, .. } if matches!(body[0], Stmt::Break { .. })));
+        assert!(matches!(&func.block[1], Stmt::Loop { body, .. } if body.len() == 2));
+        assert!(matches!(&func.block[2], Stmt::ForRange { ident, .. } if ident == "i"));
+        assert!(matches!(&func.block[3], Stmt::ForIn { ident, body, .. } if ident == "x" && body.len() == 1));
+    }
+
+    #[test]
+    fn parser_rejects_break_outside_loop() {
+        let mut lexer = Lexer::from_str("main() u32 {\n    if 1 { break; }\n    loop { }\n    continue;\n}\n");
+        Func::new(&mut lexer).unwrap();
+        let diagnostics = lexer.take_diagnostics();
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!((diagnostics[0].line, diagnostics[0].col), (2, 12));
+        assert_eq!(diagnostics[0].code, Some("E0105"));
+        assert_eq!((diagnostics[1].line, diagnostics[1].col), (4, 5));
+    }
+
+    #[test]
+    fn pest_control_flow_rules() {
+        let source = "{ if a { break; } else if b { } else { continue; } while a { } loop { } for i 0 to 10 { } for x in xs { } }";
+        let block = NemetParser::parse(Rule::block, source).unwrap().next().unwrap();
+        let rules: Vec<Rule> = block.into_inner().map(|pair| pair.as_rule()).collect();
+        assert_eq!(rules, vec![Rule::if_stmt, Rule::while_stmt, Rule::loop_stmt, Rule::for_range_stmt, Rule::for_in_stmt]);
+    }
+}
+
+#[cfg(test)]
+mod cross_check_tests {
+    use crate::ast::{Expr, Literal, Node, Pattern, Stmt};
+    use crate::frontend::{Frontend, cross_check};
+    use crate::span::Span;
+
+    // every construct both front-ends know about
+    const PROGRAM: &str = concat!(
+        "// shared syntax\n",
+        "@limit u32 :: 10;\n",
+        "static name @u32 :: 110 + 2;\n",
+        "fun add(a u32, b @u32) u32 {\n",
+        "    @total = a + b * -2;\n",
+        "    @step : 1011b2 + 0ffb16;\n",
+        "    const ratio @f : 1.5f;\n",
+        "    mut count u32;\n",
+        "    total += step;\n",
+        "    if a < b && !(a == 0) { return a; } else if b { } else { # \"neg\\n\"; }\n",
+        "    while total >= 1 { total -= 1; break; }\n",
+        "    loop { continue; }\n",
+        "    for i 0 to limit { # i; }\n",
+        "    for x in total { count |= x ^ 'c' << 2; }\n",
+        "    { total; }\n",
+        "    return total;\n",
+        "}\n",
+        "func main() {\n",
+        "    returned;\n",
+        "    return;\n",
+        "}",
+    );
+
+    #[test]
+    fn front_ends_build_the_same_tree() {
+        let program = cross_check("main.nmt", PROGRAM).unwrap();
+        assert_eq!(program.body.len(), 4);
+        let Node::Func { var: add } = &program.body[2] else {
+            panic!("expected function");
+        };
+        assert_eq!(add.block.len(), 12);
+        assert!(matches!(&add.block[9], Stmt::ForIn { .. }));
+        assert_eq!(Frontend::Pest.parse("main.nmt", PROGRAM), Frontend::Manual.parse("main.nmt", PROGRAM));
+    }
+
+    #[test]
+    fn literals_agree() {
+        let program = cross_check("main.nmt", "@a : 17b8;\n@b : 2f;\n@c : '\\'';\n").unwrap();
+        let values: Vec<Literal> = program.body.iter().map(|node| match node {
+            Node::VariableDelclear { var } => match var.init_value {
+                Some(crate::ast::Expr::Literal { ref value, .. }) => value.clone(),
+                _ => panic!("expected literal"),
             },
-            init_value: "facts".to_string(),
+            _ => panic!("expected variable"),
+        }).collect();
+        assert_eq!(values, vec![Literal::Int(15), Literal::Float(2.0), Literal::Char('\'')]);
+    }
+
+    #[test]
+    fn struct_literals_and_field_places_agree() {
+        let source = concat!(
+            "struct Point { x int, y int, }\n",
+            "fun main() {\n",
+            "    @p = Point { x: 1, y: 2 };\n",
+            "    p.x += p.y;\n",
+            "    if p.x == (Point { x: 3, y: 2 }).x { # p; }\n",
+            "}\n",
+        );
+        let program = cross_check("main.nmt", source).unwrap();
+        let Node::Struct { var } = &program.body[0] else { panic!("expected a struct") };
+        assert_eq!((var.ident.as_str(), var.fields.len(), var.span), ("Point", 2, Span::new(0, 0, 30)));
+        let Node::Func { var } = &program.body[1] else { panic!("expected a function") };
+        let Stmt::Assign { lhs: Expr::Field { base, ident, span }, .. } = &var.block[1] else { panic!("expected a field assignment") };
+        assert_eq!((ident.as_str(), *span), ("x", Span::new(0, 79, 82)));
+        assert!(matches!(base.as_ref(), Expr::Path { ident, .. } if ident == "p"));
+    }
+
+    #[test]
+    fn enums_and_match_arms_agree() {
+        let source = concat!(
+            "enum Shape { Empty, Circle(float), }\n",
+            "fun main() {\n",
+            "    @s = Shape::Circle(1.0);\n",
+            "    match s { Shape::Circle(r) => { # r; } _ => {} }\n",
+            "    # match s { Shape::Empty => 0, _ => 1, };\n",
+            "}\n",
+        );
+        let program = cross_check("main.nmt", source).unwrap();
+        let Node::Enum { var } = &program.body[0] else { panic!("expected an enum") };
+        assert_eq!((var.ident.as_str(), var.variants.len(), var.span), ("Shape", 2, Span::new(0, 0, 36)));
+        assert_eq!(var.variants[1].span, Span::new(0, 20, 33));
+        let Node::Func { var } = &program.body[1] else { panic!("expected a function") };
+        let Stmt::Match { arms, span, .. } = &var.block[1] else { panic!("expected a match") };
+        assert_eq!(*span, Span::new(0, 83, 131));
+        assert!(matches!(&arms[0].pattern, Pattern::Variant { binding: Some(binding), .. } if binding == "r"));
+        assert!(matches!(arms[1].pattern, Pattern::Wildcard { .. }));
+        assert!(matches!(&var.block[2], Stmt::DebugPrint { expr: Expr::Match { arms, .. }, .. } if arms.len() == 2));
+    }
+
+    #[test]
+    fn array_types_and_index_places_agree() {
+        let source = concat!(
+            "@grid [[int; 2]; 3];\n",
+            "fun main() {\n",
+            "    @xs @[i] = [1, 2, 3,];\n",
+            "    xs[xs[0] + 1] = 4;\n",
+            "    # grid[1][0];\n",
+            "}\n",
+        );
+        let program = cross_check("main.nmt", source).unwrap();
+        let Node::VariableDelclear { var } = &program.body[0] else { panic!("expected a variable") };
+        assert_eq!((var.kind.name.as_str(), var.kind.len, var.kind.span), ("[[int; 2]; 3]", Some(3), Span::new(0, 6, 19)));
+        assert_eq!(var.kind.element.as_ref().unwrap().name, "[int; 2]");
+        let Node::Func { var } = &program.body[1] else { panic!("expected a function") };
+        let Stmt::VariableDelclear { var: xs } = &var.block[0] else { panic!("expected a variable") };
+        assert_eq!((xs.kind.name.as_str(), xs.kind.len), ("[i]", None));
+        assert!(matches!(&xs.init_value, Some(Expr::Array { elements, span }) if elements.len() == 3 && *span == Span::new(0, 49, 59)));
+        let Stmt::Assign { lhs: Expr::Index { base, index, span }, .. } = &var.block[1] else { panic!("expected an element assignment") };
+        assert_eq!(*span, Span::new(0, 65, 78));
+        assert!(matches!(base.as_ref(), Expr::Path { ident, .. } if ident == "xs"));
+        assert!(matches!(index.as_ref(), Expr::Binary { .. }));
+    }
+
+    #[test]
+    fn array_lengths_have_to_be_integers() {
+        for frontend in [Frontend::Manual, Frontend::Pest] {
+            let diagnostics = frontend.parse("main.nmt", "@xs [i; 1.5];").diagnostics;
+            assert_eq!((diagnostics[0].code, diagnostics[0].message.as_str()), (Some("E0103"), "invalid array length `1.5`"));
+            assert_eq!(diagnostics[0].span, Span::new(0, 8, 11));
+        }
+    }
+
+    #[test]
+    fn calls_and_qualified_calls_agree() {
+        let source = concat!(
+            "fun main() {\n",
+            "    # add(1, twice(2)) * 3;\n",
+            "    util.reset();\n",
+            "    if ready() { }\n",
+            "}\n",
+        );
+        let program = cross_check("main.nmt", source).unwrap();
+        let Node::Func { var } = &program.body[0] else { panic!("expected a function") };
+        let Stmt::DebugPrint { expr: Expr::Binary { left, .. }, .. } = &var.block[0] else { panic!("expected a print") };
+        let Expr::Call { ident, args, span } = left.as_ref() else { panic!("expected a call") };
+        assert_eq!((ident.as_str(), args.len(), *span), ("add", 2, Span::new(0, 19, 35)));
+        assert!(matches!(&args[1], Expr::Call { ident, args, .. } if ident == "twice" && args.len() == 1));
+        let Stmt::Expr { expr: Expr::Call { ident, args, span }, .. } = &var.block[1] else { panic!("expected a call") };
+        assert_eq!((ident.as_str(), args.len(), *span), ("util.reset", 0, Span::new(0, 45, 57)));
+        assert!(matches!(&var.block[2], Stmt::If { cond: Expr::Call { ident, .. }, .. } if ident == "ready"));
+    }
+
+    #[test]
+    fn both_reject_the_same_programs() {
+        for source in [
+            "fun main() { static x = 1; }",
+            "fun main() { const x = 1; }",
+            "fun main() { mut x : 1; }",
+            "fun main() { @x : : 1; }",
+            "fun main() { @if = 1; }",
+            "fun main() { 1 + 2 = a; }",
+            "fun main(a u32,) { }",
+            "fun main() { # \"\\q\"; }",
+            "fun main() { # 'é'; }",
+            "fun main() { @a = 1; (a) = 2; }",
+            "fun main() { @a = [1]; (a)[0] += 2; }",
+            "@x = 99999999999999999999;",
+        ] {
+            let diagnostics = cross_check("main.nmt", source).unwrap_err();
+            assert!(diagnostics.iter().all(|d| d.code != Some("E0106")), "{}: {:?}", source, diagnostics);
+        }
+    }
+}
+
+#[cfg(test)]
+mod cli_tests {
+    use crate::cli::{Command, EXIT_ERROR, EXIT_USAGE, Options, run};
+    use crate::frontend::Frontend;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    // runs a command on a temporary file, returns (exit code, stdout, stderr)
+    fn nemet(line: &str, name: &str, source: &str) -> (i32, String, String) {
+        let path = std::env::temp_dir().join(format!("nemet_cli_{}_{}.nmt", std::process::id(), name));
+        std::fs::write(&path, source).unwrap();
+        let options = Options::parse(args(&format!("{} {}", line, path.display()))).unwrap();
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let code = run(&options, &mut out, &mut err, false);
+        std::fs::remove_file(&path).unwrap();
+        (code, String::from_utf8(out).unwrap(), String::from_utf8(err).unwrap())
+    }
+
+    #[test]
+    fn parses_arguments() {
+        let options = Options::parse(args("parse --frontend=pest main.nmt")).unwrap();
+        assert_eq!(options, Options {
+            command: Command::Parse,
+            file_path: "main.nmt".to_string(),
+            frontend: Frontend::Pest,
+            cross_check: false,
         });
+        assert!(Options::parse(args("check --frontend=both main.nmt")).unwrap().cross_check);
+        assert_eq!(Options::parse(args("compile main.nmt")).unwrap_err(), "unknown command `compile`");
+        assert_eq!(Options::parse(args("lex --frontend=yacc main.nmt")).unwrap_err(), "unknown front-end `yacc`");
+        assert_eq!(Options::parse(args("lex")).unwrap_err(), "missing file");
+        assert_eq!(Options::parse(args("lex a.nmt b.nmt")).unwrap_err(), "unexpected argument `b.nmt`");
+        assert_eq!(Options::parse(args("repl")).unwrap().command, Command::Repl);
+        assert_eq!(Options::parse(args("repl main.nmt")).unwrap_err(), "unexpected argument `main.nmt`");
+    }
+
+    #[test]
+    fn lex_and_parse_output() {
+        let (code, out, _) = nemet("lex", "lex", "@a : 1;");
+        assert_eq!(code, 0);
+        assert_eq!(out.lines().count(), 5);
+        assert!(out.lines().next().unwrap().starts_with("Token (ATSIGN) \"@\""));
+        let (code, out, _) = nemet("parse --frontend=both", "parse", "fun main() { # 1; }");
+        assert_eq!(code, 0);
+        assert!(out.contains("DebugPrint"));
+    }
+
+    #[test]
+    fn exit_codes() {
+        assert_eq!(nemet("check", "ok", "fun main() { }").0, 0);
+        let (code, _, err) = nemet("check --frontend=pest", "bad", "fun main() { @a = ; }");
+        assert_eq!(code, EXIT_ERROR);
+        assert!(err.starts_with("error[E0100]"));
+        assert_eq!(nemet("lex", "lex_error", "~").0, EXIT_ERROR);
+        let options = Options::parse(args("check /nonexistent/main.nmt")).unwrap();
+        assert_eq!(run(&options, &mut Vec::new(), &mut Vec::new(), false), EXIT_USAGE);
+    }
+
+    #[test]
+    fn runs_main() {
+        let (code, out, _) = nemet("run --frontend=pest", "run", "fun main() { for i 0 to 3 { # i * i; } }");
+        assert_eq!((code, out.as_str()), (0, "0\n1\n4\n"));
+        let (code, out, err) = nemet("run", "run_error", "fun main() { # 1; @a = 0; # 1 % a; }");
+        assert_eq!((code, out.as_str()), (EXIT_ERROR, "1\n"));
+        assert!(err.starts_with("error[E0500]: division by zero"));
+        assert_eq!(nemet("run", "no_main", "fun start() { }").0, EXIT_ERROR);
+    }
+
+    #[test]
+    fn vm_writes_and_runs_bytecode() {
+        let (code, out, _) = nemet("vm", "vm", "fun main() { # 6 * 7; }");
+        assert_eq!((code, out.as_str()), (0, "42\n"));
+        let path = std::env::temp_dir().join(format!("nemet_cli_{}_vm.nbc", std::process::id()));
+        let options = Options::parse(args(&format!("vm {}", path.display()))).unwrap();
+        let mut out = Vec::new();
+        assert_eq!(run(&options, &mut out, &mut Vec::new(), false), 0);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(out, b"42\n");
+    }
+
+    #[test]
+    fn dumps_ir() {
+        let (code, out, _) = nemet("ir", "ir", "fun main() { # 1 + 2; }");
+        assert_eq!((code, out.as_str()), (0, "\nfunc main() void {\nbb0:\n  %0 = add int 1, 2\n  print %0\n  ret\n}\n"));
+    }
+}
+
+#[cfg(test)]
+mod resolve_tests {
+    use crate::ast::{Node, Program, Stmt};
+    use crate::diagnostics::Severity;
+    use crate::lexer::Lexer;
+    use crate::semantic::resolve::{Resolution, SymbolKind, resolve};
+    use crate::span::SourceMap;
+
+    fn check(source: &str) -> (Program, Resolution) {
+        let mut sources = SourceMap::new();
+        sources.add("main.nmt", source);
+        let program = Program::new(&mut Lexer::from_source("main.nmt", source));
+        assert!(!program.has_errors(), "{:?}", program.diagnostics);
+        let resolution = resolve(&program, &sources);
+        (program, resolution)
+    }
+
+    #[test]
+    fn annotates_every_use() {
+        let source = concat!(
+            "@limit u32 :: 10;\n",
+            "fun main(a u32) u32 {\n",
+            "    @total = a + limit;\n",
+            "    for i 0 to limit { total += i; }\n",
+            "    return helper;\n",
+            "}\n",
+            "fun helper() { }\n",
+        );
+        let (program, resolution) = check(source);
+        assert!(resolution.diagnostics.is_empty(), "{:?}", resolution.diagnostics);
+        let uses: Vec<(&str, SymbolKind)> = resolution.uses.iter()
+            .map(|(span, id)| (&source[span.start..span.end], resolution.symbol(*id).kind))
+            .collect();
+        assert_eq!(uses.len(), 6);
+        assert!(uses.contains(&("a", SymbolKind::Arg)));
+        assert!(uses.contains(&("i", SymbolKind::LoopVar)));
+        assert!(uses.contains(&("helper", SymbolKind::Func)));
+        let Node::Func { var: main } = &program.body[1] else { panic!("expected function") };
+        let Stmt::VariableDelclear { var: total } = &main.block[0] else { panic!("expected variable") };
+        let id = resolution.defs[&total.span];
+        assert_eq!(resolution.uses.values().filter(|used| **used == id).count(), 1);
+        assert!(resolution.symbol(resolution.defs[&program.body[0].span()]).is_static);
+    }
+
+    #[test]
+    fn reports_undefined_and_duplicate_names() {
+        let (_, resolution) = check(concat!(
+            "@a u32 = 1;\n",
+            "fun a() { }\n",
+            "fun main(x u32, x u32) {\n",
+            "    @y = z;\n",
+            "    @y = y;\n",
+            "    { @w = 1; }\n",
+            "    w = 2;\n",
+            "}\n",
+        ));
+        let found: Vec<(Option<&str>, usize)> = resolution.diagnostics.iter().map(|d| (d.code, d.line)).collect();
+        assert_eq!(found, vec![
+            (Some("E0201"), 2), (Some("E0201"), 3), (Some("E0200"), 4), (Some("E0201"), 5), (Some("E0200"), 7),
+        ]);
+        assert_eq!(resolution.diagnostics[0].notes, vec!["first defined at 1:1"]);
+        assert!(resolution.has_errors());
+    }
+
+    #[test]
+    fn globals_only_use_the_values_before_them() {
+        let source = concat!(
+            "@a = b + 1;\n",
+            "@b = 1;\n",
+            "@c : d * 2;\n",
+            "@d = e;\n",
+            "@e : 3;\n",
+            "static f int :: g;\n",
+            "static g int :: 4;\n",
+            "fun main() { # later; }\n",
+            "@later = a;\n",
+        );
+        let (_, resolution) = check(source);
+        let found: Vec<(Option<&str>, &str)> = resolution.diagnostics.iter().map(|d| (d.code, &source[d.span.start..d.span.end])).collect();
+        assert_eq!(found, vec![(Some("E0202"), "b"), (Some("E0202"), "d")]);
+        assert_eq!(resolution.diagnostics[0].message, "`b` is used before it has a value");
+        assert_eq!(resolution.diagnostics[0].notes, vec!["`b` is defined at 2:1"]);
+    }
+
+    #[test]
+    fn shadowing_is_a_warning() {
+        let (_, resolution) = check("@n u32 = 1;\nfun main(a u32) {\n    @a = n;\n    if 1 { @n = a; }\n}\n");
+        assert!(!resolution.has_errors());
+        let lines: Vec<usize> = resolution.diagnostics.iter().map(|d| d.line).collect();
+        assert_eq!(lines, vec![3, 4]);
+        assert!(resolution.diagnostics.iter().all(|d| d.severity == Severity::Warning && d.code == Some("W0201")));
+    }
+}
+
+#[cfg(test)]
+mod types_tests {
+    use crate::ast::{Node, Program, Stmt};
+    use crate::lexer::Lexer;
+    use crate::semantic::resolve::resolve;
+    use crate::semantic::types::{Ty, TypeInfo, check};
+    use crate::span::SourceMap;
+
+    fn types(source: &str) -> (Program, TypeInfo) {
+        let mut sources = SourceMap::new();
+        sources.add("main.nmt", source);
+        let program = Program::new(&mut Lexer::from_source("main.nmt", source));
+        assert!(!program.has_errors(), "{:?}", program.diagnostics);
+        let resolution = resolve(&program, &sources);
+        assert!(!resolution.has_errors(), "{:?}", resolution.diagnostics);
+        let info = check(&program, &resolution, &sources);
+        (program, info)
+    }
+
+    fn codes(info: &TypeInfo) -> Vec<(Option<&str>, usize)> {
+        info.diagnostics.iter().map(|d| (d.code, d.line)).collect()
+    }
+
+    #[test]
+    fn infers_declarations_from_their_value() {
+        let (program, info) = types(concat!(
+            "@pi : 3.14;\n",
+            "fun main() bool {\n",
+            "    @c = 'x';\n",
+            "    @big = pi * 2f > 6f;\n",
+            "    return big && c == 'x';\n",
+            "}\n",
+        ));
+        assert!(info.diagnostics.is_empty(), "{:?}", info.diagnostics);
+        let Node::Func { var: main } = &program.body[1] else { panic!("expected function") };
+        let Stmt::Return { value: Some(value), .. } = &main.block[2] else { panic!("expected return") };
+        assert_eq!(info.expr(value), Ty::Bool);
+        let inferred: Vec<Ty> = main.block[..2].iter().map(|stmt| match stmt {
+            Stmt::VariableDelclear { var } => info.expr(var.init_value.as_ref().unwrap()),
+            _ => panic!("expected variable"),
+        }).collect();
+        assert_eq!(inferred, vec![Ty::Char, Ty::Bool]);
+    }
+
+    #[test]
+    fn reports_mismatches_with_spans() {
+        let source = concat!(
+            "@n u32 = \"ten\";\n",
+            "fun main(x float) {\n",
+            "    x += 1;\n",
+            "    @b = !1.5;\n",
+            "    if x { }\n",
+            "    @y u8 = 1;\n",
+            "}\n",
+        );
+        let (_, info) = types(source);
+        assert_eq!(codes(&info), vec![
+            (Some("E0301"), 1), (Some("E0302"), 3), (Some("E0302"), 4), (Some("E0301"), 5), (Some("E0300"), 6),
+        ]);
+        assert_eq!(info.diagnostics[0].message, "mismatched types, expected `int` found `str`");
+        let spans: Vec<&str> = info.diagnostics.iter().map(|d| &source[d.span.start..d.span.end]).collect();
+        assert_eq!(spans, vec!["\"ten\"", "1", "!1.5", "x", "u8"]);
+    }
+
+    #[test]
+    fn infers_globals_used_before_their_definition() {
+        let (_, info) = types("@a = b + 1;\n@b : 2;\n@c = c;\n");
+        assert_eq!(codes(&info), vec![(Some("E0304"), 3)]);
+        assert_eq!(info.symbols.values().filter(|ty| **ty == Ty::Int).count(), 2);
+    }
+
+    #[test]
+    fn reports_struct_misuse_with_spans() {
+        let source = concat!(
+            "struct P { x int, y str }\n",
+            "struct A { b B }\n",
+            "struct B { a A }\n",
+            "fun main() {\n",
+            "    @p = P { x: \"1\", z: 2, x: 3 };\n",
+            "    # p.w;\n",
+            "    # p.x.y;\n",
+            "    # P;\n",
+            "}\n",
+        );
+        let (_, info) = types(source);
+        let found: Vec<(Option<&str>, &str)> = info.diagnostics.iter().map(|d| (d.code, &source[d.span.start..d.span.end])).collect();
+        assert_eq!(found, vec![
+            (Some("E0300"), "struct A { b B }"),
+            (Some("E0301"), "\"1\""),
+            (Some("E0305"), "z"),
+            (Some("E0305"), "x"),
+            (Some("E0305"), "P { x: \"1\", z: 2, x: 3 }"),
+            (Some("E0305"), "w"),
+            (Some("E0305"), "p.x.y"),
+            (Some("E0304"), "P"),
+        ]);
+        assert_eq!(info.diagnostics[4].message, "missing field `y` in `P`");
+        assert_eq!(info.diagnostics[6].message, "`int` has no field `y`");
+    }
+
+    #[test]
+    fn reports_enum_misuse_with_spans() {
+        let source = concat!(
+            "enum Shape { Empty, Circle(float), }\n",
+            "enum Other { A }\n",
+            "enum L { Nil, Cons(L) }\n",
+            "fun main() {\n",
+            "    @a = Shape::Circle;\n",
+            "    @b = Shape::Round;\n",
+            "    match a {\n",
+            "        Shape::Empty(x) => {}\n",
+            "        Other::A => {}\n",
+            "        _ => {}\n",
+            "        Shape::Circle => {}\n",
+            "    }\n",
+            "}\n",
+        );
+        let (_, info) = types(source);
+        let found: Vec<(Option<&str>, &str)> = info.diagnostics.iter().map(|d| (d.code, &source[d.span.start..d.span.end])).collect();
+        assert_eq!(found, vec![
+            (Some("E0300"), "enum L { Nil, Cons(L) }"),
+            (Some("E0306"), "Shape::Circle"),
+            (Some("E0306"), "Shape::Round"),
+            (Some("E0306"), "Shape::Empty(x)"),
+            (Some("E0301"), "Other::A"),
+            (Some("W0306"), "Shape::Circle"),
+        ]);
+        assert_eq!(info.diagnostics[1].message, "`Shape::Circle` needs a `float` value");
+        assert_eq!(info.diagnostics[3].message, "`Shape::Empty` has no value to bind");
+    }
+
+    #[test]
+    fn matches_have_to_cover_every_variant() {
+        let source = "enum Shape { Empty, Circle(float) }\nfun main(a Shape) {\n    match a { Shape::Empty => {} }\n    match 1 { _ => {} }\n}\n";
+        let (_, info) = types(source);
+        assert_eq!(codes(&info), vec![(Some("E0306"), 3), (Some("E0306"), 4)]);
+        assert_eq!(info.diagnostics[0].message, "non-exhaustive match, `Shape::Circle` is not covered");
+        assert_eq!(info.diagnostics[0].helps, vec!["add an arm for every missing variant or a `_` arm"]);
+        assert_eq!(info.diagnostics[1].message, "cannot match on `int`, only on enums");
+    }
+
+    #[test]
+    fn reports_array_misuse_with_spans() {
+        let source = concat!(
+            "struct L { next [L; 1], rest [L] }\n",
+            "fun main() {\n",
+            "    @a [int; 2] = [1, 2, 3];\n",
+            "    @b = [1, true];\n",
+            "    @c = [];\n",
+            "    @s [int] = a;\n",
+            "    # s[a[2]];\n",
+            "    # 5[0];\n",
+            "    @t = \"hi\";\n",
+            "    t[0] = 'a';\n",
+            "    for x in 3 { }\n",
+            "}\n",
+        );
+        let (_, info) = types(source);
+        let found: Vec<(Option<&str>, &str)> = info.diagnostics.iter().map(|d| (d.code, &source[d.span.start..d.span.end])).collect();
+        assert_eq!(found, vec![
+            (Some("E0300"), "struct L { next [L; 1], rest [L] }"),
+            (Some("E0301"), "[1, 2, 3]"),
+            (Some("E0301"), "true"),
+            (Some("E0301"), "@c = [];"),
+            (Some("E0302"), "2"),
+            (Some("E0302"), "5"),
+            (Some("E0302"), "t[0]"),
+            (Some("E0302"), "3"),
+        ]);
+        assert_eq!(info.diagnostics[4].message, "index 2 is out of bounds for `[int; 2]`");
+        assert_eq!(info.diagnostics[6].message, "the chars of a `str` cannot be assigned to");
+    }
+
+    #[test]
+    fn reports_bad_calls_with_spans() {
+        let source = concat!(
+            "@limit = 3;\n",
+            "fun add(a int, b int) int { return a + b; }\n",
+            "fun log(msg str) { # msg; }\n",
+            "fun main() {\n",
+            "    @x = add(1);\n",
+            "    add(1, 2, 3);\n",
+            "    @y float = add(1, 2.5);\n",
+            "    @z = log(\"hi\");\n",
+            "    limit(1);\n",
+            "}\n",
+        );
+        let (_, info) = types(source);
+        let found: Vec<(Option<&str>, &str)> = info.diagnostics.iter().map(|d| (d.code, &source[d.span.start..d.span.end])).collect();
+        assert_eq!(found, vec![
+            (Some("E0307"), "add(1)"),
+            (Some("E0307"), "add(1, 2, 3)"),
+            (Some("E0301"), "2.5"),
+            (Some("E0301"), "add(1, 2.5)"),
+            (Some("E0301"), "log(\"hi\")"),
+            (Some("E0307"), "limit(1)"),
+        ]);
+        assert_eq!(info.diagnostics[1].message, "function `add` takes 2 arguments but 3 were given");
+        assert_eq!(info.diagnostics[4].message, "function `log` does not return a value");
+    }
+
+    #[test]
+    fn checks_return_types() {
+        let (_, info) = types(concat!(
+            "fun a() u32 { return 'c'; }\n",
+            "fun b(x u32) u32 { if x > 1 { return x; } }\n",
+            "fun c(x u32) u32 { loop { if x > 1 { return x; } } }\n",
+            "fun d() { return 1; }\n",
+            "fun e() u32 { return d; }\n",
+        ));
+        assert_eq!(codes(&info), vec![(Some("E0301"), 1), (Some("E0303"), 2), (Some("E0301"), 4), (Some("E0304"), 5)]);
+    }
+}
+
+#[cfg(test)]
+mod consts_tests {
+    use crate::ast::Program;
+    use crate::lexer::Lexer;
+    use crate::semantic::consts::{Consts, check};
+    use crate::semantic::resolve::{Resolution, resolve};
+    use crate::span::SourceMap;
+    use crate::value::Value;
+
+    fn consts(source: &str) -> (Resolution, Consts) {
+        let mut sources = SourceMap::new();
+        sources.add("main.nmt", source);
+        let program = Program::new(&mut Lexer::from_source("main.nmt", source));
+        assert!(!program.has_errors(), "{:?}", program.diagnostics);
+        let resolution = resolve(&program, &sources);
+        assert!(!resolution.has_errors(), "{:?}", resolution.diagnostics);
+        let types = crate::semantic::types::check(&program, &resolution, &sources);
+        let consts = check(&program, &resolution, &types, &sources);
+        (resolution, consts)
+    }
+
+    fn value<'a>(resolution: &Resolution, consts: &'a Consts, name: &str) -> Option<&'a Value> {
+        let id = resolution.symbols.iter().position(|symbol| symbol.name == name)?;
+        consts.values.get(&id)
+    }
+
+    #[test]
+    fn folds_constants() {
+        let (resolution, consts) = consts(concat!(
+            "@size u32 :: width * 2 + 1;\n",
+            "@width : 1 << 3;\n",
+            "@scale : -1.5 * 2f;\n",
+            "@counter = 0;\n",
+            "fun main(a u32) {\n",
+            "    static mask u32 :: size - 1 == 16 && !false;\n",
+            "    const local : a + 1;\n",
+            "}\n",
+        ));
+        assert!(consts.diagnostics.is_empty(), "{:?}", consts.diagnostics);
+        assert_eq!(value(&resolution, &consts, "size"), Some(&Value::Int(17)));
+        assert_eq!(value(&resolution, &consts, "scale"), Some(&Value::Float(-3.0)));
+        assert_eq!(value(&resolution, &consts, "mask"), Some(&Value::Bool(true)));
+        assert_eq!(value(&resolution, &consts, "counter"), None);
+        assert_eq!(value(&resolution, &consts, "local"), None);
+    }
+
+    #[test]
+    fn rejects_assignments_to_constants() {
+        let (_, consts) = consts(concat!(
+            "@limit u32 :: 10;\n",
+            "fun main() {\n",
+            "    @step : 2;\n",
+            "    @total = 0;\n",
+            "    total += step;\n",
+            "    step += 1;\n",
+            "    if total > 1 { limit = 3; }\n",
+            "}\n",
+        ));
+        let found: Vec<(&str, usize)> = consts.diagnostics.iter().map(|d| (d.message.as_str(), d.line)).collect();
+        assert_eq!(found, vec![("cannot assign to constant `step`", 6), ("cannot assign to static `limit`", 7)]);
+        assert!(consts.diagnostics.iter().all(|d| d.code == Some("E0400")));
+        assert_eq!(consts.diagnostics[1].notes, vec!["`limit` is defined at 1:1"]);
+    }
+
+    #[test]
+    fn rejects_field_assignments_to_constants() {
+        let source = "struct P { x int }\n@c : P { x: 1 };\nfun main() { c.x = 2; }\n";
+        let (_, consts) = consts(source);
+        let found: Vec<(Option<&str>, &str, &str)> = consts.diagnostics.iter()
+            .map(|d| (d.code, d.message.as_str(), &source[d.span.start..d.span.end]))
+            .collect();
+        assert_eq!(found, vec![(Some("E0400"), "cannot assign to constant `c`", "c")]);
+    }
+
+    #[test]
+    fn rejects_element_assignments_to_statics() {
+        let source = "static p @[i; 2] :: [1, 2];\nfun main() { p[0] = 1; }\n";
+        let (_, consts) = consts(source);
+        let found: Vec<(Option<&str>, &str, &str)> = consts.diagnostics.iter()
+            .map(|d| (d.code, d.message.as_str(), &source[d.span.start..d.span.end]))
+            .collect();
+        assert_eq!(found, vec![(Some("E0400"), "cannot assign to static `p`", "p")]);
+    }
+
+    #[test]
+    fn statics_cannot_call_functions() {
+        let source = "fun one() int { return 1; }\nfun main() { static s int :: one(); }\n";
+        let (_, consts) = consts(source);
+        let found: Vec<(Option<&str>, &str)> = consts.diagnostics.iter().map(|d| (d.code, &source[d.span.start..d.span.end])).collect();
+        assert_eq!(found, vec![(Some("E0401"), "one()")]);
+        assert_eq!(consts.diagnostics[0].message, "the value of static `s` is not known at compile time");
+    }
+
+    #[test]
+    fn folds_array_statics() {
+        let (resolution, consts) = consts("static primes @[i; 4] :: [2, 3, 5, 7];\n@first : primes[0] + 1;\n");
+        assert!(consts.diagnostics.is_empty(), "{:?}", consts.diagnostics);
+        let primes = Value::Array(vec![Value::Int(2), Value::Int(3), Value::Int(5), Value::Int(7)]);
+        assert_eq!(value(&resolution, &consts, "primes"), Some(&primes));
+        assert_eq!(value(&resolution, &consts, "first"), Some(&Value::Int(3)));
+    }
+
+    #[test]
+    fn statics_need_compile_time_values() {
+        let source = concat!(
+            "@seed = 4;\n",
+            "@a u32 :: seed + 1;\n",
+            "@b u32 :: 1 << 70;\n",
+            "@c : d;\n",
+            "@d : c;\n",
+            "fun main(x u32) { static e u32 :: x; }\n",
+        );
+        let (_, consts) = consts(source);
+        let found: Vec<(Option<&str>, &str)> = consts.diagnostics.iter()
+            .map(|d| (d.code, &source[d.span.start..d.span.end]))
+            .collect();
+        assert_eq!(found, vec![
+            (Some("E0401"), "seed"), (Some("E0402"), "1 << 70"), (Some("E0402"), "c"), (Some("E0401"), "x"),
+        ]);
+        assert!(consts.has_errors());
+    }
+}
+
+#[cfg(test)]
+mod interpreter_tests {
+    use crate::interpreter;
+    use crate::tests::common::analyze_ok;
+    use crate::value::Value;
+
+    // runs main, returns what it returned and the printed lines
+    fn run(source: &str) -> (Result<Option<Value>, String>, String) {
+        let (program, analysis, sources) = analyze_ok(source);
+        let mut out = Vec::new();
+        let result = interpreter::run(&program, &analysis, &sources, &mut out).map_err(|d| d.message);
+        (result, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn evaluates_every_assign_op() {
+        let (result, out) = run(concat!(
+            "@base u32 :: 6;\n",
+            "fun main() u32 {\n",
+            "    @a = base;\n",
+            "    a += 10; # a;\n",
+            "    a -= 1; # a;\n",
+            "    a *= 2; # a;\n",
+            "    a /= 4; # a;\n",
+            "    a %= 4; # a;\n",
+            "    a |= 8; # a;\n",
+            "    a &= 12; # a;\n",
+            "    a ^= 5; # a;\n",
+            "    @s = \"n\"; # s == \"n\" && 'a' < 'b';\n",
+            "    @f = 1.5; f *= -2f; # f;\n",
+            "    return a;\n",
+            "}\n",
+        ));
+        assert_eq!(result, Ok(Some(Value::Int(13))));
+        assert_eq!(out, "16\n15\n30\n7\n3\n11\n8\n13\ntrue\n-3.0\n");
+    }
+
+    #[test]
+    fn runs_control_flow() {
+        let (result, out) = run(concat!(
+            "fun main() {\n",
+            "    @n = 0;\n",
+            "    loop {\n",
+            "        n += 1;\n",
+            "        if n % 2 == 0 { continue; } else if n > 6 { break; }\n",
+            "        # n;\n",
+            "    }\n",
+            "    while true { for i 0 to 10 { if i == 2 { return; } # i; } }\n",
+            "}\n",
+        ));
+        assert_eq!(result, Ok(None));
+        assert_eq!(out, "1\n3\n5\n0\n1\n");
+        let (_, out) = run("@word = \"abc\";\nfun main() { for c in word { # c; } @x float; # x; }");
+        assert_eq!(out, "a\nb\nc\n0.0\n");
+    }
+
+    #[test]
+    fn keeps_64_bit_literals() {
+        let (result, out) = run("fun main() { # 3.14159265358979; # 123456789012345.5; # 5000000000 * 2; # 7fffffffffffffffb16; }");
+        assert_eq!(result, Ok(None));
+        assert_eq!(out, "3.14159265358979\n123456789012345.5\n10000000000\n9223372036854775807\n");
+    }
+
+    #[test]
+    fn assigns_nested_fields() {
+        let (result, out) = run(concat!(
+            "struct Point { x int, y int }\n",
+            "struct Line { start Point, end Point, name str }\n",
+            "@origin : Point { y: 0, x: 0 };\n",
+            "fun main() int {\n",
+            "    @l = Line { end: Point { x: 3, y: 4 }, start: origin, name: \"diag\" };\n",
+            "    l.end.x *= 2;\n",
+            "    l.start.y = -1;\n",
+            "    # l;\n",
+            "    # origin;\n",
+            "    return l.end.x + l.start.y;\n",
+            "}\n",
+        ));
+        assert_eq!(result, Ok(Some(Value::Int(5))));
+        assert_eq!(out, "Line { start: Point { x: 0, y: -1 }, end: Point { x: 6, y: 4 }, name: \"diag\" }\nPoint { x: 0, y: 0 }\n");
+    }
+
+    #[test]
+    fn zero_initialises_structs() {
+        let (_, out) = run(concat!(
+            "struct Point { x int, y int }\n",
+            "struct Line { start Point, end Point, name str }\n",
+            "fun main() { @zero Line; # zero; # zero.start == Point { x: 0, y: 0 }; }\n",
+        ));
+        assert_eq!(out, "Line { start: Point { x: 0, y: 0 }, end: Point { x: 0, y: 0 }, name: \"\" }\ntrue\n");
+    }
+
+    #[test]
+    fn matches_enum_variants() {
+        let (result, out) = run(concat!(
+            "enum Shape { Empty, Circle(float), Square(int) }\n",
+            "@origin : Shape::Square(2);\n",
+            "fun main() {\n",
+            "    @s = Shape::Circle(2.0);\n",
+            "    @e Shape;\n",
+            "    # s; # origin; # e;\n",
+            "    match s { Shape::Circle(r) => { # r * r; } _ => { # \"other\"; } }\n",
+            "    # match origin { Shape::Square(n) => n * 10, _ => 0 };\n",
+            "    # s == Shape::Circle(2.0);\n",
+            "}\n",
+        ));
+        assert_eq!(result, Ok(None));
+        assert_eq!(out, "Shape::Circle(2.0)\nShape::Square(2)\nShape::Empty\n4.0\n20\ntrue\n");
+    }
+
+    #[test]
+    fn assigns_array_elements() {
+        let (result, out) = run(concat!(
+            "struct Grid { cells [[int; 2]; 2] }\n",
+            "static primes @[i; 4] :: [2, 3, 5, 7];\n",
+            "fun main() int {\n",
+            "    @xs [int; 3] = [1, 2, 3];\n",
+            "    xs[1] = 20;\n",
+            "    xs[0] += primes[3];\n",
+            "    @g = Grid { cells: [[1, 2], [3, 4]] };\n",
+            "    g.cells[1][0] = 30;\n",
+            "    @empty [str];\n",
+            "    for x in xs { # x; }\n",
+            "    # g;\n",
+            "    # empty;\n",
+            "    # [\"a\", \"b\"] == [\"a\", \"b\"];\n",
+            "    return xs[0] + xs[2];\n",
+            "}\n",
+        ));
+        assert_eq!(result, Ok(Some(Value::Int(11))));
+        assert_eq!(out, "8\n20\n3\nGrid { cells: [[1, 2], [30, 4]] }\n[]\ntrue\n");
+    }
+
+    #[test]
+    fn evaluates_an_assigned_index_once() {
+        let (_, out) = run(concat!(
+            "@n = 0;\n",
+            "fun next() int { n += 1; return n - 1; }\n",
+            "fun main() {\n",
+            "    @xs = [1, 2, 3];\n",
+            "    xs[next()] += 10;\n",
+            "    xs[next()] = 20;\n",
+            "    # xs;\n",
+            "    # n;\n",
+            "}\n",
+        ));
+        assert_eq!(out, "[11, 20, 3]\n2\n");
+    }
+
+    #[test]
+    fn calls_recursive_functions() {
+        let (result, out) = run(concat!(
+            "fun fib(n int) int {\n",
+            "    if n < 2 { return n; }\n",
+            "    return fib(n - 1) + fib(n - 2);\n",
+            "}\n",
+            "fun half(x float) float { return x / 2.0; }\n",
+            "fun show(label str, value int) { # label; # value; }\n",
+            "fun main() int {\n",
+            "    show(\"fib\", fib(10));\n",
+            "    # half(5.0);\n",
+            "    return fib(7);\n",
+            "}\n",
+        ));
+        assert_eq!(result, Ok(Some(Value::Int(13))));
+        assert_eq!(out, "fib\n55\n2.5\n");
+    }
+
+    #[test]
+    fn limits_call_depth() {
+        // on the default stack of a test thread
+        let source = "fun down(n int) int { return down(n + 1); }\nfun main() { # down(0); }\n";
+        let (program, analysis, sources) = analyze_ok(source);
+        let error = interpreter::run(&program, &analysis, &sources, Vec::new()).unwrap_err();
+        assert_eq!((error.code, error.message.as_str()), (Some("E0500"), "too many nested calls, the limit is 1000"));
+        assert_eq!(&source[error.span.start..error.span.end], "down(n + 1)");
+    }
+
+    #[test]
+    fn reports_runtime_errors() {
+        let (result, out) = run("fun main() { @a = 1; # a; # a << 64; # a; }");
+        assert_eq!(result, Err("shift by 64 is out of range".to_string()));
+        assert_eq!(out, "1\n");
+        let (result, _) = run("fun main() { @big = 2147483647 * 2147483647 * 4; }");
+        assert_eq!(result, Err("integer overflow".to_string()));
+        let source = "fun main() { @xs = [1, 2, 3]; @i = 3; # xs[i]; }";
+        let (program, analysis, sources) = analyze_ok(source);
+        let error = interpreter::run(&program, &analysis, &sources, Vec::new()).unwrap_err();
+        assert_eq!((error.code, error.message.as_str()), (Some("E0500"), "index 3 is out of bounds for an array of length 3"));
+        assert_eq!(&source[error.span.start..error.span.end], "xs[i]");
+    }
+}
+
+#[cfg(test)]
+mod repl_tests {
+    use std::io::Cursor;
+
+    use crate::repl::{Entry, Session, repl};
+    use crate::semantic::types::Ty;
+    use crate::value::Value;
+
+    // feeds the lines to the repl, returns (stdout, stderr without prompts)
+    fn feed(lines: &str) -> (String, String) {
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        assert_eq!(repl(Cursor::new(lines.to_string()), &mut out, &mut err, false), 0);
+        let err = String::from_utf8(err).unwrap().replace("... ", "").replace("> ", "");
+        (String::from_utf8(out).unwrap(), err.trim().to_string())
+    }
+
+    #[test]
+    fn keeps_declarations_between_inputs() {
+        let (out, err) = feed(concat!(
+            "@count = 1;\n",
+            "fun double(n u32) u32 {\n",
+            "    return n * 2;\n",
+            "}\n",
+            "count += 4; # count;\n",
+            "count * 10\n",
+            "for i 0 to 2 { count += i; }\n",
+            "count\n",
+        ));
+        assert_eq!(err, "");
+        assert_eq!(out, "5\n50\n6\n");
+    }
+
+    #[test]
+    fn reports_errors_and_keeps_going() {
+        let (out, err) = feed("@a = 1;\na + \"s\"\n@a = ;\n1 / 0\n:what\na\n");
+        assert_eq!(out, "1\n");
+        let codes: Vec<&str> = err.lines().filter(|line| line.starts_with("error")).collect();
+        assert_eq!(codes, vec![
+            "error[E0302]: `+` cannot be applied to `int` and `str`",
+            "error[E0102]: expected expression found (SEMICOLON)",
+            "error[E0500]: division by zero",
+            "error: unknown command `:what`, `:help` lists them",
+        ]);
+        assert!(err.contains("<repl:2>:1:1"));
+    }
+
+    #[test]
+    fn meta_commands() {
+        let mut session = Session::new();
+        assert!(session.eval("@s = \"hi\"; @f = 2.5;", &mut Vec::new()).is_empty());
+        assert_eq!(session.values.get("f"), Some(&Value::Float(2.5)));
+        assert_eq!(session.type_of("f * 2f > 1f"), Ok(Ty::Bool));
+        assert_eq!(session.type_of("s"), Ok(Ty::Str));
+        assert!(session.type_of("s + 1").is_err());
+        let tokens = session.tokens("# s;").unwrap();
+        assert_eq!(tokens.len(), 3);
+        assert!(matches!(session.parse("@x = 1; x").unwrap().as_slice(), [Entry::Item(_), Entry::Stmt(_)]));
+        let (out, _) = feed(":type 1 < 2\n:tokens ok\n:quit\n:type 1\n");
+        assert_eq!(out, "bool\nToken (Identifier) \"ok\" <repl:2>:1:1\n");
+    }
+}
+
+#[cfg(test)]
+mod ir_tests {
+    use crate::ast::Op;
+    use crate::ir::lower::lower;
+    use crate::ir::verify::verify;
+    use crate::ir::{Block, Inst, IrFunc, Module, Operand, Terminator};
+    use crate::semantic::types::Ty;
+    use crate::tests::common::analyze_ok;
+    use crate::value::Value;
+
+    fn module(source: &str) -> Module {
+        let (program, analysis, _) = analyze_ok(source);
+        lower(&program, &analysis)
+    }
+
+    #[test]
+    fn lowers_to_blocks_and_registers() {
+        let module = module(concat!(
+            "@step : 2;\n",
+            "fun count(n u32) u32 {\n",
+            "    @total = 0;\n",
+            "    while total < n { total += step; }\n",
+            "    return total;\n",
+            "}\n",
+        ));
+        assert_eq!(module.to_string(), concat!(
+            "global @0 step int = 2\n",
+            "\n",
+            "func count(%0 int) int {\n",
+            "  $0 n int\n",
+            "  $1 total int\n",
+            "bb0:\n",
+            "  store $0, %0\n",
+            "  store $1, 0\n",
+            "  jmp bb1\n",
+            "bb1:\n",
+            "  %1 = load int $1\n",
+            "  %2 = load int $0\n",
+            "  %3 = lt bool %1, %2\n",
+            "  br %3, bb2, bb3\n",
+            "bb2:\n",
+            "  %4 = load int $1\n",
+            "  %5 = add int %4, 2\n",
+            "  store $1, %5\n",
+            "  jmp bb1\n",
+            "bb3:\n",
+            "  %6 = load int $1\n",
+            "  ret %6\n",
+            "}\n",
+        ));
+        assert_eq!(verify(&module), Ok(()));
+    }
+
+    #[test]
+    fn lowered_programs_verify() {
+        let module = module(concat!(
+            "@words = \"ab\";\n",
+            "@count = 1 + 1;\n",
+            "fun main() {\n",
+            "    static seen u32 :: 0;\n",
+            "    for c in words { if c == 'a' || count > 1 && !false { continue; } # c; }\n",
+            "    for i 0 to count { loop { if i > 0 { return; } break; } }\n",
+            "    @f = -1.5;\n",
+            "    # f * 2f;\n",
+            "}\n",
+            "fun sign(x u32) u32 { if x > 0 { return 1; } else { return 0; } }\n",
+        ));
+        assert_eq!(verify(&module), Ok(()));
+        let names: Vec<&str> = module.funcs.iter().map(|func| func.name.as_str()).collect();
+        assert_eq!(names, vec![".init", "main", "sign"]);
+        assert_eq!(module.globals[2].name, "main.seen");
+        // the merge block after two returns is dropped
+        assert_eq!(module.func("sign").unwrap().blocks.len(), 3);
+    }
+
+    #[test]
+    fn verifier_rejects_broken_functions() {
+        let int = |value| Operand::Const(Value::Int(value));
+        let func = IrFunc {
+            name: "broken".to_string(),
+            params: vec![Ty::Int],
+            ret: Ty::Int,
+            slots: Vec::new(),
+            regs: vec![Ty::Int, Ty::Int, Ty::Bool],
+            blocks: vec![
+                Block {
+                    insts: vec![Inst::Binary { dst: 2, ty: Ty::Bool, op: Op::Less, lhs: Operand::Reg(0), rhs: int(1) }],
+                    term: Some(Terminator::Branch { cond: Operand::Reg(2), then: 1, otherwise: 2 }),
+                },
+                Block {
+                    insts: vec![Inst::Copy { dst: 1, ty: Ty::Int, src: int(1) }],
+                    term: Some(Terminator::Jump(2)),
+                },
+                Block {
+                    insts: vec![Inst::Copy { dst: 2, ty: Ty::Bool, src: Operand::Const(Value::Bool(true)) }],
+                    term: Some(Terminator::Return(Some(Operand::Reg(1)))),
+                },
+                Block { insts: vec![Inst::Binary { dst: 1, ty: Ty::Int, op: Op::Add, lhs: int(1), rhs: Operand::Const(Value::Float(1.0)) }], term: Some(Terminator::Return(None)) },
+            ],
+        };
+        let errors = verify(&Module { globals: Vec::new(), funcs: vec![func] }).unwrap_err();
+        assert_eq!(errors, vec![
+            "broken bb3: can not be reached from the entry",
+            "broken bb2: %2 is assigned more than once",
+            "broken bb3: %1 is assigned more than once",
+            "broken bb2: %1 is used where it may not be defined",
+            "broken bb3: `%1 = add int 1, 1.0` has operands of the wrong type",
+            "broken bb3: `ret` has an operand of the wrong type",
+        ]);
+        let open = IrFunc { name: "open".to_string(), params: Vec::new(), ret: Ty::Void, slots: Vec::new(), regs: Vec::new(),
+            blocks: vec![Block { insts: Vec::new(), term: Some(Terminator::Jump(1)) }] };
+        assert_eq!(verify(&Module { globals: Vec::new(), funcs: vec![open] }).unwrap_err(), vec!["open bb0: jumps to bb1 which does not exist"]);
+    }
+}
+
+#[cfg(test)]
+mod codegen_tests {
+    use std::process::Command;
+
+    use crate::codegen::{build, x86_64::emit};
+    use crate::interpreter;
+    use crate::ir::lower::lower;
+    use crate::tests::common::analyze_ok;
+
+    // (assembly, output of the interpreter)
+    fn compile(source: &str) -> (Result<String, String>, String) {
+        let (program, analysis, sources) = analyze_ok(source);
+        let mut out = Vec::new();
+        let _ = interpreter::run(&program, &analysis, &sources, &mut out);
+        (emit(&lower(&program, &analysis)), String::from_utf8(out).unwrap())
+    }
+
+    // builds and runs the executable, None without an assembler and linker
+    fn execute(name: &str, asm: &str) -> Option<(i32, String, String)> {
+        if Command::new("as").arg("--version").output().is_err() || Command::new("ld").arg("--version").output().is_err() {
+            return None;
+        }
+        let path = std::env::temp_dir().join(format!("nemet_codegen_{}_{}", std::process::id(), name));
+        build(asm, &path).unwrap();
+        let output = Command::new(&path).output().unwrap();
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(path.with_extension("s"));
+        let text = |bytes: Vec<u8>| String::from_utf8(bytes).unwrap();
+        Some((output.status.code().unwrap_or(-1), text(output.stdout), text(output.stderr)))
+    }
+
+    #[test]
+    fn emits_functions_globals_and_strings() {
+        let (asm, _) = compile("@greeting : \"hé\";\nfun twice(x int) int { return x * 2; }\nfun main() { # greeting; }");
+        let asm = asm.unwrap();
+        assert!(asm.starts_with(".intel_syntax noprefix\n"));
+        assert!(asm.contains("nm.twice:\n    push rbp\n    mov rbp, rsp\n"));
+        assert!(asm.contains("    mov qword ptr [rbp - 16], rdi\n"));
+        assert!(asm.contains("nm.g.0: .quad .Lstr.0\n"));
+        assert!(asm.contains(".Lstr.0: .quad 2\n    .long 104, 233\n"));
+        assert!(asm.contains("call nemet_print_str"));
+        assert_eq!(compile("fun start() { }").0.unwrap_err(), "there is no `main` function");
+    }
+
+    #[test]
+    fn executables_print_what_the_interpreter_prints() {
+        let (asm, expected) = compile(concat!(
+            "@base = 40;\n",
+            "@total = base + 2;\n",
+            "fun main() {\n",
+            "    # total; # -7 / 2; # -7 % 2; # 1 << 40;\n",
+            "    # 2.5 * -3.0; # 7.5 % 2.0; # 1.0 / 0.0;\n",
+            "    # 10000000000000.0; # -50000000000000.0;\n",
+            "    # 'z'; # \"señal\"; # \"a\" != \"b\"; # 1 < 2 && !(2 <= 1);\n",
+            "    @sum = 0;\n",
+            "    for i 0 to 10 { if i == 7 { break; } sum += i; }\n",
+            "    # sum;\n",
+            "    for c in \"ok\" { # c; }\n",
+            "}\n",
+        ));
+        let Some((code, out, _)) = execute("prints", &asm.unwrap()) else { return };
+        assert_eq!(code, 0);
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn executables_run_enums() {
+        let (asm, expected) = compile(concat!(
+            "enum Shape { Empty, Circle(float), Square(int) }\n",
+            "enum Token { Word(str), Letter(char), Flag(bool), Wrapped(Shape) }\n",
+            "@origin : Token::Wrapped(Shape::Square(2));\n",
+            "fun wrap(s Shape) Token { return Token::Wrapped(s); }\n",
+            "fun main() {\n",
+            "    @s = Shape::Circle(2.0);\n",
+            "    @e Shape;\n",
+            "    # s; # e; # origin; # wrap(s);\n",
+            "    # Token::Word(\"say \\\"hi\\\"\\n\"); # Token::Letter('\\''); # Token::Flag(false);\n",
+            "    # match s { Shape::Circle(r) => r * r, _ => 0.0 };\n",
+            "    # s == Shape::Circle(2.0); # s != Shape::Circle(2.0); # wrap(e) == wrap(Shape::Empty);\n",
+            "    # Token::Word(\"a\") == Token::Word(\"b\"); # Shape::Circle(0.0 / 0.0) == Shape::Circle(0.0 / 0.0);\n",
+            "    for i 0 to 100000 { s = Shape::Square(i); }\n",
+            "    # s;\n",
+            "}\n",
+        ));
+        assert!(expected.starts_with("Shape::Circle(2.0)\nShape::Empty\nToken::Wrapped(Shape::Square(2))\n"));
+        let Some((code, out, _)) = execute("enums", &asm.unwrap()) else { return };
+        assert_eq!(code, 0);
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn runtime_errors_exit_with_one() {
+        let cases = [
+            ("div", "@zero = 0; # 1 / zero;", "error: division by zero\n"),
+            ("overflow", "@big = 2147483647; # big * big * big;", "error: integer overflow\n"),
+            ("shift", "@by = 64; # 1 << by;", "error: shift is out of range\n"),
+            ("index", "@s = \"ab\"; @i = 2; # s[i];", "error: index out of bounds\n"),
+        ];
+        for (name, body, expected) in cases {
+            let (asm, _) = compile(&format!("fun main() {{ # 1; {} # 2; }}", body));
+            let Some((code, out, err)) = execute(&format!("panics_{}", name), &asm.unwrap()) else { return };
+            assert_eq!((code, out.as_str(), err.as_str()), (1, "1\n", expected));
+        }
+    }
+}
+
+#[cfg(test)]
+mod bytecode_tests {
+    use crate::ast::Op;
+    use crate::bytecode::compile::compile;
+    use crate::bytecode::{Bytecode, Function, Instr, decode, encode, vm};
+    use crate::interpreter;
+    use crate::ir::lower::lower;
+    use crate::tests::common::analyze_ok;
+    use crate::value::Value;
+
+    // (bytecode, output of the interpreter)
+    fn compiled(source: &str) -> (Bytecode, String) {
+        let (program, analysis, sources) = analyze_ok(source);
+        let mut out = Vec::new();
+        let _ = interpreter::run(&program, &analysis, &sources, &mut out);
+        (compile(&lower(&program, &analysis)), String::from_utf8(out).unwrap())
+    }
+
+    fn run(bytecode: &Bytecode) -> (Result<Option<Value>, String>, String) {
+        let mut out = Vec::new();
+        let result = vm::run(bytecode, &mut out);
+        (result, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn compiles_to_a_stack_machine() {
+        let (bytecode, _) = compiled("@limit : 3;\nfun main() { @i = 0; while i < limit { i += 1; } # i * 2; }");
+        assert_eq!(bytecode.constants, vec![Value::Int(3), Value::Int(0), Value::Int(1), Value::Int(2)]);
+        assert_eq!(bytecode.globals[0].name, "limit");
+        let main = &bytecode.funcs[0];
+        assert_eq!((main.name.as_str(), main.params), ("main", 0));
+        assert_eq!(&main.code[..2], &[Instr::Const(1), Instr::Store(main.locals - 1)]);
+        assert!(main.code.contains(&Instr::Binary(Op::Less)));
+        assert!(main.code.iter().any(|instr| matches!(instr, Instr::JumpIfFalse(_))));
+        assert_eq!(main.code.last(), Some(&Instr::RetVoid));
+        assert_eq!(decode(&encode(&bytecode)), Ok(bytecode));
+    }
+
+    #[test]
+    fn runs_like_the_interpreter() {
+        let (bytecode, expected) = compiled(concat!(
+            "@base = 40;\n",
+            "@total = base + 2;\n",
+            "fun main() {\n",
+            "    # total; # -7 % 2; # 0.1 + 0.2; # 'z'; # \"señal\"; # 1 < 2 && !(2 <= 1);\n",
+            "    @sum = 0;\n",
+            "    for i 0 to 10 { if i == 7 { break; } sum += i; }\n",
+            "    # sum;\n",
+            "    for c in \"ok\" { # c; }\n",
+            "}\n",
+        ));
+        let bytecode = decode(&encode(&bytecode)).unwrap();
+        assert_eq!(run(&bytecode), (Ok(None), expected));
+        let (bytecode, _) = compiled("fun main() { # 1; @zero = 0; # 1 / zero; }");
+        let (result, out) = run(&bytecode);
+        assert_eq!(out, "1\n");
+        assert!(result.unwrap_err().starts_with("division by zero in `main`"));
+    }
+
+    #[test]
+    fn keeps_enum_constants() {
+        let (bytecode, expected) = compiled(concat!(
+            "enum Shape { Empty, Circle(float) }\n",
+            "@e Shape;\n",
+            "fun main() { # e; # match Shape::Circle(1.5) { Shape::Circle(r) => r, _ => 0.0 }; }\n",
+        ));
+        let empty = Value::Enum { name: "Shape".to_string(), variant: "Empty".to_string(), payload: None };
+        assert!(bytecode.constants.contains(&empty));
+        let bytecode = decode(&encode(&bytecode)).unwrap();
+        assert_eq!(run(&bytecode), (Ok(None), expected));
+    }
+
+    #[test]
+    fn calls_recursive_functions() {
+        let (bytecode, expected) = compiled(concat!(
+            "fun fib(n int) int { if n < 2 { return n; } return fib(n - 1) + fib(n - 2); }\n",
+            "fun main() int { # fib(10); return fib(7); }\n",
+        ));
+        assert!(bytecode.funcs[1].code.contains(&Instr::Call(0)));
+        assert_eq!(run(&bytecode), (Ok(Some(Value::Int(13))), expected));
+    }
+
+    #[test]
+    fn calls_and_rejects_bad_files() {
+        // twice(21) by hand
+        let bytecode = Bytecode {
+            constants: vec![Value::Int(21), Value::Int(2)],
+            globals: Vec::new(),
+            funcs: vec![
+                Function { name: "twice".to_string(), params: 1, locals: 1, code: vec![
+                    Instr::Load(0), Instr::Const(1), Instr::Binary(Op::Multi), Instr::Ret,
+                ]},
+                Function { name: "main".to_string(), params: 0, locals: 0, code: vec![
+                    Instr::Const(0), Instr::Call(0), Instr::Print, Instr::RetVoid,
+                ]},
+            ],
+        };
+        assert_eq!(run(&bytecode), (Ok(None), "42\n".to_string()));
+        let bytes = encode(&bytecode);
+        assert_eq!(decode(b"NBD\0").unwrap_err(), "not a nemet bytecode file");
+        assert_eq!(decode(&[&bytes[..4], &[9, 0]].concat()).unwrap_err(), "bytecode version 9 is not supported, expected 1");
+        assert_eq!(decode(&bytes[..bytes.len() - 1]).unwrap_err(), "unexpected end of file");
+        let mut broken = bytecode.clone();
+        broken.funcs[1].code[1] = Instr::Call(7);
+        assert_eq!(decode(&encode(&broken)).unwrap_err(), "main 1: `call 7` refers to something that does not exist");
+    }
+}
+
+#[cfg(test)]
+mod module_tests {
+    use std::path::{Path, PathBuf};
+
+    use crate::ast::{Expr, Node, Stmt};
+    use crate::cli::{Options, run};
+    use crate::frontend::{Frontend, cross_check, cross_check_file};
+    use crate::modules::load;
+    use crate::span::{SourceMap, Span};
+
+    // writes the files into a fresh directory, returns the path of the first
+    fn files(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("nemet_modules_{}_{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        for (path, source) in files {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, source).unwrap();
+        }
+        dir.join(files[0].0)
+    }
+
+    // loads the file and its includes with both front-ends, returns the codes of the diagnostics
+    fn codes(path: &Path) -> Vec<&'static str> {
+        let path = path.to_string_lossy();
+        let source = std::fs::read_to_string(&*path).unwrap();
+        let program = load(&path, &source, &mut SourceMap::new(), |path, source, file_id| cross_check_file(path, source, file_id).unwrap());
+        program.diagnostics.iter().map(|d| d.code.unwrap()).collect()
+    }
+
+    #[test]
+    fn both_front_ends_parse_includes_and_qualified_names() {
+        let source = concat!(
+            "include \"math.nmt\";\n",
+            "fun main() {\n",
+            "    @p math.Point = math.Point { x: math.pi };\n",
+            "    match math.Shape::Dot { math.Shape::Dot => {} _ => {} }\n",
+            "}\n",
+        );
+        let program = cross_check("main.nmt", source).unwrap();
+        let Node::Include { var } = &program.body[0] else { panic!("expected an include") };
+        assert_eq!((var.path.as_str(), var.span), ("math.nmt", Span::new(0, 0, 19)));
+        let Node::Func { var } = &program.body[1] else { panic!("expected a function") };
+        let Stmt::VariableDelclear { var } = &var.block[0] else { panic!("expected a variable") };
+        assert_eq!(var.kind.name, "math.Point");
+        assert!(matches!(&var.init_value, Some(Expr::StructLiteral { ident, .. }) if ident == "math.Point"));
+        let program = Frontend::Pest.parse("main.nmt", "include math;");
+        assert_eq!(program.diagnostics[0].code, Some("E0100"));
+    }
+
+    #[test]
+    fn reports_missing_cyclic_and_clashing_includes() {
+        let path = files("errors", &[
+            ("main.nmt", "include \"missing.nmt\";\ninclude \"a.nmt\";\ninclude \"b.nmt\";\ninclude \"lib/a.nmt\";\n"),
+            ("a.nmt", "include \"b.nmt\";\n"),
+            ("b.nmt", "include \"a.nmt\";\n"),
+            ("lib/a.nmt", "fun f() {}\n"),
+        ]);
+        assert_eq!(codes(&path), vec!["E0107", "E0108", "E0109"]);
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+        // the same file included twice and through another file is loaded once
+        let path = files("dedupe", &[
+            ("main.nmt", "include \"a.nmt\";\ninclude \"./a.nmt\";\ninclude \"b.nmt\";\nfun main() {}\n"),
+            ("a.nmt", "fun f() {}\n"),
+            ("b.nmt", "include \"a.nmt\";\n"),
+        ]);
+        let path = path.to_string_lossy();
+        let mut sources = SourceMap::new();
+        let source = std::fs::read_to_string(&*path).unwrap();
+        let program = load(&path, &source, &mut sources, |path, source, file_id| Frontend::Manual.parse_file(path, source, file_id));
+        assert!(program.diagnostics.is_empty(), "{:?}", program.diagnostics);
+        assert_eq!(sources.len(), 3);
+        assert_eq!(program.body.iter().filter(|node| matches!(node, Node::Func { .. })).count(), 2);
+        std::fs::remove_dir_all(Path::new(&*path).parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn runs_qualified_names_from_included_files() {
+        let path = files("run", &[
+            ("main.nmt", concat!(
+                "include \"util.nmt\";\n",
+                "@count = 10;\n",
+                "fun main() {\n",
+                "    @s util.Shape = util.Shape::Circle(util.pi);\n",
+                "    match s { util.Shape::Circle(r) => { # r; } _ => {} }\n",
+                "    util.count = util.count + 5;\n",
+                "    # util.count + count;\n",
+                "    # util.nothing;\n",
+                "}\n",
+            )),
+            ("util.nmt", "static pi @i :: 3;\n@count = 1;\nenum Shape { Dot, Circle(int) }\n"),
+        ]);
+        let run_file = |command: &str| {
+            let options = Options::parse(vec![command.to_string(), path.display().to_string()]).unwrap();
+            let (mut out, mut err) = (Vec::new(), Vec::new());
+            let code = run(&options, &mut out, &mut err, false);
+            (code, String::from_utf8(out).unwrap(), String::from_utf8(err).unwrap())
+        };
+        let (code, _, err) = run_file("check");
+        assert_eq!(code, 1);
+        assert!(err.starts_with("error[E0200]: cannot find `nothing` in module `util`"), "{}", err);
+        let source = std::fs::read_to_string(&path).unwrap().replace("    # util.nothing;\n", "");
+        std::fs::write(&path, source).unwrap();
+        assert_eq!(run_file("run"), (0, "3\n16\n".to_string(), String::new()));
+        assert_eq!(run_file("vm").1, "3\n16\n");
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
     }
 }
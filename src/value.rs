@@ -0,0 +1,219 @@
+use std::fmt::Display;
+
+use crate::ast::{Literal, Op};
+use crate::semantic::types::Ty;
+
+// a value while evaluating a program, used for compile time constants and
+// by the interpreter. integers are 64 bit whatever their declared type
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Char(char),
+    Str(String),
+    // fields in the order the struct declares them
+    Struct { name: String, fields: Vec<(String, Value)> },
+    // a variant of an enum, with the payload it holds
+    Enum { name: String, variant: String, payload: Option<Box<Value>> },
+    // the elements of an array or slice
+    Array(Vec<Value>),
+}
+impl Value {
+    pub fn from_literal(literal: &Literal) -> Self {
+        match literal {
+            Literal::Int(value) => Self::Int(*value),
+            Literal::Float(value) => Self::Float(*value),
+            Literal::Str(value) => Self::Str(value.clone()),
+            Literal::Char(value) => Self::Char(*value),
+            Literal::Bool(value) => Self::Bool(*value),
+        }
+    }
+
+    // value of a variable declared without one, TypeInfo::zero also knows structs and enums
+    pub fn zero(ty: &Ty) -> Self {
+        match ty {
+            Ty::Float => Self::Float(0.0),
+            Ty::Bool => Self::Bool(false),
+            Ty::Char => Self::Char('\0'),
+            Ty::Str => Self::Str(String::new()),
+            _ => Self::Int(0),
+        }
+    }
+
+    pub fn ty(&self) -> Ty {
+        match self {
+            Self::Int(_) => Ty::Int,
+            Self::Float(_) => Ty::Float,
+            Self::Bool(_) => Ty::Bool,
+            Self::Char(_) => Ty::Char,
+            Self::Str(_) => Ty::Str,
+            Self::Struct { name, .. } => Ty::Struct(name.clone()),
+            Self::Enum { name, .. } => Ty::Enum(name.clone()),
+            // an empty array does not know its element
+            Self::Array(elements) => Ty::Array(Box::new(elements.first().map_or(Ty::Error, Value::ty)), elements.len()),
+        }
+    }
+
+    pub fn field(&self, ident: &str) -> Option<&Value> {
+        let Self::Struct { fields, .. } = self else {
+            return None;
+        };
+        fields.iter().find(|(field, _)| field == ident).map(|(_, value)| value)
+    }
+
+    pub fn field_mut(&mut self, ident: &str) -> Option<&mut Value> {
+        let Self::Struct { fields, .. } = self else {
+            return None;
+        };
+        fields.iter_mut().find(|(field, _)| field == ident).map(|(_, value)| value)
+    }
+
+    // the element of an array or the char of a string at index
+    pub fn index(&self, index: i64) -> Result<Value, String> {
+        let i = usize::try_from(index).ok();
+        match self {
+            Self::Array(elements) => {
+                i.and_then(|i| elements.get(i)).cloned().ok_or_else(|| out_of_bounds(index, "an array", elements.len()))
+            },
+            Self::Str(value) => {
+                i.and_then(|i| value.chars().nth(i)).map(Self::Char).ok_or_else(|| out_of_bounds(index, "a string", value.chars().count()))
+            },
+            _ => Err(format!("cannot index into {}",self)),
+        }
+    }
+
+    pub fn index_mut(&mut self, index: i64) -> Result<&mut Value, String> {
+        let Self::Array(elements) = self else {
+            return Err(format!("cannot assign to an element of {}",self));
+        };
+        let len = elements.len();
+        usize::try_from(index).ok().and_then(|i| elements.get_mut(i)).ok_or_else(|| out_of_bounds(index, "an array", len))
+    }
+
+    pub fn binary(op: Op, left: &Value, right: &Value) -> Result<Value, String> {
+        use Value::*;
+        let value = match (left, right) {
+            (Int(a), Int(b)) => {
+                let (a, b) = (*a, *b);
+                let checked = match op {
+                    Op::Add => a.checked_add(b),
+                    Op::Sub => a.checked_sub(b),
+                    Op::Multi => a.checked_mul(b),
+                    Op::Divide | Op::Mod if b == 0 => return Err("division by zero".to_string()),
+                    Op::Divide => a.checked_div(b),
+                    Op::Mod => a.checked_rem(b),
+                    Op::Lsh | Op::Rsh if !(0..64).contains(&b) => return Err(format!("shift by {} is out of range",b)),
+                    Op::Lsh => Some(a << b),
+                    Op::Rsh => Some(a >> b),
+                    Op::And => Some(a & b),
+                    Op::Or => Some(a | b),
+                    Op::Xor => Some(a ^ b),
+                    _ => return compare(op, a.cmp(&b)),
+                };
+                Int(checked.ok_or("integer overflow")?)
+            },
+            (Float(a), Float(b)) => match op {
+                Op::Add => Float(a + b),
+                Op::Sub => Float(a - b),
+                Op::Multi => Float(a * b),
+                Op::Divide => Float(a / b),
+                Op::Mod => Float(a % b),
+                _ => match a.partial_cmp(b) {
+                    Some(ordering) => return compare(op, ordering),
+                    // NaN is only unequal to everything
+                    None => Bool(op == Op::NotEq),
+                },
+            },
+            (Bool(a), Bool(b)) => match op {
+                Op::And | Op::AndAnd => Bool(a & b),
+                Op::Or | Op::OrOr => Bool(a | b),
+                Op::Xor => Bool(a ^ b),
+                _ => return compare(op, a.cmp(b)),
+            },
+            (Char(a), Char(b)) => return compare(op, a.cmp(b)),
+            (Str(a), Str(b)) => return compare(op, a.cmp(b)),
+            (Struct { .. }, Struct { .. }) | (Enum { .. }, Enum { .. }) | (Array(_), Array(_)) if matches!(op, Op::Eq | Op::NotEq) => {
+                Bool((left == right) == (op == Op::Eq))
+            },
+            _ => return Err(format!("`{}` cannot be applied to {} and {}",op,left,right)),
+        };
+        Ok(value)
+    }
+
+    pub fn unary(op: Op, operand: &Value) -> Result<Value, String> {
+        match (op, operand) {
+            (Op::Sub, Self::Int(value)) => value.checked_neg().map(Self::Int).ok_or("integer overflow".to_string()),
+            (Op::Sub, Self::Float(value)) => Ok(Self::Float(-value)),
+            (Op::Add, Self::Int(_) | Self::Float(_)) => Ok(operand.clone()),
+            (Op::Not, Self::Int(value)) => Ok(Self::Int(!value)),
+            (Op::Not, Self::Bool(value)) => Ok(Self::Bool(!value)),
+            _ => Err(format!("`{}` cannot be applied to {}",op,operand)),
+        }
+    }
+
+    // strings and chars inside a struct, enum or array are quoted
+    fn nested(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Str(value) => write!(f,"{:?}",value),
+            Self::Char(value) => write!(f,"{:?}",value),
+            value => write!(f,"{}",value),
+        }
+    }
+}
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Int(value) => write!(f,"{}",value),
+            Self::Float(value) => write!(f,"{:?}",value),
+            Self::Bool(value) => write!(f,"{}",value),
+            Self::Char(value) => write!(f,"{}",value),
+            Self::Str(value) => write!(f,"{}",value),
+            Self::Struct { name, fields } => {
+                write!(f,"{} {{",name)?;
+                for (i, (field, value)) in fields.iter().enumerate() {
+                    write!(f,"{}{}: ",if i == 0 { " " } else { ", " },field)?;
+                    value.nested(f)?;
+                }
+                write!(f,"{}}}",if fields.is_empty() { "" } else { " " })
+            },
+            Self::Enum { name, variant, payload } => {
+                write!(f,"{}::{}",name,variant)?;
+                if let Some(payload) = payload {
+                    write!(f,"(")?;
+                    payload.nested(f)?;
+                    write!(f,")")?;
+                }
+                Ok(())
+            },
+            Self::Array(elements) => {
+                write!(f,"[")?;
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        write!(f,", ")?;
+                    }
+                    element.nested(f)?;
+                }
+                write!(f,"]")
+            },
+        }
+    }
+}
+
+fn out_of_bounds(index: i64, what: &str, len: usize) -> String {
+    format!("index {} is out of bounds for {} of length {}",index,what,len)
+}
+
+fn compare(op: Op, ordering: std::cmp::Ordering) -> Result<Value, String> {
+    use std::cmp::Ordering::*;
+    let result = match op {
+        Op::Eq => ordering == Equal,
+        Op::NotEq => ordering != Equal,
+        Op::Less => ordering == Less,
+        Op::LessEq => ordering != Greater,
+        Op::More => ordering == Greater,
+        Op::MoreEq => ordering != Less,
+        _ => return Err(format!("`{}` is not a comparison",op)),
+    };
+    Ok(Value::Bool(result))
+}